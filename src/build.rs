@@ -0,0 +1,363 @@
+//! Compiling Gizmo files from Rust, mainly for build scripts
+//!
+//! # Example
+//! A `build.rs` that compiles `src/math.gizmo` into an object file and links
+//! it into the crate:
+//! ```no_run
+//! use std::path::Path;
+//! use gizmo::build::{compile_file, OutKind, Options};
+//!
+//! let obj = compile_file(Path::new("src/math.gizmo"), OutKind::Object, &Options::default()).unwrap();
+//! println!("cargo:rerun-if-changed=src/math.gizmo");
+//! println!("cargo:rustc-link-arg={}", obj.display());
+//! ```
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use crate::driver::Driver;
+use crate::edition::Edition;
+
+/// An enum to store each kind of output
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OutKind {
+    /// llvm ir ('.ll')
+    Ir,
+
+    /// An object file ('.o')
+    Object,
+
+    /// A linked executable
+    Executable,
+}
+
+/// Stores the options for a build
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Edition of the language to compile with
+    pub edition: Edition,
+
+    /// Directory to put the output in
+    /// Defaults to $OUT_DIR when run from a build script, or the temp
+    /// directory otherwise
+    pub out_dir: Option<PathBuf>,
+
+    /// The 'llc' to use
+    /// Defaults to $LLC, then the first 'llc' found on the PATH
+    pub llc: Option<PathBuf>,
+
+    /// The 'opt' that optimizes the ir at '-O2' and '-O3'
+    /// Defaults to $OPT, then the first 'opt' found on the PATH
+    pub opt: Option<PathBuf>,
+
+    /// The 'lli' that runs programs
+    /// Defaults to $LLI, then the first 'lli' found on the PATH
+    pub lli: Option<PathBuf>,
+
+    /// The linker to use for executables
+    /// Defaults to $CC, then 'cc'
+    pub linker: Option<PathBuf>,
+
+    /// Whether or not heap values are freed by the Boehm garbage collector,
+    /// which has to be installed to link against
+    pub gc: bool,
+
+    /// The llvm triple to compile for
+    /// Defaults to the host
+    pub target: Option<String>,
+
+    /// Whether or not debug info is generated
+    pub debug: bool,
+
+    /// How much the code is optimized, from 0 to 3
+    /// '-O2' and up also run llvm's optimizer over the ir
+    pub opt_level: u32,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {edition: Edition::DEFAULT, out_dir: None, llc: None, opt: None, lli: None, linker: None, gc: false, target: None, debug: false, opt_level: 1}
+    }
+}
+
+/// The Boehm garbage collector's library, which is loaded to run programs
+/// built with '--gc'
+#[cfg(target_os = "macos")]
+const GC_LIBRARY: &str = "libgc.dylib";
+#[cfg(not(target_os = "macos"))]
+const GC_LIBRARY: &str = "libgc.so.1";
+
+/// An error from a build
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BuildError(pub String);
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Compiles a Gizmo file and returns the path of the output
+/// The output is named after the input file, i.e., 'math.gizmo' becomes
+/// 'math.o' for an object file
+pub fn compile_file(path: &Path, kind: OutKind, options: &Options) -> Result<PathBuf, BuildError> {
+    let code = fs::read_to_string(path).map_err(|e| BuildError(format!("Couldn't read '{}': {}", path.display(), e)))?;
+
+    // Compile the file
+    let mut driver = Driver::new();
+    driver.edition = options.edition;
+    driver.file = path.display().to_string();
+    driver.gc = options.gc;
+    driver.target = options.target.clone();
+    driver.debug = options.debug;
+    driver.opt_level = options.opt_level;
+    // Every error and warning is kept in the message, since a build script
+    // has nowhere else to show them
+    let ir = driver.compile(code).map_err(|errors| {
+        let mut text = format!("Couldn't compile '{}' because of {} error(s)\n", path.display(), errors.len());
+        for diagnostic in driver.diagnostics.iter() {
+            text.push_str(diagnostic.to_human(None).as_str());
+        }
+        BuildError(text)
+    })?;
+
+    // Find where the output goes
+    let out_dir = match &options.out_dir {
+        Some(dir) => dir.clone(),
+        None => env::var_os("OUT_DIR").map(PathBuf::from).unwrap_or(env::temp_dir())
+    };
+    fs::create_dir_all(&out_dir).map_err(|e| BuildError(format!("Couldn't create '{}': {}", out_dir.display(), e)))?;
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("a".to_string());
+    let ir_path = out_dir.join(format!("{}.ll", stem));
+    let obj_path = out_dir.join(format!("{}.o", stem));
+    if kind != OutKind::Ir && cfg!(feature = "llvm") {
+        // The llvm backend makes the object file without going through a
+        // file
+        llvm(ir.as_str(), &obj_path, options)?;
+    } else {
+        fs::write(&ir_path, ir).map_err(|e| BuildError(format!("Couldn't write '{}': {}", ir_path.display(), e)))?;
+        if kind == OutKind::Ir {
+            return Ok(ir_path);
+        }
+
+        // Optimize the ir, then turn it into an object file
+        if options.opt_level >= 2 {
+            opt(&ir_path, &ir_path, options)?;
+        }
+        llc(&ir_path, &obj_path, options)?;
+        let _ = fs::remove_file(&ir_path);
+    }
+    if kind == OutKind::Object {
+        return Ok(obj_path);
+    }
+
+    // Link the object file into an executable
+    let exe_path = out_dir.join(stem);
+    link(&obj_path, &exe_path, options)?;
+    let _ = fs::remove_file(&obj_path);
+    return Ok(exe_path);
+}
+
+/// Calls 'llc' to turn llvm ir into an object file
+pub fn llc(ir_path: &Path, obj_path: &Path, options: &Options) -> Result<(), BuildError> {
+    let llc = options.llc.clone().or_else(|| find_tool("llc", "LLC")).ok_or(BuildError("Couldn't find 'llc', set $LLC to its path".to_string()))?;
+    let mut command = Command::new(llc);
+    command.arg(ir_path).arg(format!("-O{}", options.opt_level)).args(["--relocation-model=pic", "-filetype=obj", "-o"]).arg(obj_path);
+    return run(command);
+}
+
+/// Calls 'opt' to run llvm's optimizer over llvm ir, at the level in the
+/// options
+/// The optimized ir can be written over the original
+pub fn opt(ir_path: &Path, out_path: &Path, options: &Options) -> Result<(), BuildError> {
+    let opt = options.opt.clone().or_else(|| find_tool("opt", "OPT")).ok_or(BuildError("Couldn't find 'opt', set $OPT to its path".to_string()))?;
+    let mut command = Command::new(opt);
+    command.arg(format!("-O{}", options.opt_level)).arg("-S").arg(ir_path).arg("-o").arg(out_path);
+    return run(command);
+}
+
+/// Verifies llvm ir and turns it into an object file in process, when the
+/// compiler is built with the 'llvm' feature
+#[cfg(feature = "llvm")]
+pub fn llvm(ir: &str, obj_path: &Path, options: &Options) -> Result<(), BuildError> {
+    return crate::parser::generator::llvm::write_object(ir, obj_path, options.opt_level).map_err(BuildError);
+}
+
+/// Fails, since the compiler was built without the 'llvm' feature
+#[cfg(not(feature = "llvm"))]
+pub fn llvm(_ir: &str, obj_path: &Path, _options: &Options) -> Result<(), BuildError> {
+    return Err(BuildError(format!("Couldn't write '{}', the compiler was built without the 'llvm' feature", obj_path.display())));
+}
+
+/// Runs llvm ir with 'lli', giving back the exit code of the program
+/// The program reads and writes the same standard input and output as the
+/// caller
+pub fn lli(ir_path: &Path, options: &Options) -> Result<i32, BuildError> {
+    let lli = options.lli.clone().or_else(|| find_tool("lli", "LLI")).ok_or(BuildError("Couldn't find 'lli', set $LLI to its path".to_string()))?;
+    let program = lli.display().to_string();
+    let mut command = Command::new(lli);
+    if options.gc {
+        command.arg(format!("--dlopen={}", GC_LIBRARY));
+    }
+    command.arg(format!("-O{}", options.opt_level)).arg(ir_path);
+    let status = command.status().map_err(|e| BuildError(format!("Failed to call {}: {}", program, e)))?;
+    return Ok(status.code().unwrap_or(1));
+}
+
+/// Runs llvm ir in process with llvm's jit, when the compiler is built with
+/// the 'llvm' feature, giving back the exit code of the program
+#[cfg(feature = "llvm")]
+pub fn jit(ir: &str, options: &Options) -> Result<i32, BuildError> {
+    let libraries = if options.gc {vec![GC_LIBRARY]} else {Vec::new()};
+    return crate::parser::generator::llvm::run(ir, &libraries, options.opt_level).map_err(BuildError);
+}
+
+/// Fails, since the compiler was built without the 'llvm' feature
+#[cfg(not(feature = "llvm"))]
+pub fn jit(_ir: &str, _options: &Options) -> Result<i32, BuildError> {
+    return Err(BuildError("Couldn't run the program, the compiler was built without the 'llvm' feature".to_string()));
+}
+
+/// Calls the linker to turn an object file into an executable
+pub fn link(obj_path: &Path, exe_path: &Path, options: &Options) -> Result<(), BuildError> {
+    let linker = options.linker.clone().or_else(|| env::var_os("CC").map(PathBuf::from)).unwrap_or(PathBuf::from("cc"));
+    let mut command = Command::new(linker);
+    command.arg(obj_path).arg("-o").arg(exe_path);
+
+    // The math builtins can end up calling into libm
+    command.arg("-lm");
+    if options.gc {
+        command.arg("-lgc");
+    }
+    return run(command);
+}
+
+/// Finds an llvm tool like 'llc', either from an environment variable or on
+/// the PATH
+/// Versioned names like 'llc-14' are tried after the plain name
+fn find_tool(name: &str, var: &str) -> Option<PathBuf> {
+    if let Some(tool) = env::var_os(var) {
+        return Some(PathBuf::from(tool));
+    }
+    let paths: Vec<PathBuf> = env::split_paths(&env::var_os("PATH")?).collect();
+    for path in paths.iter() {
+        if path.join(name).is_file() {
+            return Some(path.join(name));
+        }
+    }
+    for version in (10..=20).rev() {
+        for path in paths.iter() {
+            let tool = path.join(format!("{}-{}", name, version));
+            if tool.is_file() {
+                return Some(tool);
+            }
+        }
+    }
+    return None;
+}
+
+/// Runs a command, returning its error output if it fails
+fn run(mut command: Command) -> Result<(), BuildError> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command.output().map_err(|e| BuildError(format!("Failed to call {}: {}", program, e)))?;
+    if !output.status.success() {
+        return Err(BuildError(format!("{} failed:\n{}", program, String::from_utf8_lossy(&output.stderr))));
+    }
+    return Ok(());
+}
+
+/// Finds 'lli' for the tests that run programs, noting the skip where llvm
+/// isn't installed so it doesn't pass silently
+#[cfg(test)]
+fn lli_for_tests() -> Option<PathBuf> {
+    let lli = find_tool("lli", "LLI");
+    if lli.is_none() {
+        eprintln!("Skipping a program run, 'lli' isn't installed");
+    }
+    return lli;
+}
+
+/// Compiles a program and runs it with 'lli', feeding it 'input', giving back
+/// its exit code and what it wrote, or nothing where llvm isn't installed
+#[cfg(test)]
+pub(crate) fn run_program(code: &str, input: &str) -> Option<(i32, String)> {
+    use std::io::Write;
+    use std::process::Stdio;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let lli = lli_for_tests()?;
+    let ir = Driver::new().compile(code.to_string()).unwrap();
+
+    // Tests run at the same time, so each program gets its own file
+    let path = env::temp_dir().join(format!("gizmo-run-{}-{}.ll", std::process::id(), RUNS.fetch_add(1, Ordering::SeqCst)));
+    fs::write(&path, ir).unwrap();
+    let mut child = Command::new(lli).arg(&path).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).unwrap();
+    return Some((output.status.code().unwrap_or(1), String::from_utf8_lossy(&output.stdout).to_string()));
+}
+
+/// Compiles a program and checks that its ir has each of 'fragments', then
+/// runs it where llvm is installed and checks that it exits normally after
+/// writing 'output'
+/// The ir is given back for checks the fragments can't make
+#[cfg(test)]
+pub(crate) fn check_program(code: &str, input: &str, fragments: &[&str], output: &str) -> String {
+    let ir = Driver::new().compile(code.to_string()).unwrap();
+    for fragment in fragments {
+        assert!(ir.contains(fragment), "the ir is missing {:?}:\n{}", fragment, ir);
+    }
+    if let Some(run) = run_program(code, input) {
+        assert_eq!(run, (0, output.to_string()));
+    }
+    return ir;
+}
+
+#[test]
+fn test_compile_file_ir() {
+    let dir = env::temp_dir().join(format!("gizmo-build-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let src = dir.join("hello.gizmo");
+    fs::write(&src, "write(\"hi\");\n").unwrap();
+
+    let options = Options {out_dir: Some(dir.clone()), ..Options::default()};
+    let out = compile_file(&src, OutKind::Ir, &options).unwrap();
+    assert_eq!(out, dir.join("hello.ll"));
+    assert!(fs::read_to_string(&out).unwrap().contains("define i32 @main()"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_compile_file_errors() {
+    let dir = env::temp_dir().join(format!("gizmo-build-errors-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let src = dir.join("bad.gizmo");
+    fs::write(&src, "let x = y;\n").unwrap();
+
+    let options = Options {out_dir: Some(dir.clone()), ..Options::default()};
+    let e = compile_file(&src, OutKind::Ir, &options).unwrap_err();
+    assert!(e.0.contains("because of 1 error(s)"));
+    assert!(e.0.contains("E0008"));
+    assert!(e.0.contains("bad.gizmo:1:"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_lli() {
+    let ir = Driver::new().compile("func main(): int {\n    ret 3;\n}\n".to_string()).unwrap();
+    let path = env::temp_dir().join(format!("gizmo-lli-test-{}.ll", std::process::id()));
+    fs::write(&path, ir).unwrap();
+
+    // Only checked where llvm is installed
+    if lli_for_tests().is_some() {
+        assert_eq!(lli(&path, &Options::default()), Ok(3));
+    }
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,224 @@
+use crate::parser::lexer::Lexer;
+use crate::parser::lexer::token::Token;
+use crate::parser::lexer::token::TokenType;
+use crate::parser::lexer::error::error;
+use crate::parser::lexer::error::ErrorType;
+use crate::parser::lexer::error::Error;
+use crate::parser::lexer::error::WarningGroup;
+use crate::parser::Parser;
+use crate::parser::generator::Generator;
+use crate::pass::CompilerPass;
+use crate::pass::Diagnostics;
+use crate::edition::Edition;
+use crate::fold::ConstantFolding;
+use crate::semantics::Semantics;
+
+/// Stores information for a compiler driver
+pub struct Driver {
+    /// Custom passes that run over the ast before code generation
+    pub passes: Vec<Box<dyn CompilerPass>>,
+
+    /// Edition of the language to compile with
+    pub edition: Edition,
+
+    /// Groups of warnings that are printed
+    pub warnings: Vec<WarningGroup>,
+
+    /// Groups of warnings that are turned into errors
+    pub denied: Vec<WarningGroup>,
+
+    /// Name of the file being compiled, shown in errors
+    pub file: String,
+
+    /// Whether or not indexes are checked when the program runs
+    pub bounds_checks: bool,
+
+    /// Whether or not heap values are freed by the garbage collector
+    pub gc: bool,
+
+    /// The llvm triple to compile for, or None for the host
+    pub target: Option<String>,
+
+    /// Whether or not debug info is generated, so debuggers can step through
+    /// the source
+    pub debug: bool,
+
+    /// Whether or not each statement is written as a comment in the ir
+    pub ir_comments: bool,
+
+    /// How much the code is optimized, from 0 to 3
+    /// The compiler's own folding and dead code removal are off at 0
+    pub opt_level: u32,
+
+    /// Errors and warnings found by the last compile, in the order they were
+    /// found, for the caller to print
+    pub diagnostics: Vec<Error>,
+}
+
+impl Default for Driver {
+    fn default() -> Driver {
+        Driver::new()
+    }
+}
+
+impl Driver {
+    /// Constructs a new driver with no custom passes
+    pub fn new() -> Driver {
+        Driver {passes: Vec::new(), edition: Edition::DEFAULT, warnings: WarningGroup::DEFAULT.to_vec(), denied: Vec::new(), file: "<input>".to_string(), bounds_checks: false, gc: false, target: None, debug: false, ir_comments: false, opt_level: 1, diagnostics: Vec::new()}
+    }
+
+    /// Registers a custom pass
+    /// Passes run in the order they were registered
+    pub fn register_pass(&mut self, pass: Box<dyn CompilerPass>) -> &mut Self {
+        self.passes.push(pass);
+        self
+    }
+
+    /// Compiles the given code
+    /// The errors are given back if there were any, and every error and
+    /// warning that should be printed is kept in 'diagnostics'
+    pub fn compile(&mut self, code: String) -> Result<String, Vec<Error>> {
+        self.diagnostics.clear();
+
+        // Create a lexer
+        let mut lexer: Lexer = Lexer {pos: 0, code: code, col: 0, docs: Vec::new(), file: self.file.clone(), allows: Vec::new(), source: None};
+
+        // Lex the input, stopping at the first error
+        let tokens: Vec<Token> = match lexer.lex() {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                self.diagnostics = errors.clone();
+                return Err(errors);
+            }
+        };
+        let allows = lexer.allows.clone();
+
+        // The lines are written as comments and shown in errors from the
+        // generator, from the same source map as the tokens
+        let source = lexer.source();
+
+        // Create a parser
+        let mut parser: Parser = Parser {pos: 0, tokens: tokens, id_c: 0, function: None, delimiters: Vec::new(), edition: self.edition, docs: lexer.docs, errors: Vec::new()};
+
+        // Parse the tokens, stopping after the parser if it found errors
+        let mut ast = match parser.parse() {
+            Ok(ast) => ast,
+            Err(errors) => {
+                self.diagnostics = errors.clone();
+                return Err(errors);
+            }
+        };
+
+        // Check the finished ast, then run the custom passes
+        // Warnings that were turned into errors stop compilation too
+        let mut diagnostics = Diagnostics::new();
+        Semantics::new().run(&mut ast, &mut diagnostics);
+        for pass in self.passes.iter_mut() {
+            pass.run(&mut ast, &mut diagnostics);
+        }
+        self.report(diagnostics.errors.drain(..).collect(), &allows)?;
+
+        // Fold constant expressions
+        if self.opt_level > 0 {
+            ConstantFolding.run(&mut ast, &mut diagnostics);
+        }
+
+        // Create a generator
+        let mut generator = Generator::construct();
+        generator.bounds_checks = self.bounds_checks;
+        generator.gc = self.gc;
+        generator.file = self.file.clone();
+        generator.ir_b.optimize = self.opt_level > 0;
+        if let Some(triple) = &self.target {
+            generator.ir_b.set_target(triple);
+        }
+        if self.debug {
+            generator.enable_debug_info(self.file.as_str());
+        }
+        if self.ir_comments {
+            generator.source = Some(source.clone());
+        }
+
+        // Generate llvm ir for the ast
+        generator.generate(ast);
+        generator.identify(self.file.as_str());
+        generator.destruct();
+
+        // Ir that llc would reject is a bug in the generator, which is
+        // reported at the statement it was generated for
+        for problem in generator.ir_b.problems.iter() {
+            let line = source.line(problem.line.max(1));
+            let col = line.chars().count() - line.trim_start().chars().count();
+            let token = Token {typ: TokenType::Id, value: line.trim().to_string(), lineno: problem.line.max(1), col: col, len: line.trim().chars().count(), source: source.clone()};
+            let statement = generator.statements.get(&problem.line).cloned().unwrap_or("the program".to_string());
+            let mut e = error(ErrorType::InvalidIR, &token);
+            e.note(problem.message.as_str());
+            e.note(format!("The instruction generated for {} is '{}'", statement, problem.instruction).as_str());
+            e.help("This is a bug in the compiler, please report it along with this statement");
+            diagnostics.push(e);
+        }
+        self.report(diagnostics.errors, &allows)?;
+        return Ok(generator.ir_b.code);
+    }
+
+    /// Keeps the errors and warnings found by a stage in 'diagnostics'
+    /// Warnings in groups that are turned off, or on lines with an
+    /// 'allow(...)' comment, are left out, and ones in denied groups become
+    /// errors
+    /// Gives back the errors found so far if there were any
+    fn report(&mut self, found: Vec<Error>, allows: &[(usize, WarningGroup)]) -> Result<(), Vec<Error>> {
+        for mut error in found {
+            if let Some(group) = error.group().filter(|_| error.is_warning()) {
+                if !self.warnings.contains(&group) || allows.contains(&(error.line(), group)) {
+                    continue;
+                }
+                if self.denied.contains(&group) {
+                    // '-Werror' denies every group, '-Werror=<group>' only one
+                    let flag = if WarningGroup::ALL.iter().all(|g| self.denied.contains(g)) {
+                        "-Werror".to_string()
+                    } else {
+                        format!("-Werror={}", group.name())
+                    };
+                    error.deny(flag.as_str());
+                }
+            }
+            self.diagnostics.push(error);
+        }
+        let errors: Vec<Error> = self.diagnostics.iter().filter(|e| !e.is_warning()).cloned().collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        return Ok(());
+    }
+}
+
+#[test]
+fn test_errors_returned() {
+    let errors = Driver::new().compile("let x = y;\nlet z = 1 / 0;\n".to_string()).unwrap_err();
+    assert_eq!(errors.iter().map(|e| e.code()).collect::<Vec<&str>>(), vec!["E0008", "E0023"]);
+}
+
+#[test]
+fn test_denied_warnings() {
+    let code = "func unused() {\n}\n".to_string();
+    let mut driver = Driver::new();
+    assert!(driver.compile(code.clone()).is_ok());
+    assert!(driver.diagnostics[0].is_warning());
+    driver.denied = vec![WarningGroup::Unused];
+    let errors = driver.compile(code.clone()).unwrap_err();
+    assert_eq!(errors[0].code(), "W0001");
+    assert!(errors[0].to_json().contains("because of '-Werror=unused'"));
+    driver.denied = WarningGroup::ALL.to_vec();
+    assert!(driver.compile(code).unwrap_err()[0].to_json().contains("because of '-Werror'"));
+
+    // Each compile only keeps its own diagnostics
+    assert_eq!(driver.diagnostics.len(), 1);
+}
+
+#[test]
+fn test_allow_pragma() {
+    let code = "// gizmo: allow(unused)\nfunc unused() {\n}\nfunc other() { // gizmo: allow(all)\n}\n".to_string();
+    let mut driver = Driver::new();
+    driver.denied = vec![WarningGroup::Unused];
+    assert!(driver.compile(code).is_ok());
+}
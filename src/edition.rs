@@ -0,0 +1,53 @@
+use std::fs;
+use std::path::Path;
+
+/// An enum to store each edition of the language
+/// Newer editions opt in to changes that would break older programs
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Edition {
+    /// The original language
+    E2021,
+
+    /// Semicolons at the end of a line are optional, and every program needs
+    /// a 'main' function
+    E2024,
+}
+
+impl Edition {
+    /// The edition used when none is given
+    pub const DEFAULT: Edition = Edition::E2021;
+
+    /// Finds the edition for a year like "2024"
+    /// Returns None if there is no such edition
+    pub fn from_year(year: &str) -> Option<Edition> {
+        match year.trim() {
+            "2021" => Some(Edition::E2021),
+            "2024" => Some(Edition::E2024),
+            _ => None
+        }
+    }
+
+    /// Reads the edition out of a 'gizmo.toml' manifest
+    /// # Example
+    /// edition = "2024"
+    pub fn from_manifest(path: &Path) -> Option<Result<Edition, String>> {
+        let manifest = fs::read_to_string(path).ok()?;
+        for line in manifest.lines() {
+            let mut parts = line.splitn(2, '=');
+            if parts.next().map(|key| key.trim()) != Some("edition") {
+                continue;
+            }
+            let value = parts.next().unwrap_or("").trim().trim_matches('"');
+            return Some(Edition::from_year(value).ok_or(format!("Unknown edition '{}' in {}", value, path.display())));
+        }
+        return None;
+    }
+}
+
+#[test]
+fn test_from_year() {
+    assert_eq!(Edition::from_year("2021"), Some(Edition::E2021));
+    assert_eq!(Edition::from_year("2024"), Some(Edition::E2024));
+    assert_eq!(Edition::from_year("1999"), None);
+    assert!(Edition::E2024 > Edition::DEFAULT);
+}
@@ -0,0 +1,96 @@
+use crate::parser::ast::Node;
+use crate::parser::ast::Expr;
+use crate::parser::types::Type;
+use crate::pass::Visitor;
+use crate::pass::walk_node;
+use crate::pass::walk_expr;
+
+/// Finds the variables of a function whose arrays can outlive it
+/// A value escapes when it is returned, stored into a struct or an element,
+/// or stored into a 'ref' argument or a global, and so does anything stored
+/// into a variable that escapes
+pub struct Escapes {
+    /// Ids in ir of the variables that escape
+    pub vars: Vec<String>,
+
+    /// Values stored into variables, as (variable, variables in the value)
+    flows: Vec<(String, Vec<String>)>,
+}
+
+impl Escapes {
+    /// Finds the variables of a function body that escape, starting from its
+    /// 'ref' arguments
+    pub fn find(body: &Node, refs: Vec<String>) -> Vec<String> {
+        let mut escapes = Escapes {vars: refs, flows: Vec::new()};
+        escapes.visit_node(&mut body.clone());
+
+        // Keep following the stores until nothing new escapes
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (var, values) in escapes.flows.iter() {
+                if !escapes.vars.contains(var) && !var.starts_with('@') {
+                    continue;
+                }
+                for value in values.iter() {
+                    if !escapes.vars.contains(value) {
+                        escapes.vars.push(value.clone());
+                        changed = true;
+                    }
+                }
+            }
+        }
+        return escapes.vars;
+    }
+}
+
+/// Collects the variables holding arrays or structs that are used in an
+/// expression
+/// Calls are included, since a function can give back what it is passed
+fn vars_in(expr: &Expr) -> Vec<String> {
+    struct Vars(Vec<String>);
+    impl Visitor for Vars {
+        fn visit_expr(&mut self, expr: &mut Expr) {
+            if let Expr::Id(_, Type::Array(_) | Type::Struct(_), gen_id, _) = expr {
+                self.0.push(gen_id.clone());
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    let mut vars = Vars(Vec::new());
+    vars.visit_expr(&mut expr.clone());
+    return vars.0;
+}
+
+impl Visitor for Escapes {
+    fn visit_node(&mut self, node: &mut Node) {
+        match node {
+            // Functions declared inside are looked at when they are generated
+            Node::FuncDecl {..} => return,
+            Node::Ret {expr, ..} => self.vars.extend(vars_in(expr)),
+            Node::Let {gen_id, expr, ..} => self.flows.push((gen_id.clone(), vars_in(expr))),
+            Node::Assign {id: Expr::Id(_, _, gen_id, _), expr, ..} => self.flows.push((gen_id.clone(), vars_in(expr))),
+            Node::Assign {expr, ..} => self.vars.extend(vars_in(expr)),
+            _ => {}
+        }
+        walk_node(self, node);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        if let Expr::NewStruct {fields, ..} = expr {
+            for field in fields.iter() {
+                self.vars.extend(vars_in(field));
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+#[test]
+fn test_escaping_array() {
+    // Only the returned array has to live on the heap
+    let code = "func make(): int[] {\n    let a = [1, 2];\n    let b = [3];\n    write(b[0]);\n    ret a;\n}\nwrite(make()[1]);\n";
+    let ir = crate::build::check_program(code, "", &["alloca [1 x i32]"], "3\n2\n");
+    assert_eq!(ir.matches("call i8* @malloc").count(), 1);
+}
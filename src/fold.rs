@@ -0,0 +1,157 @@
+use crate::parser::ast::Expr;
+use crate::parser::generator::dec_constant;
+use crate::parser::generator::dec_value;
+use crate::pass::CompilerPass;
+use crate::pass::Diagnostics;
+use crate::pass::TypedAst;
+use crate::pass::Visitor;
+use crate::pass::walk_expr;
+
+/// Folds operations on constants into a single constant
+/// # Example
+/// `2 + 3 * 4` -> `14`
+pub struct ConstantFolding;
+
+impl CompilerPass for ConstantFolding {
+    fn name(&self) -> &str {
+        "constant-folding"
+    }
+
+    fn run(&mut self, ast: &mut TypedAst, _diagnostics: &mut Diagnostics) {
+        for node in ast.iter_mut() {
+            self.visit_node(node);
+        }
+    }
+}
+
+impl Visitor for ConstantFolding {
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        // Fold the children first so their results can be folded here
+        walk_expr(self, expr);
+        if let Some(folded) = fold(expr) {
+            *expr = folded;
+        }
+    }
+}
+
+/// Returns the constant an expression folds into, if it folds
+fn fold(expr: &Expr) -> Option<Expr> {
+    match expr {
+        Expr::BinaryOperator {oper, left, right, ..} => match (&**left, &**right) {
+            (Expr::Int(l), Expr::Int(r)) => {
+                let l = l.parse::<i32>().ok()?;
+                let r = r.parse::<i32>().ok()?;
+                match oper.as_str() {
+                    "+"  => Some(Expr::Int(l.wrapping_add(r).to_string())),
+                    "-"  => Some(Expr::Int(l.wrapping_sub(r).to_string())),
+                    "*"  => Some(Expr::Int(l.wrapping_mul(r).to_string())),
+                    "/" if r != 0 => Some(Expr::Dec(dec_value(l as f64 / r as f64))),
+                    "==" => Some(Expr::Bool(l == r)),
+                    "!=" => Some(Expr::Bool(l != r)),
+                    "<"  => Some(Expr::Bool(l < r)),
+                    ">"  => Some(Expr::Bool(l > r)),
+                    "<=" => Some(Expr::Bool(l <= r)),
+                    ">=" => Some(Expr::Bool(l >= r)),
+                    _ => None
+                }
+            },
+            (Expr::Dec(l), Expr::Dec(r)) => {
+                let l = dec_constant(l)?;
+                let r = dec_constant(r)?;
+                match oper.as_str() {
+                    "+"  => Some(Expr::Dec(dec_value(l + r))),
+                    "-"  => Some(Expr::Dec(dec_value(l - r))),
+                    "*"  => Some(Expr::Dec(dec_value(l * r))),
+                    "/" if r != 0.0 => Some(Expr::Dec(dec_value(l / r))),
+                    "==" => Some(Expr::Bool(l == r)),
+                    "!=" => Some(Expr::Bool(l != r)),
+                    "<"  => Some(Expr::Bool(l < r)),
+                    ">"  => Some(Expr::Bool(l > r)),
+                    "<=" => Some(Expr::Bool(l <= r)),
+                    ">=" => Some(Expr::Bool(l >= r)),
+                    _ => None
+                }
+            },
+            (Expr::Bool(l), Expr::Bool(r)) => match oper.as_str() {
+                "and" => Some(Expr::Bool(*l && *r)),
+                "or"  => Some(Expr::Bool(*l || *r)),
+                "=="  => Some(Expr::Bool(l == r)),
+                "!="  => Some(Expr::Bool(l != r)),
+                _ => None
+            },
+            _ => None
+        },
+        Expr::UnaryOperator {oper, child, ..} => match (oper.as_str(), &**child) {
+            ("-", Expr::Int(i)) => Some(Expr::Int(i.parse::<i32>().ok()?.wrapping_neg().to_string())),
+            ("-", Expr::Dec(d)) => Some(Expr::Dec(dec_value(-dec_constant(d)?))),
+            ("not", Expr::Bool(b)) => Some(Expr::Bool(!b)),
+            _ => None
+        },
+        Expr::FuncCall {id, args, ..} if id == "len" && args.len() == 1 => match &*args[0] {
+            Expr::Str(s) => Some(Expr::Int(s.len().to_string())),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+#[test]
+fn test_fold() {
+    use crate::parser::types::Type;
+    use crate::parser::lexer::token::Token;
+    use crate::parser::lexer::token::Span;
+    use crate::parser::lexer::token::TokenType;
+    use crate::parser::lexer::source::SourceMap;
+
+    let token = Token {typ: TokenType::Plus, value: "+".to_string(), lineno: 1, col: 0, len: 1, source: std::rc::Rc::new(SourceMap::new("", "+"))};
+    let int = |i: &str| Box::new(Expr::Int(i.to_string()));
+    let binary = |oper: &str, left: Box<Expr>, right: Box<Expr>| Box::new(Expr::BinaryOperator {oper: oper.to_string(), left: left, right: right, token: token.clone(), span: Span::token(&token)});
+
+    let mut expr = *binary("+", int("2"), binary("*", int("3"), int("4")));
+    ConstantFolding.visit_expr(&mut expr);
+    assert_eq!(expr, Expr::Int("14".to_string()));
+
+    let mut expr = *binary("<", int("2"), int("3"));
+    ConstantFolding.visit_expr(&mut expr);
+    assert_eq!(expr, Expr::Bool(true));
+
+    // Dividing ints gives a dec
+    let mut expr = *binary("/", int("7"), int("2"));
+    ConstantFolding.visit_expr(&mut expr);
+    assert_eq!(expr, Expr::Dec(dec_value(3.5)));
+
+    let mut expr = *binary("+", Box::new(Expr::Dec("1.5".to_string())), Box::new(Expr::Dec("2.5".to_string())));
+    ConstantFolding.visit_expr(&mut expr);
+    assert_eq!(expr, Expr::Dec(dec_value(4.0)));
+
+    // Large results are written as bits, since llvm can't read '1e20'
+    let ten = || Box::new(Expr::Dec("10000000000.0".to_string()));
+    let mut expr = *binary("*", ten(), ten());
+    ConstantFolding.visit_expr(&mut expr);
+    assert_eq!(expr, Expr::Dec("0x4415AF1D78B58C40".to_string()));
+
+    let mut expr = Expr::FuncCall {id: "len".to_string(), typ: Type::Int, args: vec![Box::new(Expr::Str("abc".to_string()))], span: Span::token(&token), arg_spans: vec![Span::token(&token)]};
+    ConstantFolding.visit_expr(&mut expr);
+    assert_eq!(expr, Expr::Int("3".to_string()));
+
+    // Variables don't fold
+    let mut expr = *binary("+", Box::new(Expr::Id("a".to_string(), Type::Int, "%a.0".to_string(), token.clone())), int("1"));
+    let copy = expr.clone();
+    ConstantFolding.visit_expr(&mut expr);
+    assert_eq!(expr, copy);
+}
+
+#[test]
+fn test_opt_level() {
+    use crate::driver::Driver;
+    let code = "let x = 2 + 3;\nwrite(x * 1);\n".to_string();
+    let ir = Driver::new().compile(code.clone()).unwrap();
+    assert!(ir.contains("@x.0 = global i32 5"));
+
+    // Nothing is folded at '-O0'
+    let mut driver = Driver::new();
+    driver.opt_level = 0;
+    let ir = driver.compile(code).unwrap();
+    assert!(ir.contains("%0 = add i32 2, 3"));
+    assert!(ir.contains("mul i32 %1, 1"));
+}
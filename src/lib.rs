@@ -0,0 +1,16 @@
+//! The Gizmo compiler
+//!
+//! Downstream crates can embed the compiler through `driver::Driver` and hook
+//! their own passes in with `pass::CompilerPass`, or compile Gizmo files from
+//! a build script with `build::compile_file`
+
+pub mod parser;
+pub mod pass;
+pub mod semantics;
+pub mod fold;
+pub mod escape;
+pub mod verify;
+pub mod driver;
+pub mod edition;
+pub mod build;
+pub mod target;
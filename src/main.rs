@@ -1,22 +1,44 @@
-mod parser;
-
 use std::env;
 use std::fs;
-use parser::lexer::Lexer;
-use parser::lexer::token::Token;
-use parser::Parser;
-use parser::generator::Generator;
-use parser::symbol::Scope;
-use parser::symbol::SymbolController;
+use gizmo::driver::Driver;
+use gizmo::edition::Edition;
+use gizmo::build;
+use gizmo::build::BuildError;
+use gizmo::build::Options;
+use gizmo::parser::lexer::error::ErrorType;
+use gizmo::parser::lexer::error::MessageFormat;
+use gizmo::parser::lexer::error::set_color;
+use gizmo::parser::lexer::error::color_from_env;
+use gizmo::parser::lexer::error::WarningGroup;
+use gizmo::parser::lexer::error::Renderer;
+use gizmo::parser::lexer::error::DEFAULT_ERROR_LIMIT;
+use gizmo::parser::lexer::explain::explain;
+use gizmo::parser::lexer::sarif;
+use std::path::Path;
 use std::fs::File;
 use std::io::Write;
-use std::process::Command;
 
 fn main() {
     let mut file_name:      String = String::new();
     let mut out_file_name:  String = String::from("a.out");
     let mut out_ir_name:    String = String::from("a.ll");
     let mut emit_llvm:      bool   = false;
+    let mut edition:        Option<Edition> = None;
+    let mut ir_path:        Option<String> = None;
+    let mut obj_path:       Option<String> = None;
+    let mut save_temps:     bool   = false;
+    let mut warnings:       Vec<WarningGroup> = WarningGroup::DEFAULT.to_vec();
+    let mut denied:         Vec<WarningGroup> = Vec::new();
+    let mut bounds_checks:  bool   = false;
+    let mut gc:             bool   = false;
+    let mut target:         Option<String> = None;
+    let mut debug:          bool   = false;
+    let mut ir_comments:    bool   = false;
+    let mut run:            bool   = false;
+    let mut opt_level:      u32    = 1;
+    let mut message_format: MessageFormat = MessageFormat::Human;
+    let mut error_limit:    usize  = DEFAULT_ERROR_LIMIT;
+    let mut sarif_path:     Option<String> = None;
 
     // Collect the command line arguments into a vector
     let args: Vec<String> = env::args().collect();
@@ -27,8 +49,17 @@ fn main() {
         return;
     }
 
-    // Parse the arguments
+    // Color errors only when they go to a terminal, unless told otherwise
+    set_color(color_from_env());
+
+    // 'gizmoc run file.gizmo' runs the program instead of building it
     let mut arg_num = 1;
+    if args[1] == "run" {
+        run = true;
+        arg_num += 1;
+    }
+
+    // Parse the arguments
     while arg_num < args.len() {
         if !args[arg_num].starts_with('-') {
             // An argument with no '-' is a file
@@ -44,54 +75,300 @@ fn main() {
             // Whether or not to emit llvm
             arg_num += 1;
             emit_llvm = true;
+        } else if args[arg_num] == "--ir-path" {
+            // Where to write the intermediate llvm ir
+            arg_num += 1;
+            ir_path = args.get(arg_num).cloned();
+            arg_num += 1;
+        } else if args[arg_num] == "--obj-path" {
+            // Where to write the intermediate object file
+            arg_num += 1;
+            obj_path = args.get(arg_num).cloned();
+            arg_num += 1;
+        } else if args[arg_num] == "--save-temps" {
+            // Whether or not to keep the intermediate files
+            arg_num += 1;
+            save_temps = true;
+        } else if args[arg_num] == "--bounds-checks" {
+            // Whether or not to check indexes when the program runs
+            arg_num += 1;
+            bounds_checks = true;
+        } else if args[arg_num] == "--gc" {
+            // Whether or not to free heap values with the garbage collector
+            arg_num += 1;
+            gc = true;
+        } else if args[arg_num] == "-g" {
+            // Whether or not to generate debug info
+            arg_num += 1;
+            debug = true;
+        } else if let Some(level) = args[arg_num].strip_prefix("-O") {
+            // How much to optimize
+            arg_num += 1;
+            match level.parse::<u32>() {
+                Ok(level) if level <= 3 => opt_level = level,
+                _ => {
+                    eprintln!("Unknown optimization level '{}'", level);
+                    std::process::exit(1);
+                }
+            }
+        } else if args[arg_num] == "--ir-comments" {
+            // Whether or not to write the statements as comments in the ir
+            arg_num += 1;
+            ir_comments = true;
+        } else if args[arg_num] == "--target" {
+            // The llvm triple to compile for, instead of the host
+            arg_num += 1;
+            match args.get(arg_num) {
+                Some(triple) => target = Some(triple.clone()),
+                None => {
+                    eprintln!("Missing a triple after '--target'");
+                    std::process::exit(1);
+                }
+            }
+            arg_num += 1;
+        } else if args[arg_num] == "-Werror" {
+            // Turn every warning that is printed into an error
+            arg_num += 1;
+            denied = WarningGroup::ALL.to_vec();
+        } else if let Some(name) = args[arg_num].strip_prefix("-Werror=") {
+            // Turn on a group of warnings as errors
+            arg_num += 1;
+            match WarningGroup::from_name(name) {
+                Some(group) => {
+                    if !warnings.contains(&group) {
+                        warnings.push(group);
+                    }
+                    if !denied.contains(&group) {
+                        denied.push(group);
+                    }
+                },
+                None => {
+                    eprintln!("Unknown warning group '{}'", name);
+                    std::process::exit(1);
+                }
+            }
+        } else if let Some(name) = args[arg_num].strip_prefix("-W") {
+            // Turn a group of warnings on, or off with '-Wno-'
+            arg_num += 1;
+            let (name, on) = match name.strip_prefix("no-") {
+                Some(name) => (name, false),
+                None => (name, true)
+            };
+            let groups = match WarningGroup::from_name(name) {
+                Some(group) => vec![group],
+                None if name == "all" => WarningGroup::ALL.to_vec(),
+                None => {
+                    eprintln!("Unknown warning group '{}'", name);
+                    std::process::exit(1);
+                }
+            };
+            for group in groups {
+                warnings.retain(|g| *g != group);
+                if on {
+                    warnings.push(group);
+                }
+            }
+        } else if args[arg_num] == "--no-color" {
+            // Print errors as plain text
+            arg_num += 1;
+            set_color(false);
+        } else if let Some(format) = args[arg_num].strip_prefix("--message-format=") {
+            // How errors are printed
+            arg_num += 1;
+            match format {
+                "human" => message_format = MessageFormat::Human,
+                "json" => message_format = MessageFormat::Json,
+                _ => {
+                    eprintln!("Unknown message format '{}'", format);
+                    std::process::exit(1);
+                }
+            }
+        } else if args[arg_num] == "--error-limit" {
+            // The most errors to print, 0 for no limit
+            arg_num += 1;
+            let limit = args.get(arg_num).cloned().unwrap_or_default();
+            match limit.parse::<usize>() {
+                Ok(limit) => error_limit = limit,
+                Err(_) => {
+                    eprintln!("Invalid error limit '{}'", limit);
+                    std::process::exit(1);
+                }
+            }
+            arg_num += 1;
+        } else if args[arg_num] == "--sarif" {
+            // Where to write a SARIF log of the errors and warnings
+            arg_num += 1;
+            match args.get(arg_num) {
+                Some(path) => sarif_path = Some(path.clone()),
+                None => {
+                    eprintln!("Missing a file after '--sarif'");
+                    std::process::exit(1);
+                }
+            }
+            arg_num += 1;
+        } else if args[arg_num] == "--explain" {
+            // Describe an error code instead of compiling
+            let code = args.get(arg_num + 1).cloned().unwrap_or_default();
+            match ErrorType::from_code(code.as_str()) {
+                Some(typ) => print!("{}", explain(&typ)),
+                None => {
+                    eprintln!("Unknown error code '{}'", code);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        } else if args[arg_num] == "--edition" {
+            // The edition of the language, overriding gizmo.toml
+            arg_num += 1;
+            let year = args.get(arg_num).cloned().unwrap_or_default();
+            edition = Edition::from_year(year.as_str());
+            if edition.is_none() {
+                eprintln!("Unknown edition '{}'", year);
+                std::process::exit(1);
+            }
+            arg_num += 1;
+        } else {
+            eprintln!("Unknown option '{}'", args[arg_num]);
+            std::process::exit(1);
         }
     }
 
+    // Without '--edition', use the edition from gizmo.toml if there is one
+    let edition = match edition {
+        Some(e) => e,
+        None => match Edition::from_manifest(Path::new("gizmo.toml")) {
+            Some(Ok(e)) => e,
+            Some(Err(msg)) => {
+                eprintln!("{}", msg);
+                std::process::exit(1);
+            },
+            None => Edition::DEFAULT
+        }
+    };
+
     // Open the input file
-    let file = fs::read_to_string(file_name).unwrap();
+    let file = fs::read_to_string(file_name.clone()).unwrap();
 
     // Compile the input file and store the llvm ir in 'output'
-    let output = compile(file);
+    let mut driver = Driver::new();
+    driver.edition = edition;
+    driver.warnings = warnings;
+    driver.denied = denied;
+    driver.bounds_checks = bounds_checks;
+    driver.gc = gc;
+    driver.target = target.clone();
+    driver.debug = debug;
+    driver.ir_comments = ir_comments;
+    driver.opt_level = opt_level;
+    driver.file = file_name.clone();
+    let output = driver.compile(file);
 
-    // Open an output file and write to it
-    let mut out_file: File;
-    if emit_llvm == true {
-        out_file = File::create(out_ir_name.clone()).expect("Couldn't create the output file");
-    } else {
-        out_file = File::create("a.ll").expect("Couldn't create the output file");
+    // Print the errors and warnings, leaving out the errors past the limit
+    let mut renderer = Renderer::new(message_format, error_limit);
+    for diagnostic in driver.diagnostics.iter() {
+        if let Some(text) = renderer.render(diagnostic) {
+            eprint!("{}", text);
+        }
     }
-    out_file.write_all((&output).as_bytes()).expect("Couldn't write to the output file");
-    
-    if emit_llvm == false {
-        // Call 'llc' on the created file
-        Command::new("llc").args(&["a.ll", "--relocation-model=pic", "-filetype=obj"]).output().expect("Failed to call llc");
-        Command::new("rm").arg("a.ll").output().expect("Failed to call rm1");
-        // Call 'gcc' on 'a.o'
-        Command::new("gcc").args(&["a.o", "-o", out_file_name.as_str()]).output().expect("Failed to call gcc");
-        Command::new("rm").arg("a.o").output().expect("Failed to rm2");
+    if let Some(path) = sarif_path {
+        if let Err(e) = fs::write(&path, sarif::to_sarif(&driver.diagnostics)) {
+            eprintln!("Couldn't write '{}': {}", path, e);
+        }
     }
-}
+    let output = match output {
+        Ok(output) => output,
+        Err(_) => {
+            if let Some(summary) = renderer.summary() {
+                eprint!("{}", summary);
+            }
+            std::process::exit(1);
+        }
+    };
 
-/// Compiles the given code
-fn compile(code: String) -> String {
-    // Create a lexer
-    let mut lexer: Lexer = Lexer {pos: 0, code: code, col: 0};
+    if emit_llvm {
+        // Only the llvm ir is wanted
+        write_file(out_ir_name.as_str(), &output);
+        return;
+    }
 
-    // Lex the input
-    let tokens: Vec<Token> = lexer.lex();
+    // Kept intermediates are named after the input file, others go in the
+    // temp directory with a name unique to this process so that builds in
+    // the same directory don't clobber each other
+    let stem = Path::new(&file_name).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("a".to_string());
+    let temp = |ext: &str| {
+        if save_temps {
+            format!("{}.{}", stem, ext)
+        } else {
+            env::temp_dir().join(format!("gizmo-{}-{}.{}", std::process::id(), stem, ext)).to_string_lossy().to_string()
+        }
+    };
+    let keep_ir = save_temps || ir_path.is_some();
+    let keep_obj = save_temps || obj_path.is_some();
+    let ir_path = ir_path.unwrap_or_else(|| temp("ll"));
+    let obj_path = obj_path.unwrap_or_else(|| temp("o"));
+
+    if run {
+        // Run the program right away with the jit, or with 'lli' when the
+        // llvm backend isn't built in, and exit with its exit code
+        let options = Options {gc: gc, opt_level: opt_level, ..Options::default()};
+        let result = if cfg!(feature = "llvm") {
+            build::jit(output.as_str(), &options)
+        } else {
+            write_file(ir_path.as_str(), &output);
+            let result = build::lli(Path::new(&ir_path), &options);
+            if !keep_ir {
+                let _ = fs::remove_file(&ir_path);
+            }
+            result
+        };
+        match result {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Write the llvm ir, then call 'opt' and 'llc' on it, unless the llvm
+    // backend builds the object file in process
+    let options = Options {edition: edition, gc: gc, target: target, debug: debug, opt_level: opt_level, ..Options::default()};
+    if keep_ir || !cfg!(feature = "llvm") {
+        write_file(ir_path.as_str(), &output);
+    }
+    let result = if cfg!(feature = "llvm") {
+        build::llvm(output.as_str(), Path::new(&obj_path), &options)
+    } else if opt_level >= 2 {
+        build::opt(Path::new(&ir_path), Path::new(&ir_path), &options).and_then(|_| build::llc(Path::new(&ir_path), Path::new(&obj_path), &options))
+    } else {
+        build::llc(Path::new(&ir_path), Path::new(&obj_path), &options)
+    };
+    if !keep_ir {
+        let _ = fs::remove_file(&ir_path);
+    }
+    exit_on_error(result);
 
-    // Create a symbol-table and a parser
-    let sym_table = SymbolController {current: Scope {parent: None, children: Vec::new(), var_symbols: Vec::new(), func_symbols: Vec::new(), struct_symbols: Vec::new()}};
-    let mut parser: Parser = Parser {pos: 0, tokens: tokens, symtable: sym_table, id_c: 0};
+    // Link the object file
+    let result = build::link(Path::new(&obj_path), Path::new(&out_file_name), &options);
+    if !keep_obj {
+        let _ = fs::remove_file(&obj_path);
+    }
+    exit_on_error(result);
+}
 
-    // Parse the tokens
-    let ast = parser.parse();
+/// Prints a build error and exits
+fn exit_on_error(result: Result<(), BuildError>) {
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
 
-    // Create a generator
-    let mut generator = Generator::construct();
-    
-    // Generate llvm ir for the ast
-    generator.generate(ast);
-    generator.destruct();
-    generator.ir_b.code
+/// Creates a file with the given contents, exiting if it can't
+fn write_file(path: &str, contents: &str) {
+    let mut out_file = File::create(path).unwrap_or_else(|e| {
+        eprintln!("Couldn't create '{}': {}", path, e);
+        std::process::exit(1);
+    });
+    out_file.write_all(contents.as_bytes()).expect("Couldn't write to the output file");
 }
@@ -1,28 +1,46 @@
 pub mod lexer;
-mod ast;
+pub mod ast;
 pub mod symbol;
+pub mod types;
 pub mod generator;
 
 use self::lexer::token::Token;
+use self::lexer::token::Span;
 use self::lexer::token::TokenType;
 use self::lexer::error::error;
+use self::lexer::error::Error;
 use self::lexer::error::ErrorType;
 use self::ast::Node;
 use self::ast::Expr;
-use self::symbol::SymbolController;
-use self::symbol::SymbolType;
+use self::ast::Argument;
+use self::symbol::did_you_mean;
+use self::symbol::mangle;
+use self::types::Type;
+use crate::edition::Edition;
 
 pub struct Parser {
     pub pos: usize,
     pub tokens: Vec<Token>,
-    pub symtable: SymbolController,
-    pub id_c: i32
+    pub id_c: i32,
+
+    /// Identifier of the function whose body is being parsed
+    pub function: Option<String>,
+
+    /// The '{' and '(' that are open around the current token
+    pub delimiters: Vec<Token>,
+    pub edition: Edition,
+
+    /// Doc comments and the index of the token they come before
+    pub docs: Vec<(usize, String)>,
+
+    /// Errors found so far, the statements they were in are skipped
+    pub errors: Vec<Error>
 }
 
 impl Parser {
     fn matches(&mut self, types: Vec<TokenType>) -> bool {
         for t in types {
-            if self.check().typ == t {
+            if !self.eof() && self.peek().typ == t {
                 self.pos += 1;
                 return true;
             }
@@ -31,14 +49,61 @@ impl Parser {
         return false;
     }
 
-    fn consume(&mut self, t: TokenType, msg: &str) -> String {
-        let found = self.matches(vec![t]);
+    fn consume(&mut self, t: TokenType, msg: &str) -> Result<String, Error> {
+        let found = self.matches(vec![t.clone()]);
         if !found {
-            error(ErrorType::ExpectedToken, &self.previous())
-                .note(msg)
-                .emit();
+            let mut err = error(ErrorType::ExpectedToken, &self.previous());
+            err.note(msg);
+            if t == TokenType::SemiColon {
+                err.fix_insert_after(&self.previous(), ";", "Add the missing ';'");
+            }
+
+            // A missing '}', ')' or ']' is easier to find from where it was
+            // opened
+            let opener = match t {
+                TokenType::RightBrace => Some(TokenType::LeftBrace),
+                TokenType::RightParen => Some(TokenType::LeftParen),
+                TokenType::RightBracket => Some(TokenType::LeftBracket),
+                _ => None
+            };
+            if let Some(open) = self.delimiters.last().filter(|d| Some(d.typ.clone()) == opener) {
+                err.label(open, format!("this '{}' is never closed", open.value).as_str());
+            }
+            return Err(err);
+        }
+        match t {
+            TokenType::LeftBrace | TokenType::LeftParen => {
+                let open = self.previous();
+                self.delimiters.push(open);
+            },
+            TokenType::RightBrace | TokenType::RightParen => {
+                self.delimiters.pop();
+            },
+            // Only the '['s of arrays and indexes are kept, not those of types
+            TokenType::RightBracket if self.delimiters.last().map(|d| d.typ.clone()) == Some(TokenType::LeftBracket) => {
+                self.delimiters.pop();
+            },
+            _ => {}
         }
-        return self.previous().value;
+        return Ok(self.previous().value);
+    }
+
+    /// Finds the doc comment written before the previous token
+    fn previous_doc(&mut self) -> Option<String> {
+        let index = self.pos - 1;
+        return self.docs.iter().find(|d| d.0 == index).map(|d| d.1.clone());
+    }
+
+    /// Consumes the ';' that ends a statement
+    /// Since the 2024 edition, the ';' can be left out at the end of a line
+    /// or before a '}'
+    fn end_statement(&mut self, msg: &str) -> Result<(), Error> {
+        let optional = self.eof() || self.peek().lineno > self.previous().lineno || self.peek().typ == TokenType::RightBrace;
+        if self.edition >= Edition::E2024 && self.check().typ != TokenType::SemiColon && optional {
+            return Ok(());
+        }
+        self.consume(TokenType::SemiColon, msg)?;
+        return Ok(());
     }
 
     fn check(&mut self) -> Token {
@@ -61,217 +126,784 @@ impl Parser {
         self.pos >= self.tokens.len()
     }
 
-    fn expression(&mut self) -> Expr {
+    /// Makes a span from the token at 'start' to the previous token
+    fn span_from(&mut self, start: usize) -> Span {
+        let end = std::cmp::max(start + 1, self.pos) - 1;
+        return Span {start: self.tokens[start].clone(), end: self.tokens[end].clone()};
+    }
+
+    fn expression(&mut self) -> Result<Expr, Error> {
         self.boolean()
     }
 
-    fn boolean(&mut self) -> Expr {
-        let mut expr = self.equality();
+    /// Builds a binary operator
+    /// 'start' is the position of the first token of the left side
+    fn binary(&mut self, left: Expr, oper: Token, right: Expr, start: usize) -> Expr {
+        return Expr::BinaryOperator {
+            oper: oper.value.clone(),
+            left: Box::new(left),
+            right: Box::new(right),
+            token: oper,
+            span: self.span_from(start)
+        };
+    }
+
+    fn boolean(&mut self) -> Result<Expr, Error> {
+        let start = self.pos;
+        let mut expr = self.equality()?;
 
         while self.matches(vec![TokenType::And, TokenType::Or]) {
-            let oper = self.previous().value;
-            let right = self.comparison();
-            expr = Expr::BinaryOperator {
-                left: Box::new(expr),
-                oper: oper,
-                right: Box::new(right)
-            };
+            let oper = self.previous();
+            let right = self.comparison()?;
+            expr = self.binary(expr, oper, right, start);
         }
         
-        return expr;
+        return Ok(expr);
     }
 
-    fn equality(&mut self) -> Expr {
-        let mut expr = self.comparison();
+    fn equality(&mut self) -> Result<Expr, Error> {
+        let start = self.pos;
+        let mut expr = self.comparison()?;
 
-        while self.matches(vec![TokenType::Equal, TokenType::NotEqual]) {
-            let oper = self.previous().value;
-            let right = self.comparison();
-            expr = Expr::BinaryOperator {
-                left: Box::new(expr),
-                oper: oper,
-                right: Box::new(right)
-            };
+        while self.matches(vec![TokenType::EqualEqual, TokenType::NotEqual]) {
+            let oper = self.previous();
+            let right = self.comparison()?;
+            expr = self.binary(expr, oper, right, start);
         }
         
-        return expr;
+        return Ok(expr);
     }
 
-    fn comparison(&mut self) -> Expr {
-        let mut expr = self.term();
+    fn comparison(&mut self) -> Result<Expr, Error> {
+        let start = self.pos;
+        let mut expr = self.term()?;
 
         while self.matches(vec![TokenType::GreaterThan, TokenType::GreaterEqual, TokenType::LessThan, TokenType::LessEqual]) {
-            let oper = self.previous().value;
-            let right = self.term();
-            expr = Expr::BinaryOperator {
-                left: Box::new(expr),
-                oper: oper,
-                right: Box::new(right)
-            };
+            let oper = self.previous();
+            let right = self.term()?;
+            expr = self.binary(expr, oper, right, start);
         }
         
-        return expr;
+        return Ok(expr);
     }
 
-    fn term(&mut self) -> Expr {
-        let mut expr = self.factor();
+    fn term(&mut self) -> Result<Expr, Error> {
+        let start = self.pos;
+        let mut expr = self.factor()?;
 
-        while self.matches(vec![TokenType::Star, TokenType::Slash]) {
-            let oper = self.previous().value;
-            let right = self.factor();
-            expr = Expr::BinaryOperator {
-                left: Box::new(expr),
-                oper: oper,
-                right: Box::new(right)
-            };
+        while self.matches(vec![TokenType::Plus, TokenType::Dash]) {
+            let oper = self.previous();
+            let right = self.factor()?;
+            expr = self.binary(expr, oper, right, start);
         }
         
-        return expr;
+        return Ok(expr);
     }
 
-    fn factor(&mut self) -> Expr {
-        let mut expr = self.unary();
+    fn factor(&mut self) -> Result<Expr, Error> {
+        let start = self.pos;
+        let mut expr = self.cast()?;
 
-        while self.matches(vec![TokenType::Plus, TokenType::Dash]) {
-            let oper = self.previous().value;
-            let right = self.unary();
-            expr = Expr::BinaryOperator {
-                left: Box::new(expr),
-                oper: oper,
-                right: Box::new(right)
-            };
+        while self.matches(vec![TokenType::Star, TokenType::Slash, TokenType::Percent]) {
+            let oper = self.previous();
+            let right = self.cast()?;
+            expr = self.binary(expr, oper, right, start);
         }
         
-        return expr;
+        return Ok(expr);
     }
 
-    fn unary(&mut self) -> Expr {
+    /// Parses an 'as' conversion between number types
+    fn cast(&mut self) -> Result<Expr, Error> {
+        let start = self.pos;
+        let mut expr = self.unary()?;
+
+        while self.matches(vec![TokenType::As]) {
+            let as_token = self.previous();
+            let typ = match self.parse_type()? {
+                Some(typ) => typ,
+                None => {
+                    let mut err = error(ErrorType::ExpectedToken, &as_token);
+                    err.note("Expect a type after this 'as'");
+                    return Err(err);
+                }
+            };
+            expr = Expr::Cast {expr: Box::new(expr), typ: typ, span: self.span_from(start)};
+        }
+
+        return Ok(expr);
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        let start = self.pos;
         if self.matches(vec![TokenType::Not, TokenType::Dash]) {
             let oper = self.previous().value;
-            let right = self.unary();
-            return Expr::UnaryOperator {
+            let right = self.unary()?;
+            return Ok(Expr::UnaryOperator {
                 oper: oper,
-                child: Box::new(right)
-            };
+                child: Box::new(right),
+                span: self.span_from(start)
+            });
         }
         
         return self.primary();
     }
 
-    fn primary(&mut self) -> Expr {
-        if self.matches(vec![TokenType::Int]) {return Expr::Int(self.previous().value);}
-        if self.matches(vec![TokenType::Str]) {return Expr::Str(self.previous().value);}
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.literal()?;
+        while self.matches(vec![TokenType::LeftBracket]) {
+            expr = self.index(expr)?;
+        }
+        return Ok(expr);
+    }
+
+    /// Parses the condition of an 'if' or 'while', along with its span
+    fn condition(&mut self) -> Result<(Expr, Span), Error> {
+        let start = self.pos;
+        let cond = self.expression()?;
+        return Ok((cond, self.span_from(start)));
+    }
+
+    /// Parses an if expression
+    /// Both branches are required
+    fn if_expression(&mut self) -> Result<Expr, Error> {
+        let if_token = self.previous();
+        let (cond, span) = self.condition()?;
+        self.consume(TokenType::LeftBrace, "Expect an '{' after this condition")?;
+        let body = self.expression()?;
+        self.consume(TokenType::RightBrace, "Expect an '}' after this expression")?;
+        if !self.matches(vec![TokenType::Else]) {
+            let mut err = error(ErrorType::ExpectedToken, &self.previous());
+            err.note("An if expression needs an 'else'")
+                .help("Add an 'else { ... }' with the value to use when the condition is false");
+            return Err(err);
+        }
+        let else_body = if self.matches(vec![TokenType::If]) {
+            self.if_expression()?
+        } else {
+            self.consume(TokenType::LeftBrace, "Expect an '{' after this 'else'")?;
+            let e = self.expression()?;
+            self.consume(TokenType::RightBrace, "Expect an '}' after this expression")?;
+            e
+        };
+        return Ok(Expr::If {cond: Box::new(cond), body: Box::new(body), else_body: Box::new(else_body), typ: Type::Error, token: if_token, span: span});
+    }
+
+    /// Parses an array literal
+    fn array(&mut self) -> Result<Expr, Error> {
+        let bracket = self.previous();
+        self.delimiters.push(bracket.clone());
+        let mut values: Vec<Expr> = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
+        while !self.eof() && self.peek().typ != TokenType::RightBracket {
+            let start = self.pos;
+            values.push(self.expression()?);
+            spans.push(self.span_from(start));
+            let comma = self.matches(vec![TokenType::Comma]);
+            if !comma {
+                break
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expect an ']' after this element")?;
+        if values.is_empty() {
+            let mut err = error(ErrorType::ExpectedToken, &bracket);
+            err.note("The type of an empty array can't be known")
+                .help("Give the array at least one element");
+            return Err(err);
+        }
+        return Ok(Expr::Array {values: values, typ: Type::Error, spans: spans});
+    }
+
+    /// Parses the index after a '[' and indexes "expr" with it
+    fn index(&mut self, expr: Expr) -> Result<Expr, Error> {
+        let bracket = self.previous();
+        self.delimiters.push(bracket.clone());
+        let start = self.pos;
+        let index = self.expression()?;
+        let span = self.span_from(start);
+        self.consume(TokenType::RightBracket, "Expect an ']' after this index")?;
+        return Ok(Expr::IndexedValue {src: Box::new(expr), index: Box::new(index), new_typ: Type::Error, token: bracket, span: span});
+    }
+
+    fn literal(&mut self) -> Result<Expr, Error> {
+        if self.matches(vec![TokenType::Int]) {return Ok(Expr::Int(self.previous().value));}
+        if self.matches(vec![TokenType::Dec]) {return Ok(Expr::Dec(self.previous().value));}
+        if self.matches(vec![TokenType::Bool]) {return Ok(Expr::Bool(self.previous().value == "true"));}
+        if self.matches(vec![TokenType::Char]) {return Ok(Expr::Chr(self.previous().value.chars().next().unwrap_or(' ')));}
+        if self.matches(vec![TokenType::Str]) {return Ok(Expr::Str(self.previous().value));}
+        if self.matches(vec![TokenType::If]) {return self.if_expression();}
+        if self.matches(vec![TokenType::LeftBracket]) {return self.array();}
         if self.matches(vec![TokenType::Id]) {
-            let prev = self.previous();
-            let symbol = self.symtable.find_global_var_error(prev.value.clone(), &prev);
-            return Expr::Id(self.previous().value, symbol.typ, symbol.gen_id);
+            let id_token = self.previous();
+            if self.check().typ == TokenType::LeftParen {
+                return self.call_expression();
+            }
+            let mut expr = Expr::Id(id_token.value.clone(), Type::Error, String::new(), id_token);
+            while self.matches(vec![TokenType::Dot]) {
+                expr = self.struct_dot(expr)?;
+            }
+            return Ok(expr);
         }
         if self.matches(vec![TokenType::New]) {
-            let id = self.consume(TokenType::Id, "Expect an identifier after this 'new'");
-            self.consume(TokenType::LeftParen, "Expect an '(' after this identifier");
+            let id = self.consume(TokenType::Id, "Expect an identifier after this 'new'")?;
+            let start = self.pos - 1;
+            self.consume(TokenType::LeftParen, "Expect an '(' after this identifier")?;
 
             let mut fields: Vec<Expr> = Vec::new();
-            while self.peek().typ != TokenType::RightParen {
-                let expr = self.expression();
-                fields.push(expr.clone());
+            let mut spans: Vec<Span> = Vec::new();
+            while !self.eof() && self.peek().typ != TokenType::RightParen {
+                let start = self.pos;
+                fields.push(self.expression()?);
+                spans.push(self.span_from(start));
                 let comma = self.matches(vec![TokenType::Comma]);
                 if !comma {
                     break
                 }
             }
-            self.consume(TokenType::RightParen, "Expect an ')' after this expression");
-            return Expr::NewStruct {id: id, fields: fields};
+            self.consume(TokenType::RightParen, "Expect an ')' after this expression")?;
+            return Ok(Expr::NewStruct {id: id, fields: fields, span: self.span_from(start), spans: spans});
         }
         
-        error(ErrorType::ExpectedToken, &self.previous())
-            .note("Expect a constant expression")
-            .emit();
-        return Expr::Non;
+        let mut err = error(ErrorType::ExpectedToken, &self.previous());
+        err.note("Expect a constant expression");
+        return Err(err);
     }
 
-    fn let_statement(&mut self) -> Node {
-        let id = self.consume(TokenType::Id, "Expect an identifier after this 'let'");
-        self.consume(TokenType::Equal, "Expect an '=' after this identifier");
-        let expr = self.expression();
-        self.consume(TokenType::SemiColon, "Expect an ';' after this expression");
+    /// Parses a call in an expression
+    /// 'typeof' looks at an expression and 'sizeof' at a type, instead of
+    /// taking arguments
+    fn call_expression(&mut self) -> Result<Expr, Error> {
+        let id_token = self.previous();
+        let start = self.pos - 1;
+        match id_token.value.as_str() {
+            "typeof" => {
+                self.consume(TokenType::LeftParen, "Expect an '(' after this identifier")?;
+                let arg_start = self.pos;
+                let expr = self.expression()?;
+                let arg_span = self.span_from(arg_start);
+                self.consume(TokenType::RightParen, "Expect an ')' after this expression")?;
+                return Ok(Expr::FuncCall {id: id_token.value, typ: Type::Error, args: vec![Box::new(expr)], span: self.span_from(start), arg_spans: vec![arg_span]});
+            },
+            "sizeof" => {
+                self.consume(TokenType::LeftParen, "Expect an '(' after this identifier")?;
+                let type_start = self.pos;
+                let typ = match self.parse_type()? {
+                    Some(typ) => typ,
+                    None => {
+                        let mut err = error(ErrorType::ExpectedToken, &self.previous());
+                        err.note("Expect a type after this '('");
+                        return Err(err);
+                    }
+                };
+                let span = self.span_from(type_start);
+                self.consume(TokenType::RightParen, "Expect an ')' after this type")?;
+                return Ok(Expr::SizeOf {typ: typ, span: span});
+            },
+            _ => {
+                let (args, arg_spans) = self.call_arguments()?;
+                return Ok(Expr::FuncCall {id: id_token.value, typ: Type::Error, args: args, span: self.span_from(start), arg_spans: arg_spans});
+            }
+        }
+    }
+
+    /// Parses the field after a '.' and indexes "expr" with it
+    fn struct_dot(&mut self, expr: Expr) -> Result<Expr, Error> {
+        let field = self.consume(TokenType::Id, "Expect a field name after this '.'")?;
+        return Ok(Expr::StructDot {
+            id: Box::new(expr),
+            id2: field,
+            typ: Type::Error,
+            field_num: 0,
+            token: self.previous()
+        });
+    }
 
+    /// Makes up the name in ir of a variable, from its name and a number that
+    /// keeps it apart from other variables with the same name
+    /// Names in the source can't have a '.', so these never clash with
+    /// labels or with the values the generator makes up
+    /// # Example
+    /// count -> %count.3
+    fn new_gen_id(&mut self, prefix: char, id: &str) -> String {
         self.id_c += 1;
-        self.symtable.add_symbol(id.clone(), expr.validate().to_string(), SymbolType::Var, format!("%.{}", self.id_c - 1), None);
-        return Node::Let {id: id, expr: expr, gen_id: format!("%.{}", self.id_c - 1)};
+        return format!("{}{}.{}", prefix, id, self.id_c - 1);
     }
 
-    fn function_call(&mut self) -> Node {
-        let id = self.previous().value;
-        self.consume(TokenType::LeftParen, "Expect an '(' after this identifier");
+    /// Parses a 'let' or 'var' statement
+    /// Only variables declared with 'var' can be assigned to later
+    fn let_statement(&mut self, mutable: bool) -> Result<Node, Error> {
+        let line = self.previous().lineno;
+        let keyword = if mutable {"var"} else {"let"};
+        let id = self.consume(TokenType::Id, format!("Expect an identifier after this '{}'", keyword).as_str())?;
+        let id_token = self.previous();
+
+        // The type can be given, and the value can be left for later
+        let mut type_span = None;
+        let typ = if self.matches(vec![TokenType::Colon]) {
+            let start = self.pos;
+            let typ = self.parse_type()?;
+            if typ.is_none() {
+                let mut err = error(ErrorType::ExpectedToken, &self.previous());
+                err.note("Expect a type after this ':'");
+                return Err(err);
+            }
+            type_span = Some(self.span_from(start));
+            typ
+        } else {
+            None
+        };
+        let (expr, span) = if typ.is_none() || self.check().typ == TokenType::Equal {
+            self.consume(TokenType::Equal, "Expect an '=' after this identifier")?;
+            let start = self.pos;
+            let expr = self.expression()?;
+            (expr, self.span_from(start))
+        } else {
+            (Expr::Non, Span::token(&id_token))
+        };
+        self.end_statement("Expect an ';' after this expression")?;
+
+        // Variables outside of functions are globals, which functions can use
+        // too
+        let gen_id = match self.function {
+            Some(_) => self.new_gen_id('%', id.as_str()),
+            None => self.new_gen_id('@', id.as_str())
+        };
+        return Ok(Node::Let {
+            id: id,
+            typ: typ.unwrap_or(Type::Error),
+            expr: expr,
+            gen_id: gen_id,
+            mutable: mutable,
+            line: line,
+            token: id_token,
+            type_span: type_span,
+            span: span
+        });
+    }
+
+    /// Parses an assignment to a variable, a field or an element
+    fn assign_statement(&mut self) -> Result<Node, Error> {
+        let prev = self.previous();
+        let start = self.pos - 1;
+        let mut target = Expr::Id(prev.value.clone(), Type::Error, String::new(), prev.clone());
+        while self.matches(vec![TokenType::Dot, TokenType::LeftBracket]) {
+            if self.previous().typ == TokenType::Dot {
+                target = self.struct_dot(target)?;
+            } else {
+                target = self.index(target)?;
+            }
+        }
+        let target_span = self.span_from(start);
+        self.consume(TokenType::Equal, "Expect an '=' after this identifier")?;
+        let start = self.pos;
+        let expr = self.expression()?;
+        let span = self.span_from(start);
+        self.end_statement("Expect an ';' after this expression")?;
+        return Ok(Node::Assign {id: target, expr: expr, line: prev.lineno, target: target_span, span: span});
+    }
+
+    /// Parses the arguments of a call, along with the span of each one
+    #[allow(clippy::vec_box)]
+    fn call_arguments(&mut self) -> Result<(Vec<Box<Expr>>, Vec<Span>), Error> {
+        self.consume(TokenType::LeftParen, "Expect an '(' after this identifier")?;
         let mut args: Vec<Box<Expr>> = Vec::new();
-        let mut arg_types: Vec<String> = Vec::new();
-        while self.peek().typ != TokenType::RightParen {
-            let expr = self.expression();
-            args.push(Box::new(expr.clone()));
-            arg_types.push(expr.validate().into());
+        let mut spans: Vec<Span> = Vec::new();
+        while !self.eof() && self.peek().typ != TokenType::RightParen {
+            let start = self.pos;
+            args.push(Box::new(self.expression()?));
+            spans.push(self.span_from(start));
             let comma = self.matches(vec![TokenType::Comma]);
             if !comma {
                 break
             }
         }
-        self.consume(TokenType::RightParen, "Expect an ')' after this expression");
-        self.consume(TokenType::SemiColon, "Expect an ';' after this ')'");
+        self.consume(TokenType::RightParen, "Expect an ')' after this expression")?;
+        return Ok((args, spans));
+    }
 
-        self.symtable.add_symbol(id.clone(), "".into(), SymbolType::Func, id.clone(), Some(arg_types));
-        return Node::FuncCall {id: id, args: args};
+    fn function_call(&mut self) -> Result<Node, Error> {
+        let id_token = self.previous();
+        let start = self.pos - 1;
+        let (args, arg_spans) = self.call_arguments()?;
+        let span = self.span_from(start);
+        self.end_statement("Expect an ';' after this ')'")?;
+        return Ok(Node::FuncCall {id: id_token.value, args: args, line: id_token.lineno, span: span, arg_spans: arg_spans});
     }
 
-    fn parse_type(&mut self) -> Option<String> {
+    fn parse_type(&mut self) -> Result<Option<Type>, Error> {
+        let mut typ = match self.parse_element_type() {
+            Some(typ) => typ,
+            None => return Ok(None)
+        };
+
+        // Each '[]' after a type makes an array of it
+        while self.matches(vec![TokenType::LeftBracket]) {
+            self.consume(TokenType::RightBracket, "Expect an ']' after this '['")?;
+            typ = Type::Array(Box::new(typ));
+        }
+        return Ok(Some(typ));
+    }
+
+    /// Parses a type without the '[]'s of arrays
+    /// Any other identifier is taken to be a struct, which the semantic pass
+    /// looks for
+    fn parse_element_type(&mut self) -> Option<Type> {
         if self.matches(vec![TokenType::Type]) {
-            return Some(self.previous().value);
+            return Some(Type::from_name(self.previous().value.as_str()));
         }
         if self.matches(vec![TokenType::Id]) {
-            let prev = self.previous();
-            let symbol = self.symtable.find_global_struct_error(prev.value.clone(), &prev);
-            return Some(symbol.id.clone());
+            return Some(Type::Struct(self.previous().value));
         }
 
         return None;
     }
 
-    fn struct_definition(&mut self) -> Node {
-        let id = self.consume(TokenType::Id, "Expect an identifier after this 'struct'");
-        self.consume(TokenType::LeftBrace, "Expect an '{' after this identifier");
-
-        let mut fields: Vec<(String, String)> = Vec::new();
-        while self.peek().typ != TokenType::RightBrace {
-            let id = self.consume(TokenType::Id, "Expect an identifier after this ','");
-            self.consume(TokenType::Colon, "Expect ':' after this identifier");
-            let typ = self.parse_type();
-            if typ == None {
-                error(ErrorType::ExpectedToken, &self.previous())
-                    .note("Expect a type after this ':'")
-                    .emit();
+    fn struct_definition(&mut self) -> Result<Node, Error> {
+        let doc = self.previous_doc();
+        let id = self.consume(TokenType::Id, "Expect an identifier after this 'struct'")?;
+        let id_token = self.previous();
+        self.consume(TokenType::LeftBrace, "Expect an '{' after this identifier")?;
+
+        let mut fields: Vec<(String, Type)> = Vec::new();
+        let mut field_tokens: Vec<Token> = Vec::new();
+        let mut type_spans: Vec<Span> = Vec::new();
+        while !self.eof() && self.peek().typ != TokenType::RightBrace {
+            let id = self.consume(TokenType::Id, "Expect an identifier after this ','")?;
+            field_tokens.push(self.previous());
+            self.consume(TokenType::Colon, "Expect ':' after this identifier")?;
+            let start = self.pos;
+            let typ = self.parse_type()?;
+            if typ.is_none() {
+                let mut err = error(ErrorType::ExpectedToken, &self.previous());
+                err.note("Expect a type after this ':'");
+                return Err(err);
             }
+            type_spans.push(self.span_from(start));
             fields.push((id, typ.unwrap()));
             let comma = self.matches(vec![TokenType::Comma]);
             if !comma {
                 break
             }
         }
-        self.consume(TokenType::RightBrace, "Expect an '}' after this type");
-        return Node::Struct {id: id, fields: fields};
+        self.consume(TokenType::RightBrace, "Expect an '}' after this type")?;
+        return Ok(Node::Struct {id: id, fields: fields, doc: doc, token: id_token, field_tokens: field_tokens, type_spans: type_spans});
     }
 
-    fn statement(&mut self) -> Node {
-        if self.matches(vec![TokenType::Let]) {return self.let_statement();}
-        if self.matches(vec![TokenType::Id]) {return self.function_call();}
-        if self.matches(vec![TokenType::Struct]) {return self.struct_definition();}
+    /// Parses a block
+    /// Variables declared inside a block only live until its '}'
+    fn block(&mut self) -> Result<Node, Error> {
+        self.consume(TokenType::LeftBrace, "Expect an '{' to start this block")?;
+        let mut statements: Vec<Box<Node>> = Vec::new();
+        while !self.eof() && self.peek().typ != TokenType::RightBrace {
+            if let Some(stmt) = self.recovering_statement() {
+                statements.push(Box::new(stmt));
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect an '}' after this statement")?;
+        return Ok(Node::Block {statements: statements});
+    }
+
+    fn if_statement(&mut self) -> Result<Node, Error> {
+        let line = self.previous().lineno;
+        let (cond, span) = self.condition()?;
+        let body = self.block()?;
+        let mut else_body: Option<Box<Node>> = None;
+        if self.matches(vec![TokenType::Else]) {
+            if self.matches(vec![TokenType::If]) {
+                else_body = Some(Box::new(self.if_statement()?));
+            } else {
+                else_body = Some(Box::new(self.block()?));
+            }
+        }
+        return Ok(Node::If {cond: cond, body: Box::new(body), else_body: else_body, line: line, span: span});
+    }
+
+    fn while_loop(&mut self) -> Result<Node, Error> {
+        let line = self.previous().lineno;
+        let (cond, span) = self.condition()?;
+        let body = self.block()?;
+        return Ok(Node::While {cond: cond, body: Box::new(body), line: line, span: span});
+    }
+
+    /// Parses a 'break' or 'continue'
+    /// They jump to the end or the next check of the innermost loop
+    fn pause_statement(&mut self) -> Result<Node, Error> {
+        let keyword = self.previous();
+        self.end_statement(format!("Expect an ';' after this '{}'", keyword.value).as_str())?;
+        return Ok(Node::Pause {is_break: keyword.typ == TokenType::Break, line: keyword.lineno, token: keyword});
+    }
+
+    fn func_declaration(&mut self) -> Result<Node, Error> {
+        let doc = self.previous_doc();
+        let id = self.consume(TokenType::Id, "Expect an identifier after this 'func'")?;
+        let id_token = self.previous();
+        let line = id_token.lineno;
+        self.consume(TokenType::LeftParen, "Expect an '(' after this identifier")?;
+
+        let mut args: Vec<Argument> = Vec::new();
+        while !self.eof() && self.peek().typ != TokenType::RightParen {
+            let is_ref = self.matches(vec![TokenType::Ref]);
+            let arg_id = self.consume(TokenType::Id, "Expect an identifier for this argument")?;
+            let arg_token = self.previous();
+            self.consume(TokenType::Colon, "Expect ':' after this identifier")?;
+            let start = self.pos;
+            let typ = self.parse_type()?;
+            if typ.is_none() {
+                let mut err = error(ErrorType::ExpectedToken, &self.previous());
+                err.note("Expect a type after this ':'");
+                return Err(err);
+            }
+            let type_span = self.span_from(start);
+            let gen_id = self.new_gen_id('%', arg_id.as_str());
+            args.push(Argument {id: arg_id, typ: typ.unwrap(), gen_id: gen_id, is_ref: is_ref, token: arg_token, type_span: type_span});
+            let comma = self.matches(vec![TokenType::Comma]);
+            if !comma {
+                break
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect an ')' after this argument")?;
+
+        // Functions without a return type return nothing
+        let mut typ = Type::Void;
+        let mut type_span = None;
+        if self.matches(vec![TokenType::Colon]) {
+            let start = self.pos;
+            let ret_typ = self.parse_type()?;
+            if ret_typ.is_none() {
+                let mut err = error(ErrorType::ExpectedToken, &self.previous());
+                err.note("Expect a return type after this ':'");
+                return Err(err);
+            }
+            typ = ret_typ.unwrap();
+            type_span = Some(self.span_from(start));
+        }
+
+        let function = self.function.replace(id.clone());
+        let body = self.block()?;
+        self.function = function;
+
+        let gen_id = mangle(id.as_str());
+        return Ok(Node::FuncDecl {
+            id: id,
+            gen_id: gen_id,
+            typ: typ,
+            args: args,
+            body: Box::new(body),
+            doc: doc,
+            end: self.previous(),
+            line: line,
+            token: id_token,
+            type_span: type_span
+        });
+    }
+
+    fn ret_statement(&mut self) -> Result<Node, Error> {
+        let token = self.previous();
+
+        // A 'ret' without a value ends a function that returns nothing
+        if self.check().typ == TokenType::SemiColon || self.check().typ == TokenType::RightBrace || self.eof() {
+            self.end_statement("Expect an ';' after this 'ret'")?;
+            return Ok(Node::Ret {expr: Expr::Non, span: Span::token(&token)});
+        }
+        let start = self.pos;
+        let expr = self.expression()?;
+        let span = self.span_from(start);
+        self.end_statement("Expect an ';' after this expression")?;
+        return Ok(Node::Ret {expr: expr, span: span});
+    }
+
+    /// Parses a statement, recovering from an error in it by skipping to where
+    /// the next statement starts, so that later errors are found too
+    /// Gives None if the statement had an error, which is kept in 'errors'
+    fn recovering_statement(&mut self) -> Option<Node> {
+        let function = self.function.clone();
+        let delimiters = self.delimiters.len();
+        match self.statement() {
+            Ok(stmt) => return Some(stmt),
+            Err(err) => {
+                self.errors.push(err);
+                self.function = function;
+                self.delimiters.truncate(delimiters);
+                self.synchronize();
+                return None;
+            }
+        }
+    }
+
+    /// Skips the rest of a statement with an error
+    /// Stops after its ';' or the '}' of its block, before the '}' of the
+    /// block around it, or before a keyword that starts a new line
+    fn synchronize(&mut self) {
+        let starts = [TokenType::Let, TokenType::Var, TokenType::Func, TokenType::Struct, TokenType::If, TokenType::While, TokenType::Ret];
+        let mut depth = 0;
+        while !self.eof() {
+            let token = self.peek();
+            let new_line = self.pos == 0 || token.lineno > self.tokens[self.pos - 1].lineno;
+            if depth == 0 && new_line && starts.contains(&token.typ) {
+                return;
+            }
+            match token.typ {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace if depth == 0 => return,
+                TokenType::RightBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.pos += 1;
+                        return;
+                    }
+                },
+                TokenType::SemiColon if depth == 0 => {
+                    self.pos += 1;
+                    return;
+                },
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
 
-        return Node::Non;
+    /// Reports an identifier starting a statement that looks like a misspelled
+    /// keyword, like 'whle', instead of treating it as an assignment
+    fn keyword_typo(&mut self) -> Result<(), Error> {
+        let id_token = self.previous();
+        let next = self.check().typ;
+        if next == TokenType::Equal || next == TokenType::Dot || next == TokenType::LeftBracket {
+            return Ok(());
+        }
+        let keywords: Vec<String> = ["let", "var", "func", "struct", "ret", "if", "while", "break", "continue"].iter().map(|k| k.to_string()).collect();
+        if let Some(keyword) = did_you_mean(id_token.value.as_str(), &keywords) {
+            let mut err = error(ErrorType::ExpectedToken, &id_token);
+            err.note(format!("Expect a statement, but '{}' isn't a keyword", id_token.value).as_str())
+                .help(format!("Perhaps you meant '{}'", keyword).as_str())
+                .fix_replace(&id_token, keyword.as_str(), format!("Change it to '{}'", keyword).as_str());
+            return Err(err);
+        }
+        return Ok(());
     }
 
-    pub fn parse(&mut self) -> Vec<Box<Node>> {
+    fn statement(&mut self) -> Result<Node, Error> {
+        if self.matches(vec![TokenType::Let]) {return self.let_statement(false);}
+        if self.matches(vec![TokenType::Var]) {return self.let_statement(true);}
+        if self.matches(vec![TokenType::Id]) {
+            if self.check().typ == TokenType::LeftParen {
+                return self.function_call();
+            }
+            self.keyword_typo()?;
+            return self.assign_statement();
+        }
+        if self.matches(vec![TokenType::Struct]) {return self.struct_definition();}
+        if self.matches(vec![TokenType::Func]) {return self.func_declaration();}
+        if self.matches(vec![TokenType::Ret]) {return self.ret_statement();}
+        if self.matches(vec![TokenType::If]) {return self.if_statement();}
+        if self.matches(vec![TokenType::While]) {return self.while_loop();}
+        if self.matches(vec![TokenType::Break, TokenType::Continue]) {return self.pause_statement();}
+        if self.check().typ == TokenType::LeftBrace {return self.block();}
+
+        let mut err = error(ErrorType::ExpectedToken, &self.check());
+        err.note("Expect a statement");
+        return Err(err);
+    }
+
+    /// Parses the tokens into statements
+    /// Gives back every error found if there were any
+    pub fn parse(&mut self) -> Result<Vec<Box<Node>>, Vec<Error>> {
         let mut stmts: Vec<Box<Node>> = Vec::new();
+
+        // First token of the first statement that isn't a declaration
+        let mut loose: Option<Token> = None;
+        let mut has_main = false;
         while !self.eof() {
-            let stmt = self.statement();
+            let start = self.peek();
+            let start_pos = self.pos;
+            let stmt = match self.recovering_statement() {
+                Some(stmt) => stmt,
+                None => {
+                    // A broken 'main' still counts as one
+                    let is_main = start.typ == TokenType::Func && self.tokens.get(start_pos + 1).map(|t| t.value.as_str()) == Some("main");
+                    has_main = has_main || is_main;
+
+                    // A stray '}' isn't skipped by the recovery
+                    if self.pos == start_pos {
+                        self.pos += 1;
+                    }
+                    continue;
+                }
+            };
+            match &stmt {
+                Node::FuncDecl {id, ..} => has_main = has_main || id == "main",
+                Node::Struct {..} | Node::Let {..} => {},
+                _ => if loose.is_none() {
+                    loose = Some(start);
+                }
+            }
             stmts.push(Box::new(stmt));
         }
-        return stmts;
+
+        // With a 'main' function, every statement has to be inside of a function
+        if has_main {
+            if let Some(token) = loose {
+                let mut err = error(ErrorType::StatementOutsideMain, &token);
+                err.note("Programs with a 'main' function can only declare functions, structs and variables outside of it")
+                    .help("Move this statement into 'main'");
+                self.errors.push(err);
+            }
+        } else if self.edition >= Edition::E2024 && !self.tokens.is_empty() {
+            // Since the 2024 edition, programs need an explicit entry point
+            let mut err = error(ErrorType::MissingMain, &self.tokens[0].clone());
+            err.note("Since the 2024 edition, statements go inside of a 'main' function")
+                .help("Wrap the program in 'func main() { ... }'");
+            self.errors.push(err);
+        }
+        if !self.errors.is_empty() {
+            return Err(self.errors.drain(..).collect());
+        }
+        return Ok(stmts);
+    }
+}
+
+#[test]
+fn test_recovery() {
+    use self::lexer::Lexer;
+    let mut lexer = Lexer {code: "let = 1;\nwhle x {\n}\nlet y = 2;\nwrite(y);\n".to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None};
+    let tokens = lexer.lex().unwrap();
+    let mut parser = Parser {pos: 0, tokens: tokens, id_c: 0, function: None, delimiters: Vec::new(), edition: Edition::E2021, docs: Vec::new(), errors: Vec::new()};
+
+    // Each broken statement is reported, and the ones after it still parse
+    let errors = parser.parse().unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[1].to_json().contains("Perhaps you meant 'while'"));
+}
+
+#[test]
+fn test_statement_outside_main() {
+    use self::lexer::Lexer;
+    let parse = |code: &str, edition: Edition| {
+        let mut lexer = Lexer {code: code.to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None};
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser {pos: 0, tokens: tokens, id_c: 0, function: None, delimiters: Vec::new(), edition: edition, docs: Vec::new(), errors: Vec::new()};
+        return parser.parse();
+    };
+
+    // Variables can be declared next to 'main', even where it is required
+    assert!(parse("let g = 5;\nvar h = 1;\nfunc main() {\n    write(g, h);\n}\n", Edition::E2024).is_ok());
+    let errors = parse("let g = 5;\nwrite(g);\nfunc main() {\n}\n", Edition::E2021).unwrap_err();
+    assert_eq!(errors[0].code(), "E0015");
+}
+
+#[test]
+fn test_unclosed_delimiters() {
+    use self::lexer::Lexer;
+    let parse = |code: &str| {
+        let mut lexer = Lexer {code: code.to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None};
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser {pos: 0, tokens: tokens, id_c: 0, function: None, delimiters: Vec::new(), edition: Edition::E2021, docs: Vec::new(), errors: Vec::new()};
+        return parser.parse().unwrap_err()[0].to_json();
+    };
+    assert!(parse("let x = sizeof(int, int);").contains("Expect an ')' after this type"));
+
+    // Input that ends before a delimiter is closed points at where it opened
+    for code in ["write(", "func f(", "let a = [1,", "struct S {", "let x = new S("] {
+        let json = parse(code);
+        assert!(json.contains("is never closed"), "{}", code);
     }
 }
@@ -1,4 +1,9 @@
+use super::lexer::token::Token;
+use super::lexer::token::Span;
+use super::types::Type;
+
 /// An enum to store each possible Node
+/// 'line' is the line a statement starts on, for debug info
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Node {
@@ -6,34 +11,69 @@ pub enum Node {
     /// let a: int = 5;
     /// or
     /// let a = 5;
+    /// or, to give it a value later
+    /// let a: int;
+    /// 'mutable' is true for a 'var'
+    /// 'typ' is Error until the semantic pass finds it, when it isn't written
+    /// 'token' is the identifier, 'type_span' the written type and 'span' the
+    /// value, or the identifier without one, for diagnostics
     Let {
         id: String,
+        typ: Type,
         expr: Expr,
         gen_id: String,
+        mutable: bool,
+        line: usize,
+        token: Token,
+        type_span: Option<Span>,
+        span: Span
     },
 
     /// Function declaration
+    /// 'gen_id' is its name in ir
+    /// 'doc' holds the '///' comments written above it
+    /// 'token' is the identifier, 'type_span' the return type and 'end' the '}'
+    /// that closes the body, for diagnostics
+    /// # Example
+    /// func add(a: int, ref total: int): int {
+    ///     ret a + total;
+    /// }
     FuncDecl {
         id: String,
-        typ: String,
-        args: Vec<(String, String)>,
-        body: Box<Node>
+        gen_id: String,
+        typ: Type,
+        args: Vec<Argument>,
+        body: Box<Node>,
+        doc: Option<String>,
+        end: Token,
+        line: usize,
+        token: Token,
+        type_span: Option<Span>
     },
 
     /// Return statement
+    /// 'expr' is Non for a 'ret' without a value
+    /// 'span' is the returned value, or the 'ret' keyword without one, for
+    /// diagnostics
     /// # Example
     /// ret 10;
+    /// or
+    /// ret;
     Ret {
-        expr: Expr
+        expr: Expr,
+        span: Span
     },
 
     /// Break or continue statement
+    /// 'token' is the keyword, for diagnostics
     /// # Example
     /// break;
     /// or
     /// continue;
     Pause {
-        label: usize,
+        is_break: bool,
+        line: usize,
+        token: Token
     },
 
     /// Use statement
@@ -43,6 +83,7 @@ pub enum Node {
     },
 
     /// If statement
+    /// 'span' is the condition, for diagnostics
     /// if "abc" == "abc" {
     ///     write("Yay");
     /// }
@@ -50,34 +91,50 @@ pub enum Node {
         cond: Expr,
         body: Box<Node>,
         else_body: Option<Box<Node>>,
-        begin: i32,
-        else_: i32,
-        end: i32
+        line: usize,
+        span: Span
     },
 
     /// Assign statement
-    /// let a: int = 5;
+    /// 'target' is what is assigned to and 'span' the value, for diagnostics
+    /// a = 5;
     /// or
-    /// let a = 5;
+    /// a.b[0] = 5;
     Assign {
         id: Expr,
         expr: Expr,
+        line: usize,
+        target: Span,
+        span: Span
     },
 
     /// Function call
+    /// 'id' is the name in the source until the semantic pass finds the
+    /// function, then its name in ir
+    /// 'span' is the whole call and 'arg_spans' each argument, for diagnostics
     /// write(5);
     FuncCall {
         id: String,
         args: Vec<Box<Expr>>,
+        line: usize,
+        span: Span,
+        arg_spans: Vec<Span>
     },
 
     /// Struct definition
+    /// 'doc' holds the '///' comments written above it
+    /// 'token' is the identifier, and 'field_tokens' and 'type_spans' the
+    /// identifier and type of each field, for diagnostics
     /// struct foo {
     ///     bar: int
     /// }
     Struct {
         id: String,
-        fields: Vec<(String, String)>
+        fields: Vec<(String, Type)>,
+        doc: Option<String>,
+        token: Token,
+        field_tokens: Vec<Token>,
+        type_spans: Vec<Span>
     },
 
     /// Block
@@ -89,46 +146,129 @@ pub enum Node {
     },
 
     /// While loop
+    /// 'span' is the condition, for diagnostics
     /// while condition {
     ///     // Statements
     /// }
     While {
         cond: Expr,
         body: Box<Node>,
-        begin: usize,
-        end: usize
+        line: usize,
+        span: Span
     },
 
     Non,
 }
 
+/// Implement functions for a node
+impl Node {
+    /// Returns whether or not every path through the node ends in a 'ret'
+    pub fn always_returns(&self) -> bool {
+        match self {
+            Node::Ret {..} => true,
+            Node::Block {statements} => statements.iter().any(|s| s.always_returns()),
+            Node::If {body, else_body: Some(else_body), ..} => body.always_returns() && else_body.always_returns(),
+
+            // A 'while true' without a 'break' never falls through
+            Node::While {cond: Expr::Bool(true), body, ..} => !body.breaks(),
+            _ => false
+        }
+    }
+
+    /// Returns whether or not the node holds a 'break' out of the loop it is
+    /// in, leaving out the loops nested inside it
+    fn breaks(&self) -> bool {
+        match self {
+            Node::Pause {is_break, ..} => *is_break,
+            Node::Block {statements} => statements.iter().any(|s| s.breaks()),
+            Node::If {body, else_body, ..} => body.breaks() || else_body.as_ref().is_some_and(|e| e.breaks()),
+            _ => false
+        }
+    }
+
+    /// Returns the line a statement starts on, or None for the nodes that
+    /// only hold other statements
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Node::Let {line, ..} | Node::FuncDecl {line, ..} | Node::Pause {line, ..} | Node::If {line, ..} |
+            Node::Assign {line, ..} | Node::FuncCall {line, ..} | Node::While {line, ..} => Some(*line),
+            Node::Ret {span, ..} => Some(span.start.lineno),
+            _ => None
+        }
+    }
+
+    /// Describes the kind of statement, for messages about it
+    /// # Example
+    /// the 'while' loop
+    pub fn describe(&self) -> String {
+        match self {
+            Node::Let {mutable: false, ..} => "the 'let' statement".to_string(),
+            Node::Let {mutable: true, ..} => "the 'var' statement".to_string(),
+            Node::FuncDecl {id, ..} => format!("the function '{}'", id),
+            Node::Ret {..} => "the 'ret' statement".to_string(),
+            Node::Pause {is_break: true, ..} => "the 'break' statement".to_string(),
+            Node::Pause {is_break: false, ..} => "the 'continue' statement".to_string(),
+            Node::If {..} => "the 'if' statement".to_string(),
+            Node::Assign {..} => "the assignment".to_string(),
+            Node::FuncCall {id, ..} => format!("the call to '{}'", id),
+            Node::While {..} => "the 'while' loop".to_string(),
+            _ => "the statement".to_string()
+        }
+    }
+}
+
+/// Stores information for each argument of a function declaration
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Argument {
+    /// Identifier of the argument
+    pub id: String,
+
+    /// Type of the argument
+    pub typ: Type,
+
+    /// Stores the id of the argument in ir for code generation
+    pub gen_id: String,
+
+    /// Whether or not the argument is passed by reference
+    /// Reference arguments point at the caller's variable instead of a copy
+    pub is_ref: bool,
+
+    /// Identifier token of the argument, for diagnostics
+    pub token: Token,
+
+    /// Where the type of the argument is written, for diagnostics
+    pub type_span: Span,
+}
+
 /// An enum to store each possible expression node
+/// The types in it are Error until the semantic pass finds them, and the
+/// tokens and spans are kept for its diagnostics
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Expr {
     /// Integer
     /// # Example
-    /// ```rust
+    /// ```text
     /// let my_int: int = 10;
     /// ```
     Int(String),
 
     /// Character
     /// # Example
-    /// ```rust
+    /// ```text
     /// let my_chr: char = 'a';
     /// ```
     Chr(char),
 
     /// Decimal number
     /// # Example
-    /// ```rust
+    /// ```text
     /// let my_dec: dec = 5.5;
     /// ```
     Dec(String),
 
     /// Boolean value (true or false)
     /// # Example
-    /// ```rust
+    /// ```text
     /// let my_true: bool = true;
     /// let my_false: bool = false;
     /// ```
@@ -136,35 +276,45 @@ pub enum Expr {
 
     /// String value
     /// # Example
-    /// ```rust
+    /// ```text
     /// let my_str: string = "This is my first string";
+    /// ```
     Str(String),
 
     /// Identifier
-    Id(String, String, String),
+    /// Its type, its name in ir and where it is written
+    Id(String, Type, String, Token),
 
     /// Array
+    /// 'spans' is each element
     Array {
         values: Vec<Expr>,
-        typ: String
+        typ: Type,
+        spans: Vec<Span>
     },
 
     /// An index
+    /// 'token' is the '[' and 'span' the index
     /// # Example
     /// `array[num]` or `string[num]`
     IndexedValue {
         src: Box<Expr>,
         index: Box<Expr>,
-        new_typ: String
+        new_typ: Type,
+        token: Token,
+        span: Span
     },
 
     /// Binary operator
+    /// 'token' is the operator and 'span' the whole operation
     /// # Example
     /// `5 + 6`
     BinaryOperator {
         oper: String,
         left: Box<Expr>,
         right: Box<Expr>,
+        token: Token,
+        span: Span
     },
 
     /// Unary operator
@@ -173,221 +323,352 @@ pub enum Expr {
     UnaryOperator {
         oper: String,
         child: Box<Expr>,
+        span: Span
     },
 
     /// New struct
+    /// 'span' goes from the identifier to the ')', and 'spans' is each value
     /// # Example
     /// let foo: Foo = new Foo(5, 6, 7);
     NewStruct {
         id: String,
-        fields: Vec<Expr>
+        fields: Vec<Expr>,
+        span: Span,
+        spans: Vec<Span>
     },
 
     /// Struct dot identifier
+    /// 'token' is the field
     /// # Example
     /// let s: string = Foo.bar;
     StructDot {
         id: Box<Expr>,
         id2: String,
-        typ: String,
-        field_num: i32
+        typ: Type,
+        field_num: i32,
+        token: Token
     },
 
     /// Function call
+    /// 'id' is the name in the source until the semantic pass finds the
+    /// function, then its name in ir
+    /// 'span' is the whole call and 'arg_spans' each argument
     /// write(5);
     FuncCall {
         id: String,
-        typ: String,
-        args: Vec<Box<Expr>>
+        typ: Type,
+        args: Vec<Box<Expr>>,
+        span: Span,
+        arg_spans: Vec<Span>
+    },
+
+    /// If expression
+    /// 'token' is the 'if' and 'span' the condition
+    /// # Example
+    /// let x = if a > 5 { 1 } else { 2 };
+    If {
+        cond: Box<Expr>,
+        body: Box<Expr>,
+        else_body: Box<Expr>,
+        typ: Type,
+        token: Token,
+        span: Span
+    },
+
+    /// Conversion between number types
+    /// # Example
+    /// let d: dec = 5 as dec;
+    Cast {
+        expr: Box<Expr>,
+        typ: Type,
+        span: Span
+    },
+
+    /// Size of a type in bytes, which the semantic pass turns into an int
+    /// 'span' is the type
+    /// # Example
+    /// let n = sizeof(Point);
+    SizeOf {
+        typ: Type,
+        span: Span
     },
 
     Non,
 }
 
 /// Returns the type of an operation "oper" "child" (i.e., -5 results in int)
-fn unary_rules<'u>(oper: &'u String, child: &'u Box<Expr>) -> &'static str {
-    match oper.as_str() {
+fn unary_rules(oper: &str, child: &Expr) -> Type {
+    match oper {
         "-" => match (*child).validate() {
-            "int" => "int",
-            "dec" => "dec",
-            _ => "error"
+            Type::Int => Type::Int,
+            Type::Long => Type::Long,
+            Type::Dec => Type::Dec,
+            _ => Type::Error
         },
         "not" => match (*child).validate() {
-            _ => "bool",
-        }
-        _ => "error"
+            Type::Bool => Type::Bool,
+            _ => Type::Error
+        },
+        _ => Type::Error
     }
 }
 
 /// Returns the type of an operation "left" "oper" "right" (i.e., 5 + 5 results in int)
-fn binary_rules<'b>(oper: &'b String, left: &'b Box<Expr>, right: &'b Box<Expr>) -> &'static str {
-    match oper.as_str() {
+fn binary_rules(oper: &str, left: &Expr, right: &Expr) -> Type {
+    match oper {
         // Match the operator
         "+" => match (*left).validate() {
             // After matching the operator, match the left side
-            "int" => match (*right).validate() {
+            Type::Int => match (*right).validate() {
                 // Once the left side is known, match the right side
-                "int" | "char" => "int",
-                _ => "error",
+                Type::Int | Type::Char => Type::Int,
+                _ => Type::Error,
             },
-            "dec" => match (*right).validate() {
-                "dec" => "dec",
-                _ => "error",
+            Type::Long => match (*right).validate() {
+                Type::Long => Type::Long,
+                _ => Type::Error,
             },
-            "char" => match (*right).validate() {
-                "int" | "char" => "char",
-                _ => "error"
+            Type::UInt => match (*right).validate() {
+                Type::UInt => Type::UInt,
+                _ => Type::Error,
             },
-            _ => "error",
+            Type::Dec => match (*right).validate() {
+                Type::Dec => Type::Dec,
+                _ => Type::Error,
+            },
+            Type::Char => match (*right).validate() {
+                Type::Int | Type::Char => Type::Char,
+                _ => Type::Error
+            },
+            _ => Type::Error,
         },
         "-" | "*" => match (*left).validate() {
-            "int" => match (*right).validate() {
-                "int" | "char" => "int",
-                _ => "error",
+            Type::Int => match (*right).validate() {
+                Type::Int | Type::Char => Type::Int,
+                _ => Type::Error,
+            },
+            Type::Char => match(*right).validate() {
+                Type::Int | Type::Char => Type::Char,
+                _ => Type::Error
+            },
+            Type::Long => match (*right).validate() {
+                Type::Long => Type::Long,
+                _ => Type::Error,
             },
-            "char" => match(*right).validate() {
-                "int" | "char" => "char",
-                _ => "error"
+            Type::UInt => match (*right).validate() {
+                Type::UInt => Type::UInt,
+                _ => Type::Error,
             },
-            "dec" => match (*right).validate() {
-                "dec" => "dec",
-                _ => "error",
+            Type::Dec => match (*right).validate() {
+                Type::Dec => Type::Dec,
+                _ => Type::Error,
             },
-            _ => "error",
+            _ => Type::Error,
         },
         "/" => match (*left).validate() {
-            "int" => match (*right).validate() {
-                "int" => "dec",
-                _ => "error",
+            Type::Int => match (*right).validate() {
+                Type::Int => Type::Dec,
+                _ => Type::Error,
             },
-            "dec" => match (*right).validate() {
-                "dec" => "dec",
-                _ => "error",
+            Type::Long => match (*right).validate() {
+                Type::Long => Type::Dec,
+                _ => Type::Error,
             },
-            _ => "error",
+
+            // Unsigned division stays whole, since it is mostly used on bits
+            // and indexes
+            Type::UInt => match (*right).validate() {
+                Type::UInt => Type::UInt,
+                _ => Type::Error,
+            },
+            Type::Dec => match (*right).validate() {
+                Type::Dec => Type::Dec,
+                _ => Type::Error,
+            },
+            _ => Type::Error,
+        },
+        "%" => match (*left).validate() {
+            t @ (Type::Int | Type::Long | Type::UInt) if (*right).validate() == t => t,
+            _ => Type::Error,
         },
         "==" | "!=" => match (*left).validate() {
-            t if (*right).validate() == t => "bool",
-            _ => "error",
+            t if (*right).validate() == t => Type::Bool,
+            _ => Type::Error,
         },
         ">=" | "<=" | ">" | "<" => match (*left).validate() {
-            "int" => match (*right).validate() {
-                "int" | "char" => "bool",
-                _ => "error",
+            Type::Int => match (*right).validate() {
+                Type::Int | Type::Char => Type::Bool,
+                _ => Type::Error,
             },
-            "char" => match (*right).validate() {
-                "int" | "char" => "bool",
-                _ => "error",
+            Type::Char => match (*right).validate() {
+                Type::Int | Type::Char => Type::Bool,
+                _ => Type::Error,
             },
-            "dec" => match (*right).validate() {
-                "dec" => "bool",
-                _ => "error",
+            Type::Long => match (*right).validate() {
+                Type::Long => Type::Bool,
+                _ => Type::Error,
             },
-            _ => "error",
+            Type::UInt => match (*right).validate() {
+                Type::UInt => Type::Bool,
+                _ => Type::Error,
+            },
+            Type::Dec => match (*right).validate() {
+                Type::Dec => Type::Bool,
+                _ => Type::Error,
+            },
+            _ => Type::Error,
         },
         "and" | "or" => match (*left).validate() {
-            "bool" => match (*right).validate() {
-                "bool" => "bool",
-                _ => "error",
+            Type::Bool => match (*right).validate() {
+                Type::Bool => Type::Bool,
+                _ => Type::Error,
             },
-            _ => "error",
+            _ => Type::Error,
         },
-        _ => "error",
+        _ => Type::Error,
     }
 }
 
 /// Implement functions for an expression node
 impl Expr {
     /// Validates the type of an expression
-    pub fn validate(&self) -> &str {
+    pub fn validate(&self) -> Type {
         match self {
             // Match each kind of expression node to find it's type
-            Expr::Int(_i) => "int",
-            Expr::Chr(_c) => "char",
-            Expr::Dec(_d) => "dec",
-            Expr::Bool(_b) => "bool",
-            Expr::Str(_s) => "string",
-            Expr::Id(_i, t, _gen_id) => t,
-            Expr::Array {typ, ..} => typ.as_str(),
-            Expr::IndexedValue {new_typ, ..} => new_typ.as_str(),
-            Expr::BinaryOperator {oper, left, right} => binary_rules(oper, left, right),
-            Expr::UnaryOperator {oper, child} => unary_rules(oper, child),
-            Expr::NewStruct {id, ..} => id,
-            Expr::StructDot {typ, ..} => typ,
-            Expr::FuncCall {typ, ..} => typ.as_str(),
-            Expr::Non => "",
+            // Whole numbers that don't fit in an int are longs
+            Expr::Int(i) if i.parse::<i32>().is_err() => Type::Long,
+            Expr::Int(_i) => Type::Int,
+            Expr::Chr(_c) => Type::Char,
+            Expr::Dec(_d) => Type::Dec,
+            Expr::Bool(_b) => Type::Bool,
+            Expr::Str(_s) => Type::Str,
+            Expr::Id(_i, t, _gen_id, _token) => t.clone(),
+            Expr::Array {typ, ..} => typ.clone(),
+            Expr::IndexedValue {new_typ, ..} => new_typ.clone(),
+            Expr::BinaryOperator {oper, left, right, ..} => binary_rules(oper, left, right),
+            Expr::UnaryOperator {oper, child, ..} => unary_rules(oper, child),
+            Expr::NewStruct {id, ..} => Type::Struct(id.clone()),
+            Expr::StructDot {typ, ..} => typ.clone(),
+            Expr::FuncCall {typ, ..} => typ.clone(),
+            Expr::If {typ, ..} => typ.clone(),
+            Expr::Cast {typ, ..} => typ.clone(),
+            Expr::SizeOf {..} => Type::Int,
+            Expr::Non => Type::Error,
+        }
+    }
+
+    /// Suggests an 'as' cast when this expression is the wrong kind of number
+    /// for a type, as a note and a help
+    pub fn cast_suggestion(&self, to: &Type) -> Option<(String, String)> {
+        let from = self.validate();
+        if from == *to || !from.casts_to(to) {
+            return None;
         }
+        let help = match self {
+            Expr::Id(id, ..) => format!("Convert it with '{} as {}'", id, to),
+            Expr::Int(i) | Expr::Dec(i) => format!("Convert it with '{} as {}'", i, to),
+            Expr::Chr(c) => format!("Convert it with ''{}' as {}'", c, to),
+            _ => format!("Convert it by adding 'as {}' after it", to)
+        };
+        return Some((format!("'{}' and '{}' are both numbers, but they aren't converted automatically", from, to), help));
     }
 }
 
 #[test]
 fn test_validate() {
-    assert_eq!(Expr::Int(5).validate(), "int");
-    assert_eq!(Expr::Chr('a').validate(), "char");
-    assert_eq!(Expr::Dec("16.788".to_string()).validate(), "dec");
-    assert_eq!(Expr::Bool(true).validate(), "bool");
-    assert_eq!(Expr::Str("Hello, World!".to_string()).validate(), "string");
-    assert_eq!(Expr::Id("foo".to_string(), "int".to_string(), "%.0".to_string()).validate(), "int");
-    assert_eq!(Expr::Array {values: vec![], typ: "int[]".to_string()}.validate(), "int[]");
-    assert_eq!(Expr::IndexedValue {src: Box::new(Expr::Non), index: Box::new(Expr::Int(0)), new_typ: "int".to_string()}.validate(), "int");
-    assert_eq!(Expr::NewStruct {id: "Foo".to_string(), fields: vec![]}.validate(), "Foo");
-    assert_eq!(Expr::StructDot {id: Box::new(Expr::Non), id2: "def".to_string(), typ: "int".to_string(), field_num: 0}.validate(), "int");
+    let token = Token {typ: super::lexer::token::TokenType::Id, value: "foo".to_string(), lineno: 1, col: 0, len: 3, source: std::rc::Rc::new(super::lexer::source::SourceMap::new("", "foo"))};
+    let span = Span::token(&token);
+    assert_eq!(Expr::Int("5".to_string()).validate(), Type::Int);
+    assert_eq!(Expr::Int("3000000000".to_string()).validate(), Type::Long);
+    assert_eq!(Expr::Chr('a').validate(), Type::Char);
+    assert_eq!(Expr::Dec("16.788".to_string()).validate(), Type::Dec);
+    assert_eq!(Expr::Bool(true).validate(), Type::Bool);
+    assert_eq!(Expr::Str("Hello, World!".to_string()).validate(), Type::Str);
+    assert_eq!(Expr::Id("foo".to_string(), Type::Int, "%foo.0".to_string(), token.clone()).validate(), Type::Int);
+    assert_eq!(Expr::Array {values: vec![], typ: Type::Array(Box::new(Type::Int)), spans: vec![]}.validate(), Type::Array(Box::new(Type::Int)));
+    assert_eq!(Expr::IndexedValue {src: Box::new(Expr::Non), index: Box::new(Expr::Int("0".to_string())), new_typ: Type::Int, token: token.clone(), span: span.clone()}.validate(), Type::Int);
+    assert_eq!(Expr::NewStruct {id: "Foo".to_string(), fields: vec![], span: span.clone(), spans: vec![]}.validate(), Type::Struct("Foo".to_string()));
+    assert_eq!(Expr::StructDot {id: Box::new(Expr::Non), id2: "def".to_string(), typ: Type::Int, field_num: 0, token: token.clone()}.validate(), Type::Int);
+    assert_eq!(Expr::Cast {expr: Box::new(Expr::Int("5".to_string())), typ: Type::Dec, span: span.clone()}.validate(), Type::Dec);
+    assert_eq!(Expr::SizeOf {typ: Type::Str, span: span}.validate(), Type::Int);
 }
 
 #[test]
 fn test_semantics() {
-    let int =    Box::new(Expr::Int(5));
+    let int =    Box::new(Expr::Int("5".to_string()));
     let dec =    Box::new(Expr::Dec("5.5".to_string()));
     let boo =    Box::new(Expr::Bool(true));
     let string = Box::new(Expr::Str("test".to_string()));
     
-    assert_eq!(unary_rules(&"-".to_string(), &int),        "int");
-    assert_eq!(unary_rules(&"-".to_string(), &dec),        "dec");
-    assert_eq!(unary_rules(&"-".to_string(), &string),     "error");
+    assert_eq!(unary_rules("-", &int),        Type::Int);
+    assert_eq!(unary_rules("-", &dec),        Type::Dec);
+    assert_eq!(unary_rules("-", &string),     Type::Error);
+
+    assert_eq!(unary_rules("not", &boo),      Type::Bool);
+    assert_eq!(unary_rules("not", &dec),      Type::Error);
 
-    assert_eq!(unary_rules(&"not".to_string(), &boo),      "bool");
-    assert_eq!(unary_rules(&"not".to_string(), &dec),      "bool");
 
+    assert_eq!(binary_rules("+", &int, &int), Type::Int);
+    assert_eq!(binary_rules("-", &int, &int), Type::Int);
+    assert_eq!(binary_rules("*", &int, &int), Type::Int);
+    assert_eq!(binary_rules("/", &int, &int), Type::Dec);
 
-    assert_eq!(binary_rules(&"+".to_string(), &int, &int), "int");
-    assert_eq!(binary_rules(&"-".to_string(), &int, &int), "int");
-    assert_eq!(binary_rules(&"*".to_string(), &int, &int), "int");
-    assert_eq!(binary_rules(&"/".to_string(), &int, &int), "dec");
+    assert_eq!(binary_rules("+", &dec, &dec), Type::Dec);
+    assert_eq!(binary_rules("-", &dec, &dec), Type::Dec);
+    assert_eq!(binary_rules("*", &dec, &dec), Type::Dec);
+    assert_eq!(binary_rules("/", &dec, &dec), Type::Dec);
 
-    assert_eq!(binary_rules(&"+".to_string(), &dec, &dec), "dec");
-    assert_eq!(binary_rules(&"-".to_string(), &dec, &dec), "dec");
-    assert_eq!(binary_rules(&"*".to_string(), &dec, &dec), "dec");
-    assert_eq!(binary_rules(&"/".to_string(), &dec, &dec), "dec");
+    assert_eq!(binary_rules("<", &dec, &dec), Type::Bool);
+    assert_eq!(binary_rules("<", &int, &int), Type::Bool);
+    assert_eq!(binary_rules("<", &string, &string), Type::Error);
 
-    assert_eq!(binary_rules(&"<".to_string(), &dec, &dec), "bool");
-    assert_eq!(binary_rules(&"<".to_string(), &int, &int), "bool");
-    assert_eq!(binary_rules(&"<".to_string(), &string, &string), "error");
+    assert_eq!(binary_rules(">", &dec, &dec), Type::Bool);
+    assert_eq!(binary_rules(">", &int, &int), Type::Bool);
+    assert_eq!(binary_rules(">", &string, &string), Type::Error);
 
-    assert_eq!(binary_rules(&">".to_string(), &dec, &dec), "bool");
-    assert_eq!(binary_rules(&">".to_string(), &int, &int), "bool");
-    assert_eq!(binary_rules(&">".to_string(), &string, &string), "error");
+    assert_eq!(binary_rules("<=", &dec, &dec), Type::Bool);
+    assert_eq!(binary_rules("<=", &int, &int), Type::Bool);
+    assert_eq!(binary_rules("<=", &string, &string), Type::Error);
 
-    assert_eq!(binary_rules(&"<=".to_string(), &dec, &dec), "bool");
-    assert_eq!(binary_rules(&"<=".to_string(), &int, &int), "bool");
-    assert_eq!(binary_rules(&"<=".to_string(), &string, &string), "error");
+    assert_eq!(binary_rules(">=", &dec, &dec), Type::Bool);
+    assert_eq!(binary_rules(">=", &int, &int), Type::Bool);
+    assert_eq!(binary_rules(">=", &string, &string), Type::Error);
 
-    assert_eq!(binary_rules(&">=".to_string(), &dec, &dec), "bool");
-    assert_eq!(binary_rules(&">=".to_string(), &int, &int), "bool");
-    assert_eq!(binary_rules(&">=".to_string(), &string, &string), "error");
+    assert_eq!(binary_rules("==", &dec, &dec), Type::Bool);
+    assert_eq!(binary_rules("==", &int, &int), Type::Bool);
+    assert_eq!(binary_rules("==", &string, &dec), Type::Error);
 
-    assert_eq!(binary_rules(&"==".to_string(), &dec, &dec), "bool");
-    assert_eq!(binary_rules(&"==".to_string(), &int, &int), "bool");
-    assert_eq!(binary_rules(&"==".to_string(), &string, &dec), "error");
+    assert_eq!(binary_rules("!=", &dec, &dec), Type::Bool);
+    assert_eq!(binary_rules("!=", &int, &int), Type::Bool);
+    assert_eq!(binary_rules("!=", &string, &dec), Type::Error);
 
-    assert_eq!(binary_rules(&"!=".to_string(), &dec, &dec), "bool");
-    assert_eq!(binary_rules(&"!=".to_string(), &int, &int), "bool");
-    assert_eq!(binary_rules(&"!=".to_string(), &string, &dec), "error");
+    assert_eq!(binary_rules("and", &boo, &boo), Type::Bool);
+    assert_eq!(binary_rules("and", &int, &int), Type::Error);
+    assert_eq!(binary_rules("and", &string, &dec), Type::Error);
 
-    assert_eq!(binary_rules(&"and".to_string(), &boo, &boo), "bool");
-    assert_eq!(binary_rules(&"and".to_string(), &int, &int), "error");
-    assert_eq!(binary_rules(&"and".to_string(), &string, &dec), "error");
+    assert_eq!(binary_rules("or", &boo, &boo), Type::Bool);
+    assert_eq!(binary_rules("or", &int, &int), Type::Error);
+    assert_eq!(binary_rules("or", &string, &dec), Type::Error);
+}
 
-    assert_eq!(binary_rules(&"or".to_string(), &boo, &boo), "bool");
-    assert_eq!(binary_rules(&"or".to_string(), &int, &int), "error");
-    assert_eq!(binary_rules(&"or".to_string(), &string, &dec), "error");
+#[test]
+fn test_always_returns() {
+    let token = Token {typ: super::lexer::token::TokenType::Ret, value: "ret".to_string(), lineno: 1, col: 0, len: 3, source: std::rc::Rc::new(super::lexer::source::SourceMap::new("", "ret 0;"))};
+    let ret = Box::new(Node::Ret {expr: Expr::Int("0".to_string()), span: Span::token(&token)});
+    let write = Box::new(Node::FuncCall {id: "write".to_string(), args: vec![], line: 1, span: Span::token(&token), arg_spans: vec![]});
+    let block = |statements: Vec<Box<Node>>| Box::new(Node::Block {statements: statements});
+    let if_ = |body: Box<Node>, else_body: Option<Box<Node>>| Node::If {cond: Expr::Bool(true), body: body, else_body: else_body, line: 1, span: Span::token(&token)};
+
+    assert!(block(vec![write.clone(), ret.clone()]).always_returns());
+    assert!(!block(vec![write.clone()]).always_returns());
+    assert!(if_(block(vec![ret.clone()]), Some(block(vec![ret.clone()]))).always_returns());
+    assert!(!if_(block(vec![ret.clone()]), Some(block(vec![write.clone()]))).always_returns());
+    assert!(!if_(block(vec![ret.clone()]), None).always_returns());
+
+    let break_ = Box::new(Node::Pause {is_break: true, line: 1, token: token.clone()});
+    let while_ = |cond: Expr, body: Box<Node>| Node::While {cond: cond, body: body, line: 1, span: Span::token(&token)};
+    assert!(while_(Expr::Bool(true), block(vec![ret.clone()])).always_returns());
+    assert!(while_(Expr::Bool(true), block(vec![Box::new(while_(Expr::Bool(true), block(vec![break_.clone()])))])).always_returns());
+    assert!(!while_(Expr::Bool(true), block(vec![Box::new(if_(block(vec![break_.clone()]), None)), ret.clone()])).always_returns());
+    assert!(!while_(Expr::Bool(false), block(vec![ret.clone()])).always_returns());
 }
@@ -1,28 +1,133 @@
+#[cfg(feature = "llvm")]
+pub mod llvm;
+
 use super::ast::Node;
 use super::ast::Expr;
+use super::ast::Argument;
+use super::types::Type;
+use super::lexer::source::SourceMap;
+use crate::escape::Escapes;
+use crate::target;
+use crate::verify;
+use crate::verify::Problem;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Start of the main function
+const MAIN_HEADER: &str = "define i32 @main() {";
 
 /// Converts a Gizmo type to an llvm ir type
-fn type_of(typ: String) -> String {
-    let struct_type = format!("%{}", typ);
-    match typ.as_str() {
-        "int"    => "i32",
-        "dec"    => "double",
-        "bool"   => "i1",
-        "char"   => "i8",
-        "string" => "i8*",
-        "void" => "void",
-        arr if arr.ends_with(']') => "%.Arr",
-        _ => struct_type.as_str()
-    }.to_string()
+fn type_of(typ: Type) -> String {
+    match typ {
+        Type::Int    => "i32".to_string(),
+        Type::Long   => "i64".to_string(),
+        Type::UInt   => "i32".to_string(),
+        Type::Dec    => "double".to_string(),
+        Type::Bool   => "i1".to_string(),
+        Type::Char   => "i8".to_string(),
+        Type::Str    => "%.Str".to_string(),
+        Type::Void   => "void".to_string(),
+        Type::Array(_) => "%.Arr".to_string(),
+        Type::Struct(id) => format!("%{}", id),
+        Type::Error  => String::new()
+    }
+}
+
+/// Writes the bytes of a string the way they go inside of an llvm 'c"..."'
+/// Anything other than printable ascii, and '"' and '\\', is escaped as
+/// hex
+/// # Example
+/// a"b\n -> a\22b\0A
+fn escape_string(s: &str) -> String {
+    let mut escaped = String::new();
+    for b in s.bytes() {
+        if (b' '..=b'~').contains(&b) && b != b'"' && b != b'\\' {
+            escaped.push(b as char);
+        } else {
+            escaped.push_str(format!("\\{:02X}", b).as_str());
+        }
+    }
+    return escaped;
+}
+
+/// Returns whether or not an expression reads a value that is stored
+/// somewhere, like a variable or a field, instead of making a new one
+fn is_place(expr: &Expr) -> bool {
+    match expr {
+        Expr::Id(..) | Expr::IndexedValue {..} | Expr::StructDot {..} => true,
+        Expr::If {body, else_body, ..} => is_place(body) || is_place(else_body),
+        _ => false
+    }
+}
+
+/// Adds the debug info of a function to its header
+/// # Example
+/// define i32 @main() { -> define i32 @main() !dbg !3 {
+fn debug_header(header: &str, subprogram: &str) -> String {
+    let start = header.strip_suffix(" {").unwrap_or(header);
+    return format!("{} !dbg {} {{", start, subprogram);
+}
+
+/// Returns whether or not values of a type are structs, which are passed to
+/// and returned from functions through pointers
+fn is_struct(typ: &Type) -> bool {
+    matches!(typ, Type::Struct(_))
+}
+
+/// Finds the printf format for a Gizmo type
+fn format_of(typ: Type) -> &'static str {
+    match typ {
+        Type::Int  => "%d",
+        Type::Long => "%lld",
+        Type::UInt => "%u",
+        Type::Dec  => "%f",
+        Type::Str  => "%.*s",
+        Type::Char => "%c",
+        Type::Bool => "%s",
+        _ => ""
+    }
 }
 
 /// Converts a Gizmo operator to an llvm ir operator
-fn type_of_oper(oper: String) -> String {
+/// 'typ' is the type of the operands, 'dec' needs the floating point
+/// instructions and 'uint' the unsigned ones
+fn type_of_oper(oper: String, typ: &Type) -> String {
+    if *typ == Type::Dec {
+        match oper.as_str() {
+            "+" => return "fadd".to_string(),
+            "-" => return "fsub".to_string(),
+            "*" => return "fmul".to_string(),
+            "/" => return "fdiv".to_string(),
+
+            // Ordered comparisons are false when either side is NaN, except
+            // for '!=' which is true like in C
+            "==" => return "fcmp oeq".to_string(),
+            "!=" => return "fcmp une".to_string(),
+            "<"  => return "fcmp olt".to_string(),
+            ">"  => return "fcmp ogt".to_string(),
+            "<=" => return "fcmp ole".to_string(),
+            ">=" => return "fcmp oge".to_string(),
+            _ => {}
+        }
+    }
+    if *typ == Type::UInt {
+        match oper.as_str() {
+            "/"  => return "udiv".to_string(),
+            "%"  => return "urem".to_string(),
+            "<"  => return "icmp ult".to_string(),
+            ">"  => return "icmp ugt".to_string(),
+            "<=" => return "icmp ule".to_string(),
+            ">=" => return "icmp uge".to_string(),
+            _ => {}
+        }
+    }
     match oper.as_str() {
         "+"   => "add",
         "-"   => "sub",
         "*"   => "mul",
         "/"   => "sdiv",
+        "%"   => "srem",
         "=="  => "icmp eq",
         "!="  => "icmp ne",
         "<"   => "icmp slt",
@@ -35,9 +140,268 @@ fn type_of_oper(oper: String) -> String {
     }.to_string()
 }
 
+/// Number of bits in the llvm type of an integer, or None if it isn't one
+fn int_width(typ: &str) -> Option<u32> {
+    match typ {
+        "i1"  => Some(1),
+        "i8"  => Some(8),
+        "i32" => Some(32),
+        "i64" => Some(64),
+        _ => None
+    }
+}
+
+/// Reads an integer constant, with bools as 1 and 0
+fn int_constant(value: &str) -> Option<i64> {
+    match value {
+        "true"  => Some(1),
+        "false" => Some(0),
+        _ => value.parse::<i64>().ok()
+    }
+}
+
+/// Reads a double constant, written either as a decimal or as the bits in hex
+pub(crate) fn dec_constant(value: &str) -> Option<f64> {
+    if let Some(bits) = value.strip_prefix("0x") {
+        return u64::from_str_radix(bits, 16).ok().map(f64::from_bits);
+    }
+    return value.parse::<f64>().ok();
+}
+
+/// Writes an integer constant of a width, wrapping it around like llvm does
+fn int_value(value: i64, width: u32) -> String {
+    match width {
+        1 => (value & 1 == 1).to_string(),
+        8 => (value as i8).to_string(),
+        32 => (value as i32).to_string(),
+        _ => value.to_string()
+    }
+}
+
+/// Writes a double constant as its bits, which llvm reads back exactly
+pub(crate) fn dec_value(value: f64) -> String {
+    format!("0x{:016X}", value.to_bits())
+}
+
+/// Finds the result of an operation without emitting it, when both sides are
+/// constants or one side makes it an identity like 'x * 1'
+fn fold_operation(oper: &str, typ: &Type, left: &str, right: &str) -> Option<String> {
+    if *typ == Type::Dec {
+        let (l, r) = (dec_constant(left), dec_constant(right));
+        if let (Some(l), Some(r)) = (l, r) {
+            return Some(match oper {
+                "+" => dec_value(l + r),
+                "-" => dec_value(l - r),
+                "*" => dec_value(l * r),
+                "/" => dec_value(l / r),
+                "==" => (l == r).to_string(),
+                "!=" => (l != r).to_string(),
+                "<"  => (l < r).to_string(),
+                ">"  => (l > r).to_string(),
+                "<=" => (l <= r).to_string(),
+                ">=" => (l >= r).to_string(),
+                _ => return None
+            });
+        }
+
+        // Adding 0.0 would turn -0.0 into 0.0, so it isn't an identity
+        return match (oper, l, r) {
+            ("*", _, Some(1.0)) | ("/", _, Some(1.0)) => Some(left.to_string()),
+            ("*", Some(1.0), _) => Some(right.to_string()),
+            ("-", _, Some(r)) if r.to_bits() == 0 => Some(left.to_string()),
+            _ => None
+        };
+    }
+
+    let width = int_width(type_of(typ.clone()).as_str())?;
+    let (l, r) = (int_constant(left), int_constant(right));
+
+    // Unsigned constants are read back from their bits, so that dividing
+    // and comparing them works like it does at runtime
+    let (l, r) = if *typ == Type::UInt {(l.map(|v| v as u32 as i64), r.map(|v| v as u32 as i64))} else {(l, r)};
+    if let (Some(l), Some(r)) = (l, r) {
+        return Some(match oper {
+            "+" => int_value(l.wrapping_add(r), width),
+            "-" => int_value(l.wrapping_sub(r), width),
+            "*" => int_value(l.wrapping_mul(r), width),
+            "/" if r != 0 && !(r == -1 && (l == i32::MIN as i64 || l == i64::MIN)) => int_value(l / r, width),
+            "%" if r != 0 && r != -1 => int_value(l % r, width),
+            "and" => int_value(l & r, width),
+            "or"  => int_value(l | r, width),
+            "==" => (l == r).to_string(),
+            "!=" => (l != r).to_string(),
+            "<"  => (l < r).to_string(),
+            ">"  => (l > r).to_string(),
+            "<=" => (l <= r).to_string(),
+            ">=" => (l >= r).to_string(),
+            _ => return None
+        });
+    }
+    match (oper, l, r) {
+        ("+", Some(0), _) | ("*", Some(1), _) => Some(right.to_string()),
+        ("+", _, Some(0)) | ("-", _, Some(0)) | ("*", _, Some(1)) | ("/", _, Some(1)) => Some(left.to_string()),
+        ("*", Some(0), _) | ("*", _, Some(0)) => Some("0".to_string()),
+
+        // Bools
+        ("and", Some(1), _) | ("or", Some(0), _) => Some(right.to_string()),
+        ("and", _, Some(1)) | ("or", _, Some(0)) => Some(left.to_string()),
+        ("and", Some(0), _) | ("and", _, Some(0)) => Some("false".to_string()),
+        ("or", Some(1), _) | ("or", _, Some(1)) => Some("true".to_string()),
+        _ => None
+    }
+}
+
+/// Finds the result of converting a constant without emitting it
+fn fold_cast(op: &str, typ: &str, value: &str, typ2: &str) -> Option<String> {
+    let from = int_width(typ)?;
+    let v = int_constant(value)?;
+    match op {
+        "sitofp" => Some(dec_value(v as f64)),
+        "uitofp" if from < 64 => Some(dec_value((v & ((1 << from) - 1)) as f64)),
+        "trunc" | "sext" => Some(int_value(v, int_width(typ2)?)),
+
+        // The bits are read as unsigned
+        "zext" if from < 64 => Some(int_value(v & ((1 << from) - 1), int_width(typ2)?)),
+        _ => None
+    }
+}
+
+/// Returns whether or not a line of ir ends its block
+fn is_terminator(line: &str) -> bool {
+    let line = line.trim_start();
+    return line.starts_with("ret") || line.starts_with("br ") || line.starts_with("unreachable");
+}
+
+/// Finds the names in a line of ir that start with '%', like '%0' or
+/// '%.tmp1'
+/// Comments are skipped, since the source in them can have a '%'
+fn values_in(line: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    if line.trim_start().starts_with(';') {
+        return values;
+    }
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.' || bytes[i] == b'_') {
+            i += 1;
+        }
+        if i > start + 1 {
+            values.push(&line[start..i]);
+        }
+    }
+    return values;
+}
+
+/// The value a line of ir gives a name to, if any
+/// # Example
+/// %3 = add i32 %1, %2 -> %3
+fn defined_by(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    if !line.starts_with('%') {
+        return None;
+    }
+    return line.split_once(" = ").map(|l| l.0);
+}
+
+/// Returns whether or not a line of ir only makes a value, so that it can be
+/// dropped when nothing uses the value
+fn is_pure(line: &str) -> bool {
+    let op = line.split_once(" = ").and_then(|l| l.1.split(' ').next()).unwrap_or("");
+    return ["alloca", "load", "getelementptr", "bitcast", "extractvalue", "insertvalue", "add", "sub", "mul", "sdiv",
+        "fadd", "fsub", "fmul", "fdiv", "icmp", "fcmp", "select", "phi", "sitofp", "fptosi", "zext", "trunc",
+        "ptrtoint", "and", "or"].contains(&op);
+}
+
+/// Numbers the unnamed values of a function again, in the order they are
+/// made, after some were dropped
+/// A call whose value isn't named still takes a number
+fn renumber(lines: Vec<String>) -> Vec<String> {
+    let numbered = |v: &str| v.len() > 1 && v[1..].bytes().all(|b| b.is_ascii_digit());
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut next = 0;
+    for line in lines.iter() {
+        match defined_by(line) {
+            Some(def) if numbered(def) => {
+                names.insert(def.to_string(), format!("%{}", next));
+                next += 1;
+            },
+            Some(_) => {},
+            None => {
+                let line = line.trim_start();
+                if line.starts_with("call ") && !line.starts_with("call void") {
+                    next += 1;
+                }
+            }
+        }
+    }
+
+    let mut renamed = Vec::new();
+    for line in lines.iter() {
+        let mut new_line = String::new();
+        let mut rest = line.as_str();
+        for value in values_in(line) {
+            let at = rest.find(value).unwrap_or(0);
+            new_line.push_str(&rest[..at]);
+            new_line.push_str(names.get(value).map(|n| n.as_str()).unwrap_or(value));
+            rest = &rest[at + value.len()..];
+        }
+        new_line.push_str(rest);
+        renamed.push(new_line);
+    }
+    return renamed;
+}
+
+/// A basic block, a label followed by instructions that run in order
+pub struct Block {
+    /// Label of the block
+    pub name: String,
+
+    /// Instructions of the block
+    pub code: String,
+
+    /// Line of the statement each instruction was generated for
+    pub lines: Vec<usize>,
+}
+
+/// A function that is put aside while a function declared inside of it is
+/// built
+pub struct Function {
+    header: String,
+    blocks: Vec<Block>,
+    order: Vec<usize>,
+    block: usize,
+    ssa_num: i32,
+    allocas: String,
+    location: Option<String>,
+}
+
+/// The metadata that the debug info of a module refers back to
+pub struct DebugInfo {
+    /// The compile unit
+    pub unit: String,
+
+    /// The source file
+    pub file: String,
+
+    /// The type given to every function, since debuggers only need the
+    /// lines to step through them
+    pub fn_type: String,
+
+    /// The subprogram of the current function, which is empty until the
+    /// implicit main needs one
+    pub scope: String,
+}
+
 /// Stores information for an ir builder
 pub struct IRBuilder {
-    /// Contains the ir code
+    /// Contains the ir code of the types, globals and finished functions
     pub code: String,
 
     /// String that will be added to the end
@@ -46,45 +410,324 @@ pub struct IRBuilder {
     /// The number of ir variables created
     pub ssa_num: i32,
 
-    /// Saves off the number of ir variables created
-    pub save_ssa_num: i32,
-
     /// The number of strings created
     pub str_num: i32,
+
+    /// Allocas of the current function
+    /// They are hoisted into the entry block so allocas made inside loops
+    /// don't grow the stack on every iteration
+    pub allocas: String,
+
+    /// Start of the current function, like 'define i32 @main() {'
+    pub header: String,
+
+    /// Blocks of the current function, the first one is its entry
+    pub blocks: Vec<Block>,
+
+    /// Order the blocks are written in, which is the order instructions were
+    /// first added to them, since unnamed values have to be numbered in order
+    pub order: Vec<usize>,
+
+    /// The block that instructions are added to the end of
+    pub block: usize,
+
+    /// Functions that the current function is declared inside of
+    pub enclosing: Vec<Function>,
+
+    /// The number of unnamed allocas created
+    pub alloca_num: i32,
+
+    /// The number of labels created
+    pub label_num: usize,
+
+    /// Lines at the top of the module, like its target triple
+    pub module: String,
+
+    /// Numbered metadata, where the index is the number
+    pub metadata: Vec<String>,
+
+    /// Named metadata other than the module flags
+    /// # Example
+    /// !llvm.dbg.cu = !{!0}
+    pub named_metadata: Vec<String>,
+
+    /// Metadata of the module flags
+    pub module_flags: Vec<String>,
+
+    /// Debug location added to the instructions, if there is debug info
+    /// # Example
+    /// !DILocation(line: 2, scope: !7)
+    pub location: Option<String>,
+
+    /// Metadata of the debug locations instructions were given, which are
+    /// only made once each
+    pub locations: HashMap<String, String>,
+
+    /// Line of the statement that instructions are generated for
+    pub line: usize,
+
+    /// Types of pointers to the globals, which the verifier checks their
+    /// uses against
+    pub globals: HashMap<String, String>,
+
+    /// What is wrong with the ir of the finished functions
+    pub problems: Vec<Problem>,
+
+    /// Whether or not operations on constants are folded and values that
+    /// are never used are dropped, which '-O0' turns off
+    pub optimize: bool,
 }
 
 /// Implement functions for an ir builder
 impl IRBuilder {
     /// Constructs a new ir builder
     fn construct() -> IRBuilder {
-        IRBuilder {code: "define i32 @main() {\nentry:\n".to_string(), ends: "\tret i32 0\n}\n".to_string(), ssa_num: 0, str_num: 0, save_ssa_num: 0}
+        IRBuilder {code: String::new(), ends: String::new(), ssa_num: 0, str_num: 0, allocas: String::new(), header: MAIN_HEADER.to_string(), blocks: vec![Block {name: "entry".to_string(), code: String::new(), lines: Vec::new()}], order: vec![0], block: 0, enclosing: Vec::new(), alloca_num: 0, label_num: 0, module: target::module_header(target::host_triple().as_str()), metadata: Vec::new(), named_metadata: Vec::new(), module_flags: Vec::new(), location: None, locations: HashMap::new(), line: 0, globals: HashMap::new(), problems: Vec::new(), optimize: true}
+    }
+
+    /// Compiles the module for another target than the host
+    pub fn set_target(&mut self, triple: &str) {
+        self.module = target::module_header(triple);
+    }
+
+    /// Adds a comment to the end of the current block
+    /// # Example
+    /// ; let x = 5; (line 3)
+    fn create_comment(&mut self, comment: &str) {
+        self.blocks[self.block].code.push_str(format!("\t; {}\n", comment).as_str());
+        self.blocks[self.block].lines.push(self.line);
+    }
+
+    /// Adds an instruction to the end of the current block
+    /// With debug info, each line gets the current location
+    fn emit(&mut self, code: &str) {
+        let line = self.line;
+        self.blocks[self.block].lines.extend(code.matches('\n').map(|_| line));
+        match self.location.clone() {
+            Some(node) => {
+                let location = match self.locations.get(&node) {
+                    Some(location) => location.clone(),
+                    None => {
+                        let location = self.create_metadata(node.clone());
+                        self.locations.insert(node, location.clone());
+                        location
+                    }
+                };
+                let code = code.replace('\n', format!(", !dbg {}\n", location).as_str());
+                self.blocks[self.block].code.push_str(code.as_str());
+            },
+            None => self.blocks[self.block].code.push_str(code)
+        }
+    }
+
+    /// Creates an empty block at the end of the current function
+    /// # Example
+    /// assert0.ok:
+    fn create_block(&mut self, name: String) -> usize {
+        self.blocks.push(Block {name: name, code: String::new(), lines: Vec::new()});
+        return self.blocks.len() - 1;
+    }
+
+    /// Creates an empty block with a new numbered label
+    /// # Example
+    /// l0:
+    fn create_label_block(&mut self) -> usize {
+        let label = self.new_label();
+        return self.create_block(format!("l{}", label));
     }
 
-    /// Creates an alloca statement
+    /// Adds instructions to the end of the given block from now on
+    fn position_at_end(&mut self, block: usize) {
+        if !self.order.contains(&block) {
+            self.order.push(block);
+        }
+        self.block = block;
+    }
+
+    /// Creates a branch to a block
+    /// # Example
+    /// br label %l0
+    fn create_br(&mut self, block: usize) {
+        let code = format!("\tbr label %{}\n", self.blocks[block].name);
+        self.emit(code.as_str());
+    }
+
+    /// Creates a branch to one of two blocks
+    /// # Example
+    /// br i1 %0, label %l0, label %l1
+    fn create_cond_br(&mut self, cond: String, then: usize, otherwise: usize) {
+        let code = format!("\tbr i1 {}, label %{}, label %{}\n", cond, self.blocks[then].name, self.blocks[otherwise].name);
+        self.emit(code.as_str());
+    }
+
+    /// Makes up the number of a new label
+    /// # Example
+    /// l0:
+    fn new_label(&mut self) -> usize {
+        self.label_num += 1;
+        return self.label_num - 1;
+    }
+
+    /// Creates an alloca statement in the entry block of the current function
     /// # Example
-    /// %0 = alloca i32
+    /// %.tmp0 = alloca i32
     fn create_alloca(&mut self, typ: String, name: Option<String>) -> String {
-        self.code.push_str(format!("\t{} = alloca {}\n", name.clone().unwrap_or(format!("%{}", self.ssa_num)), typ).as_str());
+        // If the caller didn't specify a name, make one up
+        // Unnamed values would have to be numbered in order, which hoisting
+        // would break
+        let name = match name {
+            Some(n) => n,
+            None => {
+                self.alloca_num += 1;
+                format!("%.tmp{}", self.alloca_num - 1)
+            }
+        };
+        self.allocas.push_str(format!("\t{} = alloca {}\n", name, typ).as_str());
+        name
+    }
 
-        // If the caller didn't specify a name, use the ssa_num
-        if name == None {
-            self.ssa_num += 1;
+    /// Creates a phi node, which picks a value depending on the block that
+    /// jumped to the current one
+    /// It has to come before the other instructions of the block
+    /// # Example
+    /// %2 = phi i32 [ %0, %l0 ], [ %1, %l1 ]
+    fn create_phi(&mut self, typ: String, incoming: Vec<(String, usize)>) -> String {
+        let incoming: Vec<String> = incoming.iter().map(|(value, block)| format!("[ {}, %{} ]", value, self.blocks[*block].name)).collect();
+        self.emit(format!("\t%{} = phi {} {}\n", self.ssa_num, typ, incoming.join(", ")).as_str());
+        self.ssa_num += 1;
+        format!("%{}", self.ssa_num - 1)
+    }
+
+    /// Puts the blocks of the current function together, with its allocas
+    /// moved to the start of its entry block
+    /// The finished function is verified, and what is wrong with it is kept
+    /// to be reported instead of writing the module
+    fn finish_function(&mut self) -> String {
+        self.eliminate_dead_code();
+        let blocks: Vec<&Block> = self.order.iter().map(|i| &self.blocks[*i]).collect();
+        let problems = verify::verify_function(self.header.as_str(), self.allocas.as_str(), &blocks, &self.globals);
+        self.problems.extend(problems);
+        let mut code = format!("{}\n", self.header);
+        for i in self.order.iter() {
+            let block = &self.blocks[*i];
+            code.push_str(format!("{}:\n", block.name).as_str());
+            if *i == 0 {
+                code.push_str(self.allocas.as_str());
+            }
+            code.push_str(block.code.as_str());
+        }
+        code.push_str("}\n\n");
+        self.allocas.clear();
+        return code;
+    }
+
+    /// Removes the code of the current function that can't run or whose
+    /// values are never used
+    fn eliminate_dead_code(&mut self) {
+        // Nothing after the first terminator of a block runs
+        // Each instruction keeps the line of its statement
+        let mut blocks: Vec<Vec<(String, usize)>> = Vec::new();
+        for block in self.blocks.iter() {
+            let mut lines: Vec<(String, usize)> = Vec::new();
+            for (line, src) in block.code.lines().zip(block.lines.iter()) {
+                lines.push((line.to_string(), *src));
+                if is_terminator(line) {
+                    break;
+                }
+            }
+            blocks.push(lines);
+        }
+
+        // Blocks that no branch reaches from the entry don't run either
+        let mut reachable = vec![0];
+        let mut i = 0;
+        while i < reachable.len() {
+            let targets: Vec<&str> = blocks[reachable[i]].iter().map(|l| l.0.as_str()).filter(|l| is_terminator(l)).flat_map(|l| l.split("label %").skip(1)).collect();
+            for target in targets {
+                let name = target.split([',', ' ']).next().unwrap_or("");
+                if let Some(b) = self.blocks.iter().position(|b| b.name == name) {
+                    if !reachable.contains(&b) {
+                        reachable.push(b);
+                    }
+                }
+            }
+            i += 1;
+        }
+        self.order.retain(|b| reachable.contains(b));
+
+        // Drop values that nothing uses, along with allocas that are only
+        // stored into, until there are none left
+        // Dropping one can leave the values it used without a use
+        // Code that can't run is always removed, since llvm rejects
+        // instructions after a terminator, but this is left to '-O1' and up
+        let mut allocas: Vec<String> = self.allocas.lines().map(|l| l.to_string()).collect();
+        loop {
+            if !self.optimize {
+                break;
+            }
+            let mut uses: HashMap<String, usize> = HashMap::new();
+            let mut loaded: Vec<String> = Vec::new();
+            for line in allocas.iter().chain(self.order.iter().flat_map(|b| blocks[*b].iter().map(|l| &l.0))) {
+                let values = values_in(line);
+                let def = defined_by(line);
+                let store = line.trim_start().starts_with("store ");
+                for (n, value) in values.iter().enumerate() {
+                    if Some(*value) == def {
+                        continue;
+                    }
+                    *uses.entry(value.to_string()).or_insert(0) += 1;
+
+                    // The place a store writes to doesn't count as reading it
+                    if !(store && n + 1 == values.len()) {
+                        loaded.push(value.to_string());
+                    }
+                }
+            }
+
+            let dead_allocas: Vec<String> = allocas.iter().filter_map(|l| defined_by(l)).filter(|a| !loaded.iter().any(|v| v == a)).map(|a| a.to_string()).collect();
+            let is_dead = |line: &String| {
+                if let Some(def) = defined_by(line) {
+                    return is_pure(line) && (!uses.contains_key(def) || dead_allocas.iter().any(|a| a == def));
+                }
+                let store = line.trim_start().starts_with("store ");
+                return store && values_in(line).last().map(|v| dead_allocas.iter().any(|a| a == v)).unwrap_or(false);
+            };
+
+            let before = allocas.len() + blocks.iter().map(|b| b.len()).sum::<usize>();
+            allocas.retain(|l| !is_dead(l));
+            for b in self.order.iter() {
+                blocks[*b].retain(|l| !is_dead(&l.0));
+            }
+            if allocas.len() + blocks.iter().map(|b| b.len()).sum::<usize>() == before {
+                break;
+            }
+        }
+
+        // Put the code back together, numbering the values again
+        let mut lines = allocas.clone();
+        for b in self.order.iter() {
+            lines.extend(blocks[*b].iter().map(|l| l.0.clone()));
+        }
+        let mut lines = renumber(lines).into_iter();
+        self.allocas = lines.by_ref().take(allocas.len()).map(|l| format!("{}\n", l)).collect();
+        for b in self.order.iter() {
+            self.blocks[*b].code = lines.by_ref().take(blocks[*b].len()).map(|l| format!("{}\n", l)).collect();
+            self.blocks[*b].lines = blocks[*b].iter().map(|l| l.1).collect();
         }
-        format!("{}", name.clone().unwrap_or(format!("%{}", self.ssa_num - 1)))
     }
     
     /// Creates a store statement
     /// # Example
     /// %1 = store i32 5, i32 %0
     fn create_store(&mut self, src: String, dst: String, typ: String) {
-        self.code.push_str(format!("\tstore {} {}, {0}* {}\n", typ, src, dst).as_str());
+        self.emit(format!("\tstore {} {}, {0}* {}\n", typ, src, dst).as_str());
     }
     
     /// Creates a load statement
     /// # Example
     /// %1 = load i32, i32* %0
     fn create_load(&mut self, typ: String, src: String) -> String {
-        self.code.push_str(format!("\t%{} = load {}, {1}* {}\n", self.ssa_num, typ, src).as_str());
+        self.emit(format!("\t%{} = load {}, {1}* {}\n", self.ssa_num, typ, src).as_str());
         self.ssa_num += 1;
         format!("%{}", self.ssa_num - 1)
     }
@@ -93,7 +736,19 @@ impl IRBuilder {
     /// # Example
     /// %1 = bitcast i32* %0, i8*
     fn create_bitcast(&mut self, typ: String, name: String, typ2: String) -> String {
-        self.code.push_str(format!("\t%{} = bitcast {} {} to {}\n", self.ssa_num, typ, name, typ2).as_str());
+        self.emit(format!("\t%{} = bitcast {} {} to {}\n", self.ssa_num, typ, name, typ2).as_str());
+        self.ssa_num += 1;
+        format!("%{}", self.ssa_num - 1)
+    }
+
+    /// Creates a conversion between two types
+    /// # Example
+    /// %1 = trunc i32 %0 to i8
+    fn create_cast(&mut self, op: &str, typ: String, name: String, typ2: String) -> String {
+        if let Some(value) = fold_cast(op, typ.as_str(), name.as_str(), typ2.as_str()).filter(|_| self.optimize) {
+            return value;
+        }
+        self.emit(format!("\t%{} = {} {} {} to {}\n", self.ssa_num, op, typ, name, typ2).as_str());
         self.ssa_num += 1;
         format!("%{}", self.ssa_num - 1)
     }
@@ -102,14 +757,32 @@ impl IRBuilder {
     /// # Example
     /// %0 = getelementptr inbounds [3 x i8], [3 x i8]* @.str, i32 0, i32 0
     fn create_gep(&mut self, typ: String, name: String, indices: Vec<String>) -> String {
-        self.code.push_str(format!("\t%{} = getelementptr inbounds {}, {1}* {}", self.ssa_num, typ, name).as_str());
+        self.emit(format!("\t%{} = getelementptr inbounds {}, {1}* {}", self.ssa_num, typ, name).as_str());
         self.ssa_num += 1;
 
         // Add each of the indices
         for indice in indices {
-            self.code.push_str(format!(", i32 {}", indice).as_str());
+            self.emit(format!(", i32 {}", indice).as_str());
         }
-        self.code.push('\n');
+        self.emit("\n");
+        format!("%{}", self.ssa_num - 1)
+    }
+
+    /// Creates an extractvalue statement
+    /// # Example
+    /// %1 = extractvalue %.Str %0, 1
+    fn create_extract_value(&mut self, typ: String, agg: String, index: usize) -> String {
+        self.emit(format!("\t%{} = extractvalue {} {}, {}\n", self.ssa_num, typ, agg, index).as_str());
+        self.ssa_num += 1;
+        format!("%{}", self.ssa_num - 1)
+    }
+
+    /// Creates an insertvalue statement
+    /// # Example
+    /// %1 = insertvalue %.Str %0, i32 3, 1
+    fn create_insert_value(&mut self, typ: String, agg: String, value: String, index: usize) -> String {
+        self.emit(format!("\t%{} = insertvalue {} {}, {}, {}\n", self.ssa_num, typ, agg, value, index).as_str());
+        self.ssa_num += 1;
         format!("%{}", self.ssa_num - 1)
     }
 
@@ -118,7 +791,17 @@ impl IRBuilder {
     /// @.str = constant [4 x i8] c"abc\00"
     fn create_global(&mut self, id: String, value: String) -> String {
         self.code = format!("{} = constant {}\n\n{}", id, value, self.code);
-        format!("{}", id)
+        self.globals.insert(id.clone(), format!("{}*", verify::split_type(value.as_str()).0));
+        id.to_string()
+    }
+
+    /// Creates a global variable, which can be changed unlike a constant
+    /// # Example
+    /// @count.0 = global i32 5
+    fn create_global_variable(&mut self, id: String, typ: String, init: String) -> String {
+        self.code = format!("{} = global {} {}\n\n{}", id, typ, init, self.code);
+        self.globals.insert(id.clone(), format!("{}*", typ));
+        id.to_string()
     }
 
     /// Adds a string to the end of the code
@@ -126,16 +809,65 @@ impl IRBuilder {
         self.ends.push_str(s.as_str());
     }
 
-    /// Saves the current ssa_num into a save variable
-    /// Resets the ssa_num to 0
-    fn enter_function(&mut self) {
-        self.save_ssa_num = self.ssa_num;
-        self.ssa_num = 0;
+    /// Creates a numbered metadata node
+    /// # Example
+    /// !3 = !DILocation(line: 2, scope: !2)
+    fn create_metadata(&mut self, node: String) -> String {
+        self.metadata.push(node);
+        return format!("!{}", self.metadata.len() - 1);
+    }
+
+    /// Writes the named and numbered metadata
+    /// # Example
+    /// !llvm.module.flags = !{!0}
+    ///
+    /// !0 = !{i32 2, !"Debug Info Version", i32 3}
+    fn finish_metadata(&self) -> String {
+        let mut code = String::new();
+        for named in self.named_metadata.iter() {
+            code.push_str(format!("{}\n", named).as_str());
+        }
+        if !self.module_flags.is_empty() {
+            code.push_str(format!("!llvm.module.flags = !{{{}}}\n", self.module_flags.join(", ")).as_str());
+        }
+        if !code.is_empty() {
+            code.push('\n');
+        }
+        for (i, node) in self.metadata.iter().enumerate() {
+            code.push_str(format!("!{} = {}\n", i, node).as_str());
+        }
+        return code;
+    }
+
+    /// Starts a function with an empty entry block, putting the current one
+    /// aside until the new one is finished
+    fn enter_function(&mut self, header: String) {
+        let entry = vec![Block {name: "entry".to_string(), code: String::new(), lines: Vec::new()}];
+        self.enclosing.push(Function {
+            header: std::mem::replace(&mut self.header, header),
+            blocks: std::mem::replace(&mut self.blocks, entry),
+            order: std::mem::replace(&mut self.order, vec![0]),
+            block: std::mem::replace(&mut self.block, 0),
+            ssa_num: std::mem::replace(&mut self.ssa_num, 0),
+            allocas: std::mem::take(&mut self.allocas),
+            location: std::mem::take(&mut self.location)
+        });
     }
 
-    /// Resets the ssa_num to the saved variable
+    /// Adds the current function to the code and goes back to the one it is
+    /// declared inside of
     fn exit_function(&mut self) {
-        self.ssa_num = self.save_ssa_num;
+        let code = self.finish_function();
+        self.code.push_str(code.as_str());
+        if let Some(function) = self.enclosing.pop() {
+            self.header = function.header;
+            self.blocks = function.blocks;
+            self.order = function.order;
+            self.block = function.block;
+            self.ssa_num = function.ssa_num;
+            self.allocas = function.allocas;
+            self.location = function.location;
+        }
     }
 
     /// Creates a new struct
@@ -144,35 +876,41 @@ impl IRBuilder {
     ///     i8*,
     ///     i32
     /// }
-    fn create_new_struct(&mut self, id: String, fields: Vec<(String, String)>) -> String {
+    fn create_new_struct(&mut self, id: String, fields: Vec<(String, Type)>) -> String {
+        let fields = fields.iter().map(|f| type_of(f.1.clone())).collect();
+        self.create_type(id, fields)
+    }
+
+    /// Creates a new struct out of llvm types
+    fn create_type(&mut self, id: String, fields: Vec<String>) -> String {
         // New string to store the fields
         let mut cat_fields = String::new();
 
-        // Counts the number of fields
-        let mut num_fields = 0;
-
         // Iterate of the the fields and add them to 'cat_fields'
-        for field in fields.clone().iter() {
-            cat_fields.push_str(format!("\t{}", type_of(field.clone().1)).as_str());
+        for (num_fields, field) in fields.iter().enumerate() {
+            cat_fields.push_str(format!("\t{}", field).as_str());
 
             // If the last field is not reached, add a comma
             if num_fields + 1 < fields.clone().len() {
                 cat_fields.push(',');
             }
             cat_fields.push('\n');
-
-            // Increment the number of fields
-            num_fields += 1;
         }
         self.code = format!("%{} = type {{\n{}}}\n\n{}", id, cat_fields, self.code);
         format!("@{}", id)
     }
 
     /// Creates an operation
+    /// Operations on constants, and ones like 'x * 1', give back their result
+    /// instead
     /// # Example
-    /// %0 = add i32 5, 6
-    fn create_operation(&mut self, oper: String, typ: String, left: String, right: String) -> String {
-        self.code.push_str(format!("\t%{} = {} {} {}, {}\n", self.ssa_num, type_of_oper(oper), type_of(typ), left, right).as_str());
+    /// %0 = add i32 %a, 6
+    /// %1 = fadd double %b, 0.5
+    fn create_operation(&mut self, oper: String, typ: Type, left: String, right: String) -> String {
+        if let Some(value) = fold_operation(oper.as_str(), &typ, left.as_str(), right.as_str()).filter(|_| self.optimize) {
+            return value;
+        }
+        self.emit(format!("\t%{} = {} {} {}, {}\n", self.ssa_num, type_of_oper(oper, &typ), type_of(typ), left, right).as_str());
         self.ssa_num += 1;
         format!("%{}", self.ssa_num - 1)
     }
@@ -195,6 +933,19 @@ pub struct Generator {
     /// Whether or not @strlen was declared
     pub dec_strlen: bool,
 
+    /// Whether or not @exit was declared
+    pub dec_exit: bool,
+
+    /// Number of asserts generated
+    pub assert_num: usize,
+
+    /// Number of bounds checks generated
+    pub bounds_num: usize,
+
+    /// Stores the return type and which arguments are 'ref' for each
+    /// declared function
+    pub functions: HashMap<String, (Type, Vec<bool>)>,
+
     /// Whether or not @.int was declared
     pub dec_int: bool,
 
@@ -206,37 +957,231 @@ pub struct Generator {
 
     /// Whether or not @.str was declared
     pub dec_str: bool,
+
+    /// Whether or not @getenv and the empty string were declared
+    pub dec_getenv: bool,
+
+    /// Whether or not @.true and @.false were declared
+    pub dec_bool_strs: bool,
+
+    /// Whether or not the strings used for reading input were declared
+    pub dec_input: bool,
+
+    /// Other libc functions that were declared
+    pub externs: Vec<String>,
+
+    /// What a 'ret' without a value turns into in the current function
+    pub bare_ret: String,
+
+    /// The struct the current function returns through its 'sret' pointer
+    pub sret: Option<Type>,
+
+    /// Blocks of the loops around the current statement, as
+    /// (continue block, break block)
+    pub loops: Vec<(usize, usize)>,
+
+    /// Whether or not the program declared its own main function
+    pub has_main: bool,
+
+    /// Values that the top-level variables are given when the program's own
+    /// main starts, with the line each is declared on
+    pub global_inits: Vec<(Type, Expr, String, usize)>,
+
+    /// Whether or not indexes are checked against the length of what they
+    /// index when the program runs
+    pub bounds_checks: bool,
+
+    /// Whether or not heap values are allocated through the Boehm garbage
+    /// collector instead of malloc
+    pub gc: bool,
+
+    /// Variables of the current function whose values outlive it
+    pub escapes: Vec<String>,
+
+    /// Whether or not the expression being generated outlives the current
+    /// function, so arrays in it go on the heap
+    pub escaping: bool,
+
+    /// Values of the 'let's and arguments that can't change, which are used
+    /// directly instead of being stored in an alloca
+    pub values: HashMap<String, String>,
+
+    /// Metadata for the debug info, if it is generated
+    pub debug: Option<DebugInfo>,
+
+    /// Lines of the source, when each statement is written as a comment
+    /// before its instructions
+    pub source: Option<Rc<SourceMap>>,
+
+    /// The first statement on each line, which problems the verifier finds
+    /// in the ir are blamed on
+    pub statements: HashMap<usize, String>,
+
+    /// Name of the source file, which failed asserts print
+    pub file: String,
 }
 
 impl Generator {
     /// Constructs a new code generator
     pub fn construct() -> Generator {
-        Generator {ir_b: IRBuilder::construct(), has_array: false, dec_printf: false, dec_int: false, dec_dec: false, dec_char: false, dec_str: false, format_num: 0, dec_strlen: false}
+        Generator {ir_b: IRBuilder::construct(), has_array: false, dec_printf: false, dec_int: false, dec_dec: false, dec_char: false, dec_str: false, format_num: 0, dec_strlen: false, dec_exit: false, assert_num: 0, bounds_num: 0, functions: HashMap::new(), dec_getenv: false, dec_bool_strs: false, dec_input: false, externs: Vec::new(), bare_ret: "ret i32 0".to_string(), sret: None, loops: Vec::new(), has_main: false, global_inits: Vec::new(), bounds_checks: false, gc: false, escapes: Vec::new(), escaping: false, values: HashMap::new(), debug: None, source: None, statements: HashMap::new(), file: "<input>".to_string()}
+    }
+
+    /// Starts generating debug info for a source file
+    pub fn enable_debug_info(&mut self, path: &str) {
+        let path = Path::new(path);
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let mut dir = std::env::current_dir().unwrap_or_default();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            dir = dir.join(parent);
+        }
+        let file = self.ir_b.create_metadata(format!("!DIFile(filename: \"{}\", directory: \"{}\")", escape_string(name.as_str()), escape_string(dir.to_string_lossy().as_ref())));
+        let unit = self.ir_b.create_metadata(format!("distinct !DICompileUnit(language: DW_LANG_C, file: {}, producer: \"gizmoc version {}\", isOptimized: false, runtimeVersion: 0, emissionKind: FullDebug)", file, env!("CARGO_PKG_VERSION")));
+        let fn_type = self.ir_b.create_metadata("!DISubroutineType(types: !{null})".to_string());
+        self.ir_b.named_metadata.push(format!("!llvm.dbg.cu = !{{{}}}", unit));
+        let dwarf = self.ir_b.create_metadata("!{i32 7, !\"Dwarf Version\", i32 4}".to_string());
+        let version = self.ir_b.create_metadata("!{i32 2, !\"Debug Info Version\", i32 3}".to_string());
+        self.ir_b.module_flags.extend([dwarf, version]);
+
+        self.debug = Some(DebugInfo {unit: unit, file: file, fn_type: fn_type, scope: String::new()});
+    }
+
+    /// Gives the subprogram of the current function
+    /// The implicit main only gets one the first time code outside of the
+    /// functions needs it, which never happens when the program declares its
+    /// own main
+    fn current_scope(&mut self) -> Option<String> {
+        let scope = self.debug.as_ref()?.scope.clone();
+        if !scope.is_empty() {
+            return Some(scope);
+        }
+        if self.has_main {
+            return None;
+        }
+        let scope = self.create_subprogram("main", "main", 1);
+        self.ir_b.header = debug_header(MAIN_HEADER, &scope);
+        self.debug.as_mut()?.scope = scope.clone();
+        return Some(scope);
+    }
+
+    /// Creates the debug info of a function that starts on a line
+    /// The name in ir is given too when it is different from the source
+    /// # Example
+    /// !5 = distinct !DISubprogram(name: "add", linkageName: "gz_add", scope: !0, file: !0, line: 3, ...)
+    fn create_subprogram(&mut self, name: &str, linkage: &str, line: usize) -> String {
+        let (unit, file, fn_type) = match &self.debug {
+            Some(debug) => (debug.unit.clone(), debug.file.clone(), debug.fn_type.clone()),
+            None => return String::new()
+        };
+        let linkage = if linkage == name {String::new()} else {format!("linkageName: \"{}\", ", linkage)};
+        return self.ir_b.create_metadata(format!("distinct !DISubprogram(name: \"{}\", {}scope: {2}, file: {2}, line: {3}, type: {4}, scopeLine: {3}, spFlags: DISPFlagDefinition, unit: {5})", name, linkage, file, line, fn_type, unit));
+    }
+
+    /// Names the source file of the module and the compiler that produced it,
+    /// so tools can tell where an object file came from
+    /// The target has to be set first, since it replaces the top of the
+    /// module
+    /// # Example
+    /// source_filename = "sum.gizmo"
+    /// !llvm.ident = !{!4}
+    /// !4 = !{!"gizmoc version 0.1.0"}
+    pub fn identify(&mut self, file: &str) {
+        self.ir_b.module = format!("; ModuleID = '{}'\nsource_filename = \"{}\"\n{}", file, escape_string(file), self.ir_b.module);
+
+        // The object file is always position independent, see 'build::llc'
+        let pic = self.ir_b.create_metadata("!{i32 7, !\"PIC Level\", i32 2}".to_string());
+        self.ir_b.module_flags.push(pic);
+        let ident = self.ir_b.create_metadata(format!("!{{!\"gizmoc version {}\"}}", env!("CARGO_PKG_VERSION")));
+        self.ir_b.named_metadata.push(format!("!llvm.ident = !{{{}}}", ident));
+    }
+
+    /// Writes a line of the source as a comment, when comments are wanted
+    fn comment_line(&mut self, line: usize) {
+        let text = match &self.source {
+            Some(source) if line > 0 && line <= source.line_count() => source.line(line).trim().to_string(),
+            _ => return
+        };
+        self.ir_b.create_comment(format!("{} (line {})", text, line).as_str());
+    }
+
+    /// Gives the instructions generated from now on a line, which the
+    /// verifier blames problems on, and a location in the current function
+    /// when there is debug info
+    fn set_location(&mut self, line: usize) {
+        self.ir_b.line = line;
+        self.ir_b.location = self.current_scope().map(|scope| format!("!DILocation(line: {}, scope: {})", line, scope));
     }
 
     /// Destructs the code generator
     pub fn destruct(&mut self) {
-        self.ir_b.code.push_str(self.ir_b.ends.as_str());
+        // When the program declares its own main, it was generated like any
+        // other function, which leaves the implicit one empty
+        if !self.has_main {
+            self.current_scope();
+            self.ir_b.emit("\tret i32 0\n");
+            let code = self.ir_b.finish_function();
+            self.ir_b.code.push_str(code.as_str());
+        }
+        let ends = self.ir_b.ends.clone();
+        self.ir_b.code.push_str(ends.as_str());
+
+        // Strings can show up in any signature, so the type is declared
+        // once everything is generated
+        if self.ir_b.code.contains("%.Str") {
+            self.ir_b.create_type(".Str".to_string(), vec!["i8*".to_string(), "i32".to_string()]);
+        }
+        self.ir_b.code = format!("{}{}", self.ir_b.module, self.ir_b.code);
+        let metadata = self.ir_b.finish_metadata();
+        if !metadata.is_empty() {
+            self.ir_b.code.push_str(format!("\n{}", metadata).as_str());
+        }
     }
 
     /// Iterates through the nodes and generates ir for them
     pub fn generate(&mut self, nodes: Vec<Box<Node>>) {
+        // The program's own main gives the top-level variables their values,
+        // wherever they are declared, so they are all found before it is
+        let mut nodes = nodes;
+        if nodes.iter().any(|n| matches!(&**n, Node::FuncDecl {id, ..} if id == "main")) {
+            self.has_main = true;
+            let (decls, others): (Vec<Box<Node>>, Vec<Box<Node>>) = nodes.into_iter().partition(|n| matches!(**n, Node::Let {..} | Node::Struct {..}));
+            nodes = decls.into_iter().chain(others).collect();
+        }
         for node in nodes.iter() {
+            if let Some(line) = node.line() {
+                self.set_location(line);
+                self.statements.entry(line).or_insert_with(|| node.describe());
+
+                // A function's instructions are in its own define
+                if !matches!(**node, Node::FuncDecl {..}) {
+                    self.comment_line(line);
+                }
+            }
             match *node.clone() {
-                Node::Let {id: _, expr, gen_id} => self.generate_let_stmt(expr.clone(), gen_id.clone()),
-                Node::Ret {expr} => self.generate_ret_stmt(expr.clone()),
-                Node::Pause {label} => {
-                    self.ir_b.code.push_str(format!("\tbr label %l{}\n", label).as_str());
-                    self.ir_b.ssa_num += 1;
+                Node::Let {id: _, typ, expr, gen_id, mutable, ..} => self.generate_let_stmt(typ.clone(), expr.clone(), gen_id.clone(), mutable),
+                Node::Ret {expr, ..} => self.generate_ret_stmt(expr.clone()),
+                Node::Pause {is_break, ..} => {
+                    let (next, end) = *self.loops.last().expect("'break' and 'continue' are only parsed inside of loops");
+                    self.ir_b.create_br(if is_break {end} else {next});
+
+                    // Code after it can't run, but still needs a block
+                    let dead = self.ir_b.create_label_block();
+                    self.ir_b.position_at_end(dead);
                 },
-                Node::FuncDecl {id, typ, args, body} => self.generate_func_decl(id.clone(), typ.clone(), args.clone(), body.clone()),
-                Node::While {cond, body, begin, end} => self.generate_while_loop(cond.clone(), body.clone(), begin, end),
-                Node::If {cond, body, else_body, begin, else_, end} => self.generate_if_stmt(cond.clone(), body.clone(), else_body.clone(), begin, else_, end),
-                Node::Assign {id, expr} => self.generate_assign_stmt(id.clone(), expr.clone()),
-                Node::FuncCall {id, args} => {
-                    self.generate_func_call(id.clone(), "void".to_string(), args.clone());
+                Node::FuncDecl {id, gen_id, typ, args, body, line, ..} => self.generate_func_decl(id.clone(), gen_id.clone(), typ.clone(), args.clone(), body.clone(), line),
+                Node::While {cond, body, line, ..} => self.generate_while_loop(cond.clone(), body.clone(), line),
+                Node::If {cond, body, else_body, ..} => self.generate_if_stmt(cond.clone(), body.clone(), else_body.clone()),
+                Node::Assign {id, expr, ..} => self.generate_assign_stmt(id.clone(), expr.clone()),
+                Node::FuncCall {id, args, ..} => {
+                    // Calls to functions that return a value still need the
+                    // right return type, even if the value is thrown away
+                    let typ = match self.functions.get(&id) {
+                        Some((t, _)) if *t != Type::Void => type_of(t.clone()),
+                        _ => "void".to_string()
+                    };
+                    self.generate_func_call(id.clone(), typ, args.clone());
                 },
-                Node::Struct {id, fields} => self.generate_struct_decl(id.clone(), fields.clone()),
+                Node::Struct {id, fields, ..} => self.generate_struct_decl(id.clone(), fields.clone()),
                 Node::Block {statements} => {
                     self.generate(statements);
                 }
@@ -245,26 +1190,18 @@ impl Generator {
         }
     }
 
-    /// Gets the length of a string
-    /// # Example
-    /// abc\0Adef\00 -> 8
-    fn get_str_length(&self, s: String) -> (i32, String) {
-        let mut found = String::new();
-        let mut pos = 0;
-        for c in s.chars() {
-            if c == '.' {
-                return (found.parse::<i32>().unwrap_or(0) + 1, s[pos + 1..s.len()].to_string());
-            }
-            pos += 1;
-            found.push(c);
+    /// Declares a libc function if it isn't declared yet
+    fn declare_extern(&mut self, decl: &str) {
+        if !self.externs.iter().any(|d| d == decl) {
+            self.ir_b.create_ends(format!("{}\n", decl));
+            self.externs.push(decl.to_string());
         }
-        return (0, String::new());
     }
 
     /// Generates an expression
     /// # Example
     /// 5 -> i32 5
-    /// a -> %.1
+    /// a -> %a.1
     /// ...
     pub fn generate_expression(&mut self, expr: Expr, load_id: bool) -> String {
         match expr.clone() {
@@ -273,98 +1210,136 @@ impl Generator {
             Expr::Dec(d) => d.to_string(),
             Expr::Bool(b) => b.to_string(),
             Expr::Str(s) => {
-                // Get the length of the string
-                let (length, rest) = self.get_str_length(s.clone());
+                // The array holds the bytes of the string and a 0 at the end
+                let length = s.len() + 1;
 
                 // Create a new global string
-                let global = self.ir_b.create_global(format!("@.str.{}", self.ir_b.str_num), format!("[{} x i8] c\"{}\\00\"", length, rest));
+                let global = self.ir_b.create_global(format!("@.str.{}", self.ir_b.str_num), format!("[{} x i8] c\"{}\\00\"", length, escape_string(s.as_str())));
                
                 // Increment the number of strings created
                 self.ir_b.str_num += 1;
 
-                self.ir_b.create_gep(format!("[{} x i8]", length), global, vec!["0".to_string(), "0".to_string()])
+                let gep = self.ir_b.create_gep(format!("[{} x i8]", length), global, vec!["0".to_string(), "0".to_string()]);
+                self.create_str(gep, s.len().to_string())
             },
-            Expr::Id(_id, typ, gen_id) => {
+            Expr::Id(_id, typ, gen_id, _) => {
+                // Values that can't change are only put in memory when
+                // something needs a pointer to them
+                if let Some(value) = self.values.get(&gen_id).cloned() {
+                    if load_id {
+                        return value;
+                    }
+                    let space = self.ir_b.create_alloca(type_of(typ.clone()), None);
+                    self.ir_b.create_store(value, space.clone(), type_of(typ));
+                    return space;
+                }
+
                 // If the caller wants to load the identifiers into a true
                 // value instead of keeping them pointers
-                if load_id == true {
+                if load_id {
                     self.ir_b.create_load(type_of(typ), gen_id)
                 } else {
                     gen_id
                 }
             }
-            Expr::NewStruct {id, fields} => {
+            Expr::NewStruct {id, fields, ..} => {
                 // Allocate a new struct
-                let begin = self.ir_b.create_alloca(type_of(id.clone()), None);
+                let typ = Type::Struct(id.clone());
+                let begin = self.ir_b.create_alloca(type_of(typ.clone()), None);
 
                 // Number of fields
-                let mut field_num = 0;
 
                 // Iterate through the fields
-                for field in fields.iter() {
+                for (field_num, field) in fields.iter().enumerate() {
                     // Generate the expression
-                    let gen_field = self.generate_expression(field.clone(), true);
+                    let gen_field = self.generate_copy(field.clone(), true);
 
                     // Find the correct place to store the expression
-                    let gep = self.ir_b.create_gep(type_of(id.clone()), begin.clone(), vec!["0".to_string(), field_num.to_string()]);
+                    let gep = self.ir_b.create_gep(type_of(typ.clone()), begin.clone(), vec!["0".to_string(), field_num.to_string()]);
 
                     // Store the expression in it's place
-                    self.ir_b.create_store(gen_field, gep, type_of(field.validate().to_string()));
-
-                    // Increment the number of fields found
-                    field_num += 1;
+                    self.ir_b.create_store(gen_field, gep, type_of(field.validate()));
                 }
-                self.ir_b.create_load(type_of(id), begin)
+                self.ir_b.create_load(type_of(typ), begin)
             }
             Expr::StructDot {id, typ, field_num, ..} => {
                 // Generates the left side of the '.'
                 let gen_begin = self.generate_expression(*id.clone(), false);
 
                 // Index the left side with the field number
-                let gep = self.ir_b.create_gep(type_of(id.validate().to_string()), gen_begin, vec!["0".to_string(), field_num.to_string()]);
-                if load_id == true {
+                let gep = self.ir_b.create_gep(type_of(id.validate()), gen_begin, vec!["0".to_string(), field_num.to_string()]);
+                if load_id {
                     self.ir_b.create_load(type_of(typ.clone()), gep)
                 } else {
                     gep
                 }
             }
-            Expr::FuncCall {id, typ, args} => {
-                if typ.as_str() == "void" {
+            Expr::FuncCall {id, typ, args, ..} => {
+                if typ == Type::Void {
                     self.generate_func_call(id, "void".to_string(), args)
                 } else {
                     self.generate_func_call(id, type_of(typ), args)
                 }
             },
+            Expr::If {cond, body, else_body, typ, ..} => {
+                let begin = self.ir_b.create_label_block();
+                let else_ = self.ir_b.create_label_block();
+                let end = self.ir_b.create_label_block();
+
+                // Generate the condition
+                let gen_cond = self.generate_expression(*cond, true);
+                self.ir_b.create_cond_br(gen_cond, begin, else_);
+
+                // Generate each branch, remembering the block it ends in since
+                // a branch with an if of its own ends in another block
+                self.ir_b.position_at_end(begin);
+                let gen_body = self.generate_expression(*body, true);
+                let body_end = self.ir_b.block;
+                self.ir_b.create_br(end);
+                self.ir_b.position_at_end(else_);
+                let gen_else = self.generate_expression(*else_body, true);
+                let else_end = self.ir_b.block;
+                self.ir_b.create_br(end);
+
+                // Pick the value of whichever branch ran
+                self.ir_b.position_at_end(end);
+                self.ir_b.create_phi(type_of(typ), vec![(gen_body, body_end), (gen_else, else_end)])
+            }
             Expr::Array {values, ..} => {
                 // If %.Arr isn't already declared, declare it
                 if !self.has_array {
-                    self.ir_b.create_new_struct(".Arr".to_string(), vec![("".to_string(), "string".to_string()), ("".to_string(), "int".to_string())]);
+                    self.ir_b.create_type(".Arr".to_string(), vec!["i8*".to_string(), "i32".to_string()]);
                     self.has_array = true;
                 }
 
                 // Store the type of the first element
-                let v_typ = type_of(values[0].clone().validate().to_string());
+                let v_typ = type_of(values[0].clone().validate());
 
                 // Allocate the array
                 let alloca = self.ir_b.create_alloca("%.Arr".to_string(), None);
 
-                // Allocate a temporary sized array
-                let sized_alloca = self.ir_b.create_alloca(format!("[{} x {}]", values.len(), v_typ), None);
+                // Allocate a temporary sized array, or put it on the heap if it
+                // outlives the function
+                let sized_typ = format!("[{} x {}]", values.len(), v_typ);
+                let sized_alloca = if self.escaping {
+                    self.generate_malloc(sized_typ)
+                } else {
+                    self.ir_b.create_alloca(sized_typ, None)
+                };
 
                 // Number of elements
-                let mut value_num = 0;
                 
                 // Iterate through the elements
-                for value in values.iter() {
+                for (value_num, value) in values.iter().enumerate() {
                     // Generate the element
-                    let gen_value = self.generate_expression((*value).clone(), true);
+                    let escaping = self.escaping;
+                    let gen_value = self.generate_copy((*value).clone(), escaping);
 
                     // Get the pointer to the elements location
                     let gep = self.ir_b.create_gep(format!("[{} x {}]", values.len(), v_typ), sized_alloca.clone(), vec!["0".to_string(), value_num.to_string()]);
 
                     // Store the element in it's location
-                    self.ir_b.create_store(gen_value, gep, type_of(value.clone().validate().to_string()));
-                    value_num += 1;
+                    self.ir_b.create_store(gen_value, gep, type_of(value.clone().validate()));
                 }
 
                 // Bitcast the sized array to a pointer
@@ -381,7 +1356,7 @@ impl Generator {
                 // Load the array
                 self.ir_b.create_load("%.Arr".to_string(), alloca)
             }
-            Expr::IndexedValue {src, index, new_typ} => {
+            Expr::IndexedValue {src, index, new_typ, ..} => {
                 // Generate the value being indexed
                 let gen_src = self.generate_expression(*src.clone(), true);
 
@@ -389,9 +1364,14 @@ impl Generator {
                 let gen_index = self.generate_expression(*index.clone(), true);
 
                 match src.validate() {
-                    "string" => {
+                    Type::Str => {
+                        let (ptr, len) = self.str_parts(gen_src);
+                        if self.bounds_checks {
+                            self.generate_bounds_check(gen_index.clone(), len);
+                        }
+
                         // Bitcast the i8* to a [0 x i8]*
-                        let bitcast = self.ir_b.create_bitcast("i8*".to_string(), gen_src, "[0 x i8]*".to_string());
+                        let bitcast = self.ir_b.create_bitcast("i8*".to_string(), ptr, "[0 x i8]*".to_string());
 
                         // Get and load the location of the index
                         let gep = self.ir_b.create_gep("[0 x i8]".to_string(), bitcast, vec!["0".to_string(), gen_index]);
@@ -403,6 +1383,12 @@ impl Generator {
                         let alloca = self.ir_b.create_alloca("%.Arr".to_string(), None);
                         self.ir_b.create_store(gen_src, alloca.clone(), "%.Arr".to_string());
 
+                        if self.bounds_checks {
+                            let gep = self.ir_b.create_gep("%.Arr".to_string(), alloca.clone(), vec!["0".to_string(), "1".to_string()]);
+                            let len = self.ir_b.create_load("i32".to_string(), gep);
+                            self.generate_bounds_check(gen_index.clone(), len);
+                        }
+
                         // Get and load the i8* from the %.Arr*
                         let gep = self.ir_b.create_gep("%.Arr".to_string(), alloca.clone(), vec!["0".to_string(), "0".to_string()]);
                         let load = self.ir_b.create_load("i8*".to_string(), gep);
@@ -412,7 +1398,7 @@ impl Generator {
 
                         // Get and load the location of the index
                         let gep2 = self.ir_b.create_gep(format!("[0 x {}]", type_of(new_typ.clone())), bitcast, vec!["0".to_string(), gen_index]);
-                        if load_id == true {
+                        if load_id {
                             self.ir_b.create_load(type_of(new_typ.clone()), gep2)
                         } else {
                             gep2
@@ -420,208 +1406,665 @@ impl Generator {
                     }
                 }
             }
-            Expr::BinaryOperator {oper, left, right} => {
+            Expr::BinaryOperator {oper, left, right, ..} => {
                 // Generate the left and right sides of the expression
                 let gen_left = self.generate_expression((*left).clone(), true);
                 let gen_right = self.generate_expression((*right).clone(), true);
 
+                // Dividing two ints or longs gives a dec, so both sides are
+                // converted to doubles first
+                if oper == "/" && (left.validate() == Type::Int || left.validate() == Type::Long) {
+                    let gen_left = self.ir_b.create_cast("sitofp", type_of(left.validate()), gen_left, type_of(Type::Dec));
+                    let gen_right = self.ir_b.create_cast("sitofp", type_of(left.validate()), gen_right, type_of(Type::Dec));
+                    return self.ir_b.create_operation(oper, Type::Dec, gen_left, gen_right);
+                }
+
+                if left.validate() == Type::Str {
+                    return self.generate_str_compare(oper, gen_left, gen_right);
+                }
+
                 // Call the ir builder to create the operation
-                self.ir_b.create_operation(oper, left.clone().validate().to_string(), gen_left, gen_right)
+                self.ir_b.create_operation(oper, left.clone().validate(), gen_left, gen_right)
             }
-            Expr::UnaryOperator {oper, child} => {
+            Expr::Cast {expr, typ, ..} => {
+                let from = expr.validate();
+                let gen_expr = self.generate_expression((*expr).clone(), true);
+                let op = match (from.clone(), typ.clone()) {
+                    (Type::Int, Type::Dec) | (Type::Long, Type::Dec) => "sitofp",
+                    (Type::Dec, Type::Int) | (Type::Dec, Type::Long) => "fptosi",
+                    (Type::Int, Type::Long) => "sext",
+                    (Type::Long, Type::Int) | (Type::Long, Type::UInt) => "trunc",
+                    (Type::UInt, Type::Long) => "zext",
+                    (Type::UInt, Type::Dec) => "uitofp",
+                    (Type::Dec, Type::UInt) => "fptoui",
+                    (Type::Char, Type::Int) => "zext",
+                    (Type::Int, Type::Char) => "trunc",
+                    _ => return gen_expr
+                };
+                return self.ir_b.create_cast(op, type_of(from), gen_expr, type_of(typ));
+            },
+            Expr::UnaryOperator {oper, child, ..} => {
                 let gen_child = self.generate_expression((*child).clone(), true);
-                if oper == "-".to_string() {
+                if oper == "-" {
                     // Having a negative value is the same as multiplying
                     // the value by -1
                     // -5 and 5 * -1 are equal
-                    return self.ir_b.create_operation("*".to_string(), child.clone().validate().to_string(), gen_child.clone(), "-1".to_string());
+                    let minus_one = if child.validate() == Type::Dec {"-1.0"} else {"-1"};
+                    return self.ir_b.create_operation("*".to_string(), child.clone().validate(), gen_child.clone(), minus_one.to_string());
                 } else {
                     // Having a 'not' value is the same as subtracting
                     // 1 by the value
                     // not 0 and 1 - 0 are equal
-                    return self.ir_b.create_operation("-".to_string(), child.clone().validate().to_string(), gen_child, "1".to_string());
+                    return self.ir_b.create_operation("-".to_string(), child.clone().validate(), gen_child, "1".to_string());
                 }
             }
             _ => "".to_string()
         }
     }
 
+    /// Generates an expression, saying whether or not its value outlives the
+    /// current function
+    fn generate_escaping(&mut self, expr: Expr, escaping: bool) -> String {
+        let save_escaping = std::mem::replace(&mut self.escaping, escaping);
+        let value = self.generate_expression(expr, true);
+        self.escaping = save_escaping;
+        return value;
+    }
+
+    /// Generates a value that is stored somewhere new, copying it if it is an
+    /// array that already belongs to something else
+    /// Arrays are values, so the copy gets its own elements
+    fn generate_copy(&mut self, expr: Expr, escaping: bool) -> String {
+        let value = self.generate_escaping(expr.clone(), escaping);
+        match expr.validate() {
+            Type::Array(element) if is_place(&expr) => self.generate_array_copy(value, type_of(*element)),
+            _ => value
+        }
+    }
+
+    /// Copies the elements of an array into a new buffer on the heap
+    /// Arrays inside of it still share their elements
+    fn generate_array_copy(&mut self, array: String, element: String) -> String {
+        let ptr = self.ir_b.create_extract_value("%.Arr".to_string(), array.clone(), 0);
+        let len = self.ir_b.create_extract_value("%.Arr".to_string(), array.clone(), 1);
+
+        // The size of the elements is the address of the one past the last
+        // one, counting from null
+        self.ir_b.emit(format!("\t%{} = getelementptr {}, {1}* null, i32 {}\n", self.ir_b.ssa_num, element, len).as_str());
+        self.ir_b.ssa_num += 1;
+        let size = self.ir_b.create_cast("ptrtoint", format!("{}*", element), format!("%{}", self.ir_b.ssa_num - 1), "i64".to_string());
+        let buf = self.generate_heap_alloc(size.clone());
+        self.declare_extern("declare void @llvm.memcpy.p0i8.p0i8.i64(i8*, i8*, i64, i1)");
+        self.ir_b.emit(format!("\tcall void @llvm.memcpy.p0i8.p0i8.i64(i8* {}, i8* {}, i64 {}, i1 false)\n", buf, ptr, size).as_str());
+        self.ir_b.create_insert_value("%.Arr".to_string(), array, format!("i8* {}", buf), 0)
+    }
+
+    /// Allocates space for a type on the heap
+    /// The size comes from indexing one past a null pointer of the type
+    fn generate_malloc(&mut self, typ: String) -> String {
+        self.ir_b.emit(format!("\t%{} = getelementptr {}, {1}* null, i32 1\n", self.ir_b.ssa_num, typ).as_str());
+        self.ir_b.ssa_num += 1;
+        let size = self.ir_b.create_cast("ptrtoint", format!("{}*", typ), format!("%{}", self.ir_b.ssa_num - 1), "i64".to_string());
+        let buf = self.generate_heap_alloc(size);
+        self.ir_b.create_bitcast("i8*".to_string(), buf, format!("{}*", typ))
+    }
+
+    /// Allocates a number of bytes on the heap, declaring the allocator if it
+    /// isn't already
+    /// With the garbage collector the memory is freed once nothing points to
+    /// it, otherwise it lives until the program exits
+    fn generate_heap_alloc(&mut self, size: String) -> String {
+        let alloc = if self.gc {"GC_malloc"} else {"malloc"};
+        self.declare_extern(format!("declare i8* @{}(i64)", alloc).as_str());
+        self.ir_b.emit(format!("\t%{} = call i8* @{}(i64 {})\n", self.ir_b.ssa_num, alloc, size).as_str());
+        self.ir_b.ssa_num += 1;
+        format!("%{}", self.ir_b.ssa_num - 1)
+    }
+
     /// Generates code for a 'let' statement
-    fn generate_let_stmt(&mut self, expr: Expr, gen_id: String) {
+    fn generate_let_stmt(&mut self, typ: Type, expr: Expr, gen_id: String, mutable: bool) {
+        // Globals start out as their value when it is known right away, and
+        // as zero otherwise
+        if gen_id.starts_with('@') {
+            let constant = matches!(expr, Expr::Int(_) | Expr::Dec(_) | Expr::Bool(_) | Expr::Chr(_));
+            let init = if constant {self.generate_expression(expr.clone(), true)} else {"zeroinitializer".to_string()};
+            self.ir_b.create_global_variable(gen_id.clone(), type_of(typ.clone()), init);
+
+            // A 'let' in a loop gives the variable its value again each time
+            if expr == Expr::Non || (constant && self.loops.is_empty()) {
+                return;
+            }
+            if self.has_main {
+                self.global_inits.push((typ, expr, gen_id, self.ir_b.line));
+                return;
+            }
+            let gen_expr = self.generate_copy(expr.clone(), false);
+            self.ir_b.create_store(gen_expr, gen_id, type_of(typ));
+            return;
+        }
+
+        // A 'let' with a value never changes, so the value is used directly
+        if !mutable && expr != Expr::Non {
+            let escaping = self.escapes.contains(&gen_id);
+            let gen_expr = self.generate_copy(expr.clone(), escaping);
+            self.values.insert(gen_id, gen_expr);
+            return;
+        }
+
+        // Variables without a value only need their space
+        if expr == Expr::Non {
+            self.ir_b.create_alloca(type_of(typ), Some(gen_id));
+            return;
+        }
+
         // Generate the value
-        let gen_expr = self.generate_expression(expr.clone(), true);
+        let escaping = self.escapes.contains(&gen_id);
+        let gen_expr = self.generate_copy(expr.clone(), escaping);
 
         // Allocate a pointer of that type
-        let var = self.ir_b.create_alloca(type_of(expr.clone().validate().to_string()), Some(gen_id));
+        let var = self.ir_b.create_alloca(type_of(typ.clone()), Some(gen_id));
 
         // Store the value into the pointer
-        self.ir_b.create_store(gen_expr, var, type_of(expr.clone().validate().to_string()));
+        self.ir_b.create_store(gen_expr, var, type_of(typ));
     }
 
     /// Generates code for a return statement
     fn generate_ret_stmt(&mut self, expr: Expr) {
-        let gen_expr = self.generate_expression(expr.clone(), true);
-        self.ir_b.code.push_str(format!("\tret {} {}\n", type_of(expr.validate().to_string()), gen_expr).as_str());
-        self.ir_b.ssa_num += 1;
+        if expr == Expr::Non {
+            self.ir_b.emit(format!("\t{}\n", self.bare_ret).as_str());
+        } else if let Some(typ) = self.sret.clone() {
+            // Structs are copied into the space the caller gave
+            let gen_expr = self.generate_escaping(expr.clone(), true);
+            self.ir_b.create_store(gen_expr, "%.ret".to_string(), type_of(typ));
+            self.ir_b.emit("\tret void\n");
+        } else {
+            let gen_expr = self.generate_escaping(expr.clone(), true);
+            self.ir_b.emit(format!("\tret {} {}\n", type_of(expr.validate()), gen_expr).as_str());
+        }
+
+        // Code after it can't run, but still needs a block
+        let dead = self.ir_b.create_label_block();
+        self.ir_b.position_at_end(dead);
     }
 
     /// Generates code for a function declaration
-    fn generate_func_decl(&mut self, id: String, typ: String, args: Vec<(String, String)>, body: Box<Node>) {
-        // Save the current code
-        let save = self.ir_b.code.clone();
+    /// 'name' is the name of the function in the source, and 'id' its name
+    /// in ir
+    fn generate_func_decl(&mut self, name: String, id: String, typ: Type, args: Vec<Argument>, body: Box<Node>, line: usize) {
+        // Remember the signature for the calls to this function
+        self.functions.insert(id.clone(), (typ.clone(), args.iter().map(|a| a.is_ref).collect()));
 
         // New string to store the generated arguments
-        let mut arg_code = String::new();
-
-        // Number of arguments
-        let mut arg_num = 0;
+        // A struct is returned by storing it into space the caller gives as
+        // a hidden first argument
+        let sret = if is_struct(&typ) {Some(typ.clone())} else {None};
+        let mut arg_code = match &sret {
+            Some(typ) => format!("{0}* sret({0}) %.ret", type_of(typ.clone())),
+            None => String::new()
+        };
+        if sret.is_some() && !args.is_empty() {
+            arg_code.push_str(", ");
+        }
 
         // Iterate through the arguments
-        for arg in args.iter() {
+        for (arg_num, arg) in args.iter().enumerate() {
             // Add the argument to the code
-            arg_code.push_str(format!("{}* {}", type_of(arg.0.clone()), arg.1).as_str());
+            // 'ref' arguments are pointers to the caller's variable, others
+            // are values that get copied into their own alloca
+            // Structs are passed as pointers either way, and copied here
+            if arg.is_ref {
+                arg_code.push_str(format!("{}* {}", type_of(arg.typ.clone()), arg.gen_id).as_str());
+            } else if is_struct(&arg.typ) {
+                arg_code.push_str(format!("{}* {}.arg", type_of(arg.typ.clone()), arg.gen_id).as_str());
+            } else {
+                arg_code.push_str(format!("{} {}.arg", type_of(arg.typ.clone()), arg.gen_id).as_str());
+            }
 
             // If the end isn't reached, add a comma
             if arg_num + 1 < args.len() {
                 arg_code.push_str(", ");
             }
+        }
 
-            // Increment the argument number
-            arg_num += 1;
+        // The program's main always returns an exit code
+        let is_main = id == "main";
+        if is_main {
+            self.has_main = true;
         }
 
-        if typ.clone() == "void" {
-            self.ir_b.code = format!("define void @{}({}) {{\nentry:\n", id, arg_code);
+        let header = if is_main {
+            MAIN_HEADER.to_string()
+        } else if typ == Type::Void || sret.is_some() {
+            format!("define void @{}({}) {{", id, arg_code)
         } else {
-            self.ir_b.code = format!("define {} @{}({}) {{\nentry:\n", type_of(typ.clone()), id, arg_code);
+            format!("define {} @{}({}) {{", type_of(typ.clone()), id, arg_code)
+        };
+
+        // The function gets its own scope in the debug info
+        let mut header = header;
+        let mut save_scope = None;
+        if self.debug.is_some() {
+            let scope = self.create_subprogram(name.as_str(), id.as_str(), line);
+            header = debug_header(header.as_str(), &scope);
+            save_scope = self.debug.as_mut().map(|d| std::mem::replace(&mut d.scope, scope));
         }
-        
+
         // Tell the ir builder to enter a function
-        self.ir_b.enter_function();
+        self.ir_b.enter_function(header);
+        self.set_location(line);
+        let save_bare_ret = std::mem::replace(&mut self.bare_ret, if is_main {"ret i32 0".to_string()} else {"ret void".to_string()});
+        let save_sret = std::mem::replace(&mut self.sret, sret.clone());
+        let refs = args.iter().filter(|a| a.is_ref).map(|a| a.gen_id.clone()).collect();
+        let save_escapes = std::mem::replace(&mut self.escapes, Escapes::find(&body, refs));
+
+        // Arguments passed by value can't change, so they are used directly
+        // Structs are copied out of the caller's memory first
+        for arg in args.iter().filter(|a| !a.is_ref) {
+            let mut value = format!("{}.arg", arg.gen_id);
+            if is_struct(&arg.typ) {
+                value = self.ir_b.create_load(type_of(arg.typ.clone()), value);
+            }
+            self.values.insert(arg.gen_id.clone(), value);
+        }
+
+        // The top-level variables get their values before anything else runs
+        if is_main {
+            for (typ, expr, gen_id, line) in std::mem::take(&mut self.global_inits) {
+                self.set_location(line);
+                let gen_expr = self.generate_copy(expr, false);
+                self.ir_b.create_store(gen_expr, gen_id, type_of(typ));
+            }
+            self.set_location(line);
+        }
 
         // Generate the body of the function
         self.generate(vec![body]);
 
         let mut _alloca: String = String::new();
-        let base_type = match typ.as_str() { 
-            "int"    => "0",
-            "dec"    => "0.0",
-            "char"   => "32",
-            "bool"   => "false",
-            "string" => {
-                _alloca = self.ir_b.create_alloca("i8".to_string(), None);
-                self.ir_b.create_store("32".to_string(), _alloca.clone(), "i8".to_string());
+        let base_type = match typ { 
+            Type::Int | Type::Long | Type::UInt => "0",
+            Type::Dec  => "0.0",
+            Type::Char => "32",
+            Type::Bool => "false",
+            Type::Str  => {
+                _alloca = self.generate_expression(Expr::Str(String::new()), true);
                 &_alloca
             },
+            Type::Array(_) => "zeroinitializer",
             _ => ""
         };
 
-        self.ir_b.code.push_str(format!("\tret {} {}\n", type_of(typ.clone()), base_type).as_str());
+        if is_main {
+            self.ir_b.emit("\tret i32 0\n");
+        } else if sret.is_some() {
+            self.ir_b.emit("\tret void\n");
+        } else {
+            self.ir_b.emit(format!("\tret {} {}\n", type_of(typ.clone()), base_type).as_str());
+        }
 
         // Tell the ir builder to exit a function
         self.ir_b.exit_function();
-
-        // Reset the code
-        self.ir_b.code = format!("{}\n}}\n{}", self.ir_b.code, save.clone());
+        if let (Some(debug), Some(scope)) = (&mut self.debug, save_scope) {
+            debug.scope = scope;
+        }
+        self.bare_ret = save_bare_ret;
+        self.sret = save_sret;
+        self.escapes = save_escapes;
     }
 
     /// Generates code for a while-loop
-    fn generate_while_loop(&mut self, cond: Expr, body: Box<Node>, begin: usize, end: usize) {
+    fn generate_while_loop(&mut self, cond: Expr, body: Box<Node>, line: usize) {
+        let begin = self.ir_b.create_label_block();
+        let next = self.ir_b.create_label_block();
+        let end = self.ir_b.create_label_block();
+
         // Generate the condition
         let gen_cond = self.generate_expression(cond.clone(), true);
 
         // Jump to the while-label to start the loop
-        self.ir_b.code.push_str(format!("\tbr i1 {}, label %l{}, label %l{}\nl{}:\n", gen_cond, begin.clone(), end, begin.clone()).as_str());
-        
-        // Generate the body of the loop
+        self.ir_b.create_cond_br(gen_cond, begin, end);
+
+        // Generate the body of the loop, where 'break' and 'continue' jump
+        // to this loop's blocks
+        self.ir_b.position_at_end(begin);
+        self.loops.push((next, end));
         self.generate(vec![body]);
+        self.loops.pop();
+        self.ir_b.create_br(next);
 
         // Depending on the condition, either jump back and start the loop
         // again, or exit the loop
+        self.ir_b.position_at_end(next);
+        self.set_location(line);
         let gen_cond2 = self.generate_expression(cond.clone(), true);
-        self.ir_b.code.push_str(format!("\tbr i1 {}, label %l{}, label %l{}\nl{}:\n", gen_cond2, begin, end.clone(), end).as_str());
+        self.ir_b.create_cond_br(gen_cond2, begin, end);
+        self.ir_b.position_at_end(end);
     }
     
     /// Generates code for an if-statement
-    fn generate_if_stmt(&mut self, cond: Expr, body: Box<Node>, else_body: Option<Box<Node>>, begin: i32, else_: i32, end: i32) {
+    fn generate_if_stmt(&mut self, cond: Expr, body: Box<Node>, else_body: Option<Box<Node>>) {
+        let begin = self.ir_b.create_label_block();
+        let else_ = else_body.as_ref().map(|_| self.ir_b.create_label_block());
+        let end = self.ir_b.create_label_block();
+
         // Generate the condition
         let gen_cond = self.generate_expression(cond.clone(), true);
 
-        // Jump to the if-label to start the loop
-        if else_body == None {
-            self.ir_b.code.push_str(format!("\tbr i1 {}, label %l{}, label %l{}\nl{}:\n", gen_cond, begin.clone(), end, begin.clone()).as_str());
-        } else {
-            self.ir_b.code.push_str(format!("\tbr i1 {}, label %l{}, label %l{}\nl{}:\n", gen_cond, begin.clone(), else_, begin.clone()).as_str());
-        }
-        
-        // Generate the body of the loop
+        // Jump to the if-block, or to the else-block or the end
+        self.ir_b.create_cond_br(gen_cond, begin, else_.unwrap_or(end));
+
+        // Generate the body of the if
+        self.ir_b.position_at_end(begin);
         self.generate(vec![body]);
-        self.ir_b.code.push_str(format!("\tbr label %l{}\n", end.clone()).as_str());
+        self.ir_b.create_br(end);
 
-        match else_body {
-            Some(e) => {
-                self.ir_b.code.push_str(format!("l{}:\n", else_).as_str());
-                self.generate(vec![e]);
-                self.ir_b.code.push_str(format!("\tbr label %l{}\n", end.clone()).as_str());
-            },
-            None => {}
+        if let (Some(e), Some(else_)) = (else_body, else_) {
+            self.ir_b.position_at_end(else_);
+            self.generate(vec![e]);
+            self.ir_b.create_br(end);
         }
-        self.ir_b.code.push_str(format!("l{}:\n", end).as_str());
+        self.ir_b.position_at_end(end);
     }
 
     /// Generates code for an assignment
     fn generate_assign_stmt(&mut self, id: Expr, expr: Expr) {
-        // Generate the value
-        let gen_expr = self.generate_expression(expr.clone(), true);
+        // Generate the value, which escapes when it is stored into a struct
+        // or an element, or into a variable that escapes
+        let escaping = match &id {
+            Expr::Id(_, _, gen_id, _) => gen_id.starts_with('@') || self.escapes.contains(gen_id),
+            _ => true
+        };
+        let gen_expr = self.generate_copy(expr.clone(), escaping);
 
         // Generate the left side of the equals sign
         // Make sure not to load the end id       vvvvv
         let gen_id = self.generate_expression(id, false);
 
         // Store the value into the id
-        self.ir_b.create_store(gen_expr, gen_id, type_of(expr.clone().validate().to_string()));
+        self.ir_b.create_store(gen_expr, gen_id, type_of(expr.clone().validate()));
+    }
+
+    /// Generates code for an assert
+    /// Prints the message with the line number and exits if the condition is false
+    #[allow(clippy::vec_box)]
+    fn generate_assert(&mut self, args: Vec<Box<Expr>>) -> String {
+        // Generate the condition, message and line number
+        let gen_cond = self.generate_expression(*args[0].clone(), true);
+        let gen_msg = self.generate_expression(*args[1].clone(), true);
+        let (msg_ptr, msg_len) = self.str_parts(gen_msg);
+        let gen_line = self.generate_expression(*args[2].clone(), true);
+
+        let fail = self.ir_b.create_block(format!("assert{}.fail", self.assert_num));
+        let ok = self.ir_b.create_block(format!("assert{}.ok", self.assert_num));
+        self.assert_num += 1;
+
+        // Jump to the failure block if the condition is false
+        self.ir_b.create_cond_br(gen_cond, ok, fail);
+        self.ir_b.position_at_end(fail);
+
+        // Print the file, line and message, with any '%' in the file name
+        // kept out of the format, and exit
+        let fmt = format!("Assertion failed in {} on line %d: %.*s\n", self.file.replace('%', "%%"));
+        self.create_printf(escape_string(fmt.as_str()), fmt.len() + 1, format!("i32 {}, i32 {}, i8* {}", gen_line, msg_len, msg_ptr));
+        if !self.dec_exit {
+            self.ir_b.create_ends("declare void @exit(i32)\n".to_string());
+            self.dec_exit = true;
+        }
+        self.ir_b.emit("\tcall void @exit(i32 1)\n\tunreachable\n");
+        self.ir_b.position_at_end(ok);
+        String::new()
+    }
+
+    /// Puts a pointer to the bytes of a string and its length together into a
+    /// %.Str
+    fn create_str(&mut self, ptr: String, len: String) -> String {
+        let with_ptr = self.ir_b.create_insert_value("%.Str".to_string(), "undef".to_string(), format!("i8* {}", ptr), 0);
+        self.ir_b.create_insert_value("%.Str".to_string(), with_ptr, format!("i32 {}", len), 1)
+    }
+
+    /// Takes a %.Str apart into the pointer to its bytes and its length
+    fn str_parts(&mut self, s: String) -> (String, String) {
+        let ptr = self.ir_b.create_extract_value("%.Str".to_string(), s.clone(), 0);
+        let len = self.ir_b.create_extract_value("%.Str".to_string(), s, 1);
+        return (ptr, len);
+    }
+
+    /// Compares two strings with '==' or '!='
+    /// Strings are equal when they have the same length and the same bytes
+    fn generate_str_compare(&mut self, oper: String, left: String, right: String) -> String {
+        let (left_ptr, left_len) = self.str_parts(left);
+        let (right_ptr, right_len) = self.str_parts(right);
+        let same_len = self.ir_b.create_operation("==".to_string(), Type::Int, left_len.clone(), right_len);
+
+        // Only compare the bytes when the lengths match, which keeps memcmp
+        // inside of both strings
+        self.declare_extern("declare i32 @memcmp(i8*, i8*, i64)");
+        self.ir_b.emit(format!("\t%{} = select i1 {}, i32 {}, i32 0\n", self.ir_b.ssa_num, same_len, left_len).as_str());
+        self.ir_b.ssa_num += 1;
+        let n = self.ir_b.create_cast("zext", "i32".to_string(), format!("%{}", self.ir_b.ssa_num - 1), "i64".to_string());
+        self.ir_b.emit(format!("\t%{} = call i32 @memcmp(i8* {}, i8* {}, i64 {})\n", self.ir_b.ssa_num, left_ptr, right_ptr, n).as_str());
+        self.ir_b.ssa_num += 1;
+        let same_bytes = self.ir_b.create_operation("==".to_string(), Type::Int, format!("%{}", self.ir_b.ssa_num - 1), "0".to_string());
+        let equal = self.ir_b.create_operation("and".to_string(), Type::Bool, same_len, same_bytes);
+        if oper == "!=" {
+            return self.ir_b.create_operation("==".to_string(), Type::Bool, equal, "false".to_string());
+        }
+        return equal;
+    }
+
+    /// Gives the arguments that printf needs to print a value
+    /// Strings are printed with '%.*s', which takes the length and then the
+    /// pointer
+    /// Bools are printed as the word 'true' or 'false'
+    /// Values passed through '...' are promoted the way C promotes them, so
+    /// chars are widened to an i32, while decs are already the double that
+    /// '%f' reads
+    fn printf_arg(&mut self, typ: Type, value: String) -> String {
+        match typ {
+            Type::Str => {
+                let (ptr, len) = self.str_parts(value);
+                return format!("i32 {}, i8* {}", len, ptr);
+            },
+            Type::Bool => {
+                let word = self.bool_word(value);
+                return format!("i8* {}", word);
+            },
+            Type::Char => {
+                let value = self.ir_b.create_cast("zext", type_of(typ), value, "i32".to_string());
+                return format!("i32 {}", value);
+            },
+            _ => format!("{} {}", type_of(typ), value)
+        }
+    }
+
+    /// Picks the word 'true' or 'false' for a bool, declaring the words if
+    /// they aren't already
+    fn bool_word(&mut self, value: String) -> String {
+        if !self.dec_bool_strs {
+            self.ir_b.create_global("@.true".to_string(), "[5 x i8] c\"true\\00\"".to_string());
+            self.ir_b.create_global("@.false".to_string(), "[6 x i8] c\"false\\00\"".to_string());
+            self.dec_bool_strs = true;
+        }
+        self.ir_b.emit(format!("\t%{} = select i1 {}, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.true, i32 0, i32 0), i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.false, i32 0, i32 0)\n", self.ir_b.ssa_num, value).as_str());
+        self.ir_b.ssa_num += 1;
+        format!("%{}", self.ir_b.ssa_num - 1)
+    }
+
+    /// Generates a call to strlen, declaring it if it isn't already
+    /// The argument is given with its type, like 'i8* %0'
+    fn generate_strlen(&mut self, arg: String) -> String {
+        if !self.dec_strlen {
+            self.ir_b.create_ends("declare i32 @strlen(i8*)\n".to_string());
+            self.dec_strlen = true;
+        }
+        self.ir_b.emit(format!("\t%{} = call i32 @strlen({})\n", self.ir_b.ssa_num, arg).as_str());
+        self.ir_b.ssa_num += 1;
+        format!("%{}", self.ir_b.ssa_num - 1)
+    }
+
+    /// Generates code that checks an index against a length
+    /// Prints the index and the length and exits if it is out of bounds
+    fn generate_bounds_check(&mut self, index: String, len: String) {
+        // Comparing as unsigned catches negative indexes too
+        self.ir_b.emit(format!("\t%{} = icmp ult i32 {}, {}\n", self.ir_b.ssa_num, index, len).as_str());
+        self.ir_b.ssa_num += 1;
+        let in_bounds = format!("%{}", self.ir_b.ssa_num - 1);
+
+        let fail = self.ir_b.create_block(format!("bounds{}.fail", self.bounds_num));
+        let ok = self.ir_b.create_block(format!("bounds{}.ok", self.bounds_num));
+        self.bounds_num += 1;
+        self.ir_b.create_cond_br(in_bounds, ok, fail);
+        self.ir_b.position_at_end(fail);
+
+        let fmt = "Index %d is out of bounds for length %d\n";
+        self.create_printf(escape_string(fmt), fmt.len() + 1, format!("i32 {}, i32 {}", index, len));
+        if !self.dec_exit {
+            self.ir_b.create_ends("declare void @exit(i32)\n".to_string());
+            self.dec_exit = true;
+        }
+        self.ir_b.emit("\tcall void @exit(i32 1)\n\tunreachable\n");
+        self.ir_b.position_at_end(ok);
+    }
+
+    /// Generates code for a formatted write
+    /// Each '{}' in the format is replaced by the next argument
+    #[allow(clippy::vec_box)]
+    fn generate_writef(&mut self, args: Vec<Box<Expr>>) -> String {
+        let contents = match *args[0].clone() {
+            Expr::Str(s) => s,
+            _ => String::new()
+        };
+
+        // Build the printf format out of the literal parts and the types of
+        // the arguments
+        let mut fmt = String::new();
+        let mut values: Vec<String> = Vec::new();
+        for (i, part) in contents.split("{}").enumerate() {
+            if i > 0 {
+                let arg = *args[i].clone();
+                fmt.push_str(format_of(arg.validate()));
+                let gen_arg = self.generate_expression(arg.clone(), true);
+                values.push(self.printf_arg(arg.validate(), gen_arg));
+            }
+            fmt.push_str(part.replace('%', "%%").as_str());
+        }
+        let fmt_len = fmt.len() + 1;
+        self.create_printf(escape_string(fmt.as_str()), fmt_len, values.join(", "));
+        String::new()
+    }
+
+    /// Creates a global constant for the format and calls printf with it
+    fn create_printf(&mut self, fmt: String, fmt_len: usize, arg_values: String) {
+        self.ir_b.create_global(format!("@fmt{}", self.format_num), format!("[{} x i8] c\"{}\\00\"", fmt_len, fmt));
+
+        // If @printf is not declared, declare it
+        if !self.dec_printf {
+            self.ir_b.create_ends("declare i32 @printf(i8*, ...)\n".to_string());
+            self.dec_printf = true;
+        }
+
+        // Generate the function call
+        let mut call_args = format!("i8* getelementptr inbounds ([{} x i8], [{0} x i8]* @fmt{}, i32 0, i32 0)", fmt_len, self.format_num);
+        if !arg_values.is_empty() {
+            call_args.push_str(format!(", {}", arg_values).as_str());
+        }
+        self.ir_b.emit(format!("\tcall i32 (i8*, ...) @printf({})\n", call_args).as_str());
+        self.format_num += 1;
+        self.ir_b.ssa_num += 1;
     }
 
     /// Generates code for a function call
+    #[allow(clippy::vec_box)]
     fn generate_func_call(&mut self, id: String, typ: String, args: Vec<Box<Expr>>) -> String {
+        if id.as_str() == "assert" {
+            return self.generate_assert(args);
+        }
+        if id.as_str() == "writef" {
+            return self.generate_writef(args);
+        }
+        if id.as_str() == "len" && args[0].validate() == Type::Str {
+            // Strings carry their length
+            let gen_arg = self.generate_expression(*args[0].clone(), true);
+            return self.ir_b.create_extract_value("%.Str".to_string(), gen_arg, 1);
+        }
+
         // New string to store the arguments
         let mut arg_values = String::new();
 
-        // Number of arguments
-        let mut arg_num = 0;
+        // Which of the arguments are passed by reference
+        let refs = self.functions.get(&id).map(|f| f.1.clone()).unwrap_or_default();
+
+        // A function that returns a struct stores it in space given by the
+        // caller
+        let sret = match self.functions.get(&id) {
+            Some((ret, _)) if is_struct(ret) => {
+                let space = self.ir_b.create_alloca(typ.clone(), None);
+                arg_values.push_str(format!("{0}* sret({0}) {1}", typ, space).as_str());
+                if !args.is_empty() {
+                    arg_values.push_str(", ");
+                }
+                Some(space)
+            },
+            _ => None
+        };
 
         // Iterate through the arguments
-        for arg in args.iter() {
-            // Generate the argument expression
-            let gen_arg = self.generate_expression(*arg.clone(), true);
-
+        for (arg_num, arg) in args.iter().enumerate() {
             // Find the type of the current argument
-            let typ = type_of((*arg.clone().validate()).to_string());
-
-            // If the function call is a built-in, don't make the argument
-            // a pointer
-            // Otherwise, make the argument a pointer
-            if id.clone().as_str() != "write" && id.clone().as_str() != "len" {
-                // Allocate space for the pointer
-                let alloca = self.ir_b.create_alloca(typ.clone(), None);
-
-                // Store the value in the pointer
-                self.ir_b.create_store(gen_arg.clone(), alloca.clone(), typ.clone());
-
-                // Use the pointer as the argument
-                arg_values.push_str(format!("{}* {}", typ, alloca).as_str());
+            let typ = type_of(arg.validate());
+
+            // 'ref' arguments pass a pointer to the variable itself
+            // Otherwise, pass the value
+            if refs.get(arg_num) == Some(&true) {
+                let gen_arg = self.generate_expression(*arg.clone(), false);
+                arg_values.push_str(format!("{}* {}", typ, gen_arg).as_str());
+            } else if is_struct(&arg.validate()) && self.functions.contains_key(&id) {
+                // A struct is passed as a pointer, the function copies it
+                // A variable can be pointed to directly, other values are put
+                // into a temporary first
+                let gen_arg = if let Expr::Id(..) = **arg {
+                    self.generate_expression(*arg.clone(), false)
+                } else {
+                    let value = self.generate_expression(*arg.clone(), true);
+                    let space = self.ir_b.create_alloca(typ.clone(), None);
+                    self.ir_b.create_store(value, space.clone(), typ.clone());
+                    space
+                };
+                arg_values.push_str(format!("{}* {}", typ, gen_arg).as_str());
+            } else if arg.validate() == Type::Str && !self.functions.contains_key(&id) {
+                // Builtins hand strings to libc, which wants the pointer to
+                // the bytes, except 'write' which also prints the length
+                let gen_arg = self.generate_expression(*arg.clone(), true);
+                if id == "write" {
+                    let value = self.printf_arg(Type::Str, gen_arg);
+                    arg_values.push_str(value.as_str());
+                } else {
+                    let (ptr, _) = self.str_parts(gen_arg);
+                    arg_values.push_str(format!("i8* {}", ptr).as_str());
+                }
             } else {
-                // Use the value as the argument
-                arg_values.push_str(format!("{} {}", typ, gen_arg).as_str());
+                // Generate the argument expression, functions get their own
+                // copy of arrays
+                let gen_arg = if self.functions.contains_key(&id) {
+                    self.generate_copy(*arg.clone(), false)
+                } else {
+                    self.generate_expression(*arg.clone(), true)
+                };
+
+                // Use the value as the argument, promoted if it goes to
+                // printf
+                if id == "write" || id == "to_string" {
+                    let value = self.printf_arg(arg.validate(), gen_arg);
+                    arg_values.push_str(value.as_str());
+                } else {
+                    arg_values.push_str(format!("{} {}", typ, gen_arg).as_str());
+                }
             }
 
             // If the end of the arguments isn't reached, add a comma
             if arg_num + 1 < args.len() {
                 arg_values.push_str(", ");
             }
-
-            // Increment the number of arguments
-            arg_num += 1;
         }
 
         match id.clone().as_str() {
@@ -635,67 +2078,463 @@ impl Generator {
                 // Iterate through the arguments
                 for arg in args.iter() {
                     // Add the format type to the 'fmt' string
-                    let c = match arg.validate() {
-                        "int" | "bool" => "%d",
-                        "dec" => "%f",
-                        "string" => "%s",
-                        "char" => "%c",
-                        _ => ""
-                    };
-                    fmt.push_str(format!("{}", c).as_str());
-                    
-                    // Increment the length by 2 because %d is two characters
-                    fmt_len += 2;
+                    fmt.push_str(format_of(arg.validate()));
+                    fmt_len += format_of(arg.validate()).len();
                 }
                 
-                // Add the NUL terminator to the string
-                fmt.push_str("\\00");
+                // End the line
+                fmt.push_str("\\0A");
+                fmt_len += 1;
 
-                // Create a global constant for the format
-                self.ir_b.create_global(format!("@fmt{}", self.format_num), format!("[{} x i8] c\"{}\"", fmt_len, fmt));
-
-                // If @printf is not declared, declare it
-                if !self.dec_printf {
-                    self.ir_b.create_ends(format!("declare i32 @printf(i8*, ...)\n"));
-                    self.dec_printf = true;
+                self.create_printf(fmt, fmt_len, arg_values.clone());
+            },
+            "len" => {
+                let gen_expr = self.generate_expression(*args[0].clone(), false);
+                let gep = self.ir_b.create_gep("%.Arr".to_string(), gen_expr, vec!["0".to_string(), "1".to_string()]);
+                self.ir_b.create_load("i32".to_string(), gep);
+            },
+            "env" => {
+                // If @getenv is not declared, declare it along with the
+                // string used for missing variables
+                if !self.dec_getenv {
+                    self.ir_b.create_ends("declare i8* @getenv(i8*)\n".to_string());
+                    self.ir_b.create_global("@.empty".to_string(), "[1 x i8] c\"\\00\"".to_string());
+                    self.dec_getenv = true;
                 }
 
-                // Generate the function call
-                self.ir_b.code.push_str(format!("\tcall i32 (i8*, ...) @printf(i8* getelementptr inbounds ([{} x i8], [{0} x i8]* @fmt{}, i32 0, i32 0), {})\n", fmt_len, self.format_num, arg_values.clone()).as_str());
+                // Missing variables give back null, so use an empty string
+                // instead
+                self.ir_b.emit(format!("\t%{} = call i8* @getenv({})\n", self.ir_b.ssa_num, arg_values.clone()).as_str());
+                self.ir_b.emit(format!("\t%{} = icmp eq i8* %{}, null\n", self.ir_b.ssa_num + 1, self.ir_b.ssa_num).as_str());
+                self.ir_b.emit(format!("\t%{} = select i1 %{}, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.empty, i32 0, i32 0), i8* %{}\n", self.ir_b.ssa_num + 2, self.ir_b.ssa_num + 1, self.ir_b.ssa_num).as_str());
+                self.ir_b.ssa_num += 3;
+                let value = format!("%{}", self.ir_b.ssa_num - 1);
+                let len = self.generate_strlen(format!("i8* {}", value));
+                return self.create_str(value, len);
+            },
+            "to_string" => {
+                let fmt = format_of(args[0].validate());
+
+                // Create a global constant for the format
+                self.ir_b.create_global(format!("@fmt{}", self.format_num), format!("[{} x i8] c\"{}\\00\"", fmt.len() + 1, fmt));
+
+                // Print the value into a new buffer, which is big enough for
+                // any int, char or bool and all but the biggest decs
+                self.declare_extern("declare i32 @snprintf(i8*, i64, i8*, ...)");
+                let buf = self.generate_heap_alloc("64".to_string());
+                self.ir_b.emit(format!("\tcall i32 (i8*, i64, i8*, ...) @snprintf(i8* {}, i64 64, i8* getelementptr inbounds ([{} x i8], [{} x i8]* @fmt{}, i32 0, i32 0), {})\n", buf, fmt.len() + 1, fmt.len() + 1, self.format_num, arg_values).as_str());
                 self.format_num += 1;
                 self.ir_b.ssa_num += 1;
+                let len = self.generate_strlen(format!("i8* {}", buf));
+                return self.create_str(buf, len);
             },
-            "len" => {
-                if (*args[0].clone()).validate() == "string" {
-                    // Generate the function call
-                    self.ir_b.code.push_str(format!("\t%{} = call i32 @strlen({})\n", self.ir_b.ssa_num, arg_values.clone()).as_str());
-                    if !self.dec_strlen {
-                        self.ir_b.create_ends(format!("declare i32 @strlen(i8*)\n"));
-                        self.dec_strlen = true;
-                    }
+            "read" | "read_line" => {
+                if !self.dec_input {
+                    self.ir_b.create_global("@.word".to_string(), "[6 x i8] c\"%255s\\00\"".to_string());
+                    self.ir_b.create_global("@.newline".to_string(), "[2 x i8] c\"\\0A\\00\"".to_string());
+                    self.dec_input = true;
+                }
+
+                // Start with an empty string in case there is nothing left
+                // to read
+                let size = if id == "read" {256} else {1024};
+                let buf = self.generate_heap_alloc(size.to_string());
+                self.ir_b.emit(format!("\tstore i8 0, i8* {}\n", buf).as_str());
+
+                if id == "read" {
+                    // Read a single word
+                    self.declare_extern("declare i32 @scanf(i8*, ...)");
+                    self.ir_b.emit(format!("\tcall i32 (i8*, ...) @scanf(i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.word, i32 0, i32 0), i8* {})\n", buf).as_str());
                     self.ir_b.ssa_num += 1;
                 } else {
-                    let gen_expr = self.generate_expression(*args[0].clone(), false);
-                    let gep = self.ir_b.create_gep("%.Arr".to_string(), gen_expr, vec!["0".to_string(), "1".to_string()]);
-                    self.ir_b.create_load("i32".to_string(), gep);
+                    // Read a whole line, then cut off the newline
+                    self.declare_extern("@stdin = external global i8*");
+                    self.declare_extern("declare i8* @fgets(i8*, i32, i8*)");
+                    self.declare_extern("declare i64 @strcspn(i8*, i8*)");
+                    let stdin = self.ir_b.create_load("i8*".to_string(), "@stdin".to_string());
+                    self.ir_b.emit(format!("\tcall i8* @fgets(i8* {}, i32 {}, i8* {})\n", buf, size, stdin).as_str());
+                    self.ir_b.emit(format!("\t%{} = call i64 @strcspn(i8* {}, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.newline, i32 0, i32 0))\n", self.ir_b.ssa_num + 1, buf).as_str());
+                    self.ir_b.emit(format!("\t%{} = getelementptr inbounds i8, i8* {}, i64 %{}\n", self.ir_b.ssa_num + 2, buf, self.ir_b.ssa_num + 1).as_str());
+                    self.ir_b.emit(format!("\tstore i8 0, i8* %{}\n", self.ir_b.ssa_num + 2).as_str());
+                    self.ir_b.ssa_num += 3;
                 }
+                let len = self.generate_strlen(format!("i8* {}", buf));
+                return self.create_str(buf, len);
+            },
+            "sqrt" | "pow" | "abs" | "floor" | "ceil" | "round" | "sin" | "cos" | "tan" | "exp" | "log" => {
+                // Most of these have an llvm intrinsic, tan comes from libm
+                let func = match id.as_str() {
+                    "abs" => "llvm.fabs.f64".to_string(),
+                    "tan" => "tan".to_string(),
+                    _ => format!("llvm.{}.f64", id)
+                };
+                let params = vec!["double"; args.len()].join(", ");
+                self.declare_extern(format!("declare double @{}({})", func, params).as_str());
+                self.ir_b.emit(format!("\t%{} = call double @{}({})\n", self.ir_b.ssa_num, func, arg_values.clone()).as_str());
+                self.ir_b.ssa_num += 1;
+            },
+            "clock" => {
+                // Seconds of processor time, CLOCKS_PER_SEC is a million on
+                // every POSIX system
+                self.declare_extern("declare i64 @clock()");
+                self.ir_b.emit(format!("\t%{} = call i64 @clock()\n", self.ir_b.ssa_num).as_str());
+                self.ir_b.emit(format!("\t%{} = sitofp i64 %{} to double\n", self.ir_b.ssa_num + 1, self.ir_b.ssa_num).as_str());
+                self.ir_b.emit(format!("\t%{} = fdiv double %{}, 1000000.0\n", self.ir_b.ssa_num + 2, self.ir_b.ssa_num + 1).as_str());
+                self.ir_b.ssa_num += 3;
+            },
+            "time" => {
+                // Seconds since the epoch
+                self.declare_extern("declare i64 @time(i64*)");
+                self.ir_b.emit(format!("\t%{} = call i64 @time(i64* null)\n", self.ir_b.ssa_num).as_str());
+                self.ir_b.emit(format!("\t%{} = sitofp i64 %{} to double\n", self.ir_b.ssa_num + 1, self.ir_b.ssa_num).as_str());
+                self.ir_b.ssa_num += 2;
+            },
+            "chr" | "ord" => {
+                // Chars are bytes, so only the low byte of an int is kept
+                let gen_arg = arg_values.split_once(' ').map(|a| a.1.to_string()).unwrap_or_default();
+                if id == "chr" {
+                    return self.ir_b.create_cast("trunc", "i32".to_string(), gen_arg, "i8".to_string());
+                }
+                return self.ir_b.create_cast("zext", "i8".to_string(), gen_arg, "i32".to_string());
+            },
+            "parse_int" => {
+                // Anything that isn't a number gives back 0
+                self.declare_extern("declare i32 @atoi(i8*)");
+                self.ir_b.emit(format!("\t%{} = call i32 @atoi({})\n", self.ir_b.ssa_num, arg_values.clone()).as_str());
+                self.ir_b.ssa_num += 1;
+            },
+            "parse_dec" => {
+                self.declare_extern("declare double @atof(i8*)");
+                self.ir_b.emit(format!("\t%{} = call double @atof({})\n", self.ir_b.ssa_num, arg_values.clone()).as_str());
+                self.ir_b.ssa_num += 1;
             },
             _ => {
                 // Generate the function call
-                if typ.clone() == "void" {
-                    self.ir_b.code.push_str(format!("\tcall void @{}({})\n", id.clone(), arg_values.clone()).as_str());
+                if let Some(space) = sret {
+                    self.ir_b.emit(format!("\tcall void @{}({})\n", id.clone(), arg_values.clone()).as_str());
+                    return self.ir_b.create_load(typ, space);
+                } else if typ.clone() == "void" {
+                    self.ir_b.emit(format!("\tcall void @{}({})\n", id.clone(), arg_values.clone()).as_str());
                 } else {
-                    self.ir_b.code.push_str(format!("\t%{} = call {} @{}({})\n", self.ir_b.ssa_num, typ.clone(), id.clone(), arg_values.clone()).as_str());
+                    self.ir_b.emit(format!("\t%{} = call {} @{}({})\n", self.ir_b.ssa_num, typ.clone(), id.clone(), arg_values.clone()).as_str());
+                    self.ir_b.ssa_num += 1;
                 }
-                self.ir_b.ssa_num += 1;
             }
         };
         format!("%{}", self.ir_b.ssa_num - 1)
     }
 
     /// Generates code for a struct declaration
-    fn generate_struct_decl(&mut self, id: String, fields: Vec<(String, String)>) {
+    fn generate_struct_decl(&mut self, id: String, fields: Vec<(String, Type)>) {
         // Tell the ir builder to create a new struct
         self.ir_b.create_new_struct(id, fields);
     }
 }
+
+#[test]
+fn test_hoist_allocas() {
+    let mut ir_b = IRBuilder::construct();
+    let block = ir_b.create_label_block();
+    ir_b.create_br(block);
+    ir_b.position_at_end(block);
+    let alloca = ir_b.create_alloca("i32".to_string(), None);
+    ir_b.create_store("5".to_string(), alloca.clone(), "i32".to_string());
+    let value = ir_b.create_load("i32".to_string(), alloca.clone());
+    ir_b.emit(format!("\tret i32 {}\n", value).as_str());
+    let code = ir_b.finish_function();
+    assert_eq!(alloca, "%.tmp0");
+    assert_eq!(code, format!("{}\nentry:\n\t%.tmp0 = alloca i32\n\tbr label %l0\nl0:\n\tstore i32 5, i32* %.tmp0\n\t%0 = load i32, i32* %.tmp0\n\tret i32 %0\n}}\n\n", MAIN_HEADER));
+}
+
+#[test]
+fn test_dead_code() {
+    let mut ir_b = IRBuilder::construct();
+
+    // A load that nothing uses, and an alloca that is only stored into
+    let unused = ir_b.create_alloca("i32".to_string(), None);
+    ir_b.create_store("1".to_string(), unused.clone(), "i32".to_string());
+    ir_b.create_load("i32".to_string(), "@.0".to_string());
+    let value = ir_b.create_load("i32".to_string(), "@.1".to_string());
+    ir_b.emit(format!("\tret i32 {}\n\tret i32 0\n", value).as_str());
+
+    // A block after the 'ret' that nothing jumps to
+    let dead = ir_b.create_label_block();
+    ir_b.position_at_end(dead);
+    ir_b.emit("\tret i32 1\n");
+    let code = ir_b.finish_function();
+    assert_eq!(code, format!("{}\nentry:\n\t%0 = load i32, i32* @.1\n\tret i32 %0\n}}\n\n", MAIN_HEADER));
+}
+
+#[test]
+fn test_float_operations() {
+    let mut ir_b = IRBuilder::construct();
+    ir_b.create_operation("+".to_string(), Type::Dec, "%a".to_string(), "0.5".to_string());
+    ir_b.create_operation("/".to_string(), Type::Int, "%b".to_string(), "3".to_string());
+    ir_b.create_operation("<=".to_string(), Type::Dec, "%0".to_string(), "1.0".to_string());
+    assert!(ir_b.blocks[0].code.ends_with("\t%0 = fadd double %a, 0.5\n\t%1 = sdiv i32 %b, 3\n\t%2 = fcmp ole double %0, 1.0\n"));
+}
+
+#[test]
+fn test_peephole() {
+    let mut ir_b = IRBuilder::construct();
+    assert_eq!(ir_b.create_operation("*".to_string(), Type::Int, "%x".to_string(), "1".to_string()), "%x");
+    assert_eq!(ir_b.create_operation("+".to_string(), Type::Int, "0".to_string(), "%y".to_string()), "%y");
+    assert_eq!(ir_b.create_operation("+".to_string(), Type::Int, "2147483647".to_string(), "1".to_string()), "-2147483648");
+    assert_eq!(ir_b.create_operation("+".to_string(), Type::Char, "200".to_string(), "100".to_string()), "44");
+    assert_eq!(ir_b.create_operation("/".to_string(), Type::Dec, "1.0".to_string(), "4.0".to_string()), "0x3FD0000000000000");
+    assert_eq!(ir_b.create_operation("-".to_string(), Type::Bool, "true".to_string(), "1".to_string()), "false");
+    assert_eq!(ir_b.create_cast("sitofp", "i32".to_string(), "2".to_string(), "double".to_string()), "0x4000000000000000");
+
+    // Adding 0.0 keeps the sign of -0.0 from being lost
+    assert_eq!(ir_b.create_operation("+".to_string(), Type::Dec, "%d".to_string(), "0.0".to_string()), "%0");
+    assert_eq!(ir_b.blocks[0].code, "\t%0 = fadd double %d, 0.0\n");
+}
+
+#[test]
+fn test_phi() {
+    let mut ir_b = IRBuilder::construct();
+    let then = ir_b.create_label_block();
+    let end = ir_b.create_label_block();
+    ir_b.create_cond_br("true".to_string(), then, end);
+    ir_b.position_at_end(then);
+    ir_b.create_br(end);
+    ir_b.position_at_end(end);
+    assert_eq!(ir_b.create_phi("i32".to_string(), vec![("1".to_string(), 0), ("2".to_string(), then)]), "%0");
+    assert_eq!(ir_b.blocks[end].code, "\t%0 = phi i32 [ 1, %entry ], [ 2, %l0 ]\n");
+}
+
+#[test]
+fn test_assert() {
+    let code = "assert(1 > 2, \"math is broken\");\n";
+    let mut driver = crate::driver::Driver::new();
+    driver.file = "100%.gizmo".to_string();
+    let ir = driver.compile(code.to_string()).unwrap();
+    assert!(ir.contains("@fmt0 = constant [50 x i8] c\"Assertion failed in 100%%.gizmo on line %d: %.*s\\0A\\00\""));
+    assert!(ir.contains("@fmt0, i32 0, i32 0), i32 1, i32 %"));
+    if let Some((status, output)) = crate::build::run_program(code, "") {
+        assert_eq!((status, output.as_str()), (1, "Assertion failed in <input> on line 1: math is broken\n"));
+    }
+}
+
+#[test]
+fn test_explicit_main() {
+    let code = "func main() {\n    write(1);\n}\n";
+    let ir = crate::build::check_program(code, "", &[], "1\n");
+    assert_eq!(ir.matches("define i32 @main()").count(), 1);
+}
+
+#[test]
+fn test_deferred_let() {
+    let code = "let x: int;\nif true {\n    x = 1;\n} else {\n    x = 2;\n}\nwrite(x);\n";
+    crate::build::check_program(code, "", &["store i32 1, i32* @x.0\n\tbr label %l", "store i32 2, i32* @x.0\n"], "1\n");
+}
+
+#[test]
+fn test_struct_abi() {
+    let code = "struct P {\n    x: int\n}\nfunc make(p: P): P {\n    ret p;\n}\nlet p = make(new P(1));\nwrite(p.x);\n";
+    crate::build::check_program(code, "", &["define void @gz_make(%P* sret(%P) %.ret, %P* %p.0.arg)"], "1\n");
+}
+
+#[test]
+fn test_fields_one_by_one() {
+    let code = "struct P {\n    x: int,\n    y: int\n}\nfunc main() {\n    var p: P;\n    p.x = 1;\n    p.y = 2;\n    write(p.x + p.y);\n}\n";
+    crate::build::check_program(code, "", &["%p.0 = alloca %P", "getelementptr inbounds %P, %P* %p.0, i32 0, i32 1"], "3\n");
+}
+
+#[test]
+fn test_escaped_string() {
+    let code = "let s = \"a\\\"b\\n\";\nwrite(s);\n";
+    crate::build::check_program(code, "", &["@.str.0 = constant [5 x i8] c\"a\\22b\\0A\\00\""], "a\"b\n\n");
+}
+
+#[test]
+fn test_bounds_checks() {
+    let code = "let a = [1, 2];\nwrite(a[2]);\n".to_string();
+    let ir = crate::driver::Driver::new().compile(code.clone()).unwrap();
+    assert!(!ir.contains("bounds0.fail"));
+
+    let mut driver = crate::driver::Driver::new();
+    driver.bounds_checks = true;
+    let ir = driver.compile(code).unwrap();
+    assert!(ir.contains("icmp ult i32 2, "));
+    assert!(ir.contains("bounds0.fail:"));
+}
+
+#[test]
+fn test_string_length() {
+    let code = "let s = \"abc\";\nwrite(len(s));\n";
+    let ir = crate::build::check_program(code, "", &["%.Str = type {\n\ti8*,\n\ti32\n}"], "3\n");
+    assert!(!ir.contains("@strlen"));
+}
+
+#[test]
+fn test_gc() {
+    let mut driver = crate::driver::Driver::new();
+    driver.gc = true;
+    let ir = driver.compile("let s = to_string(5);\nwrite(s);\n".to_string()).unwrap();
+    assert!(ir.contains("call i8* @GC_malloc(i64 64)"));
+    assert!(!ir.contains("@malloc"));
+}
+
+#[test]
+fn test_array_copy() {
+    // Only 'b' is copied, since the literal in 'c' isn't used anywhere else
+    let code = "var a = [1, 2];\nvar b = a;\nb[0] = 3;\nlet c = [4];\nwrite(a[0], b[0], c[0]);\n";
+    let ir = crate::build::check_program(code, "", &[], "134\n");
+    assert_eq!(ir.matches("call void @llvm.memcpy.p0i8.p0i8.i64").count(), 1);
+}
+
+#[test]
+fn test_globals() {
+    let code = "var count = 1;\nfunc bump() {\n    count = count + 1;\n}\nbump();\nwrite(count);\n";
+    crate::build::check_program(code, "", &["@count.0 = global i32 1"], "2\n");
+}
+
+#[test]
+fn test_globals_with_main() {
+    // The program's own main gives the variables declared outside of it their
+    // values before its body runs
+    let code = "let g = 5;\nvar s = \"hi\";\nfunc main() {\n    write(g, s);\n}\n";
+    let ir = crate::build::check_program(code, "", &["@g.0 = global i32 5"], "5hi\n");
+    assert!(ir.find("%.Str* @s.1\n").unwrap() < ir.find("load i32, i32* @g.0").unwrap());
+}
+
+#[test]
+fn test_values_in_registers() {
+    let code = "func add(a: int, b: int): int {\n    let c = a + b;\n    ret c;\n}\nwrite(add(1, 2));\n".to_string();
+    let ir = crate::driver::Driver::new().compile(code).unwrap();
+    assert!(ir.contains("define i32 @gz_add(i32 %a.0.arg, i32 %b.1.arg) {\nentry:\n\t%0 = add i32 %a.0.arg, %b.1.arg\n\tret i32 %0\n}"));
+}
+
+#[test]
+fn test_long() {
+    let code = "func twice(n: long): long {\n    ret n * 2 as long;\n}\nwrite(twice(3000000000));\n";
+    crate::build::check_program(code, "", &["define i64 @gz_twice(i64 %n.0.arg)"], "6000000000\n");
+}
+
+#[test]
+fn test_uint() {
+    let code = "func half(a: uint, b: uint): uint {\n    if a < b {\n        ret a % b;\n    }\n    ret a / b;\n}\nwrite(half(4000000000 as uint, 2 as uint));\n";
+    crate::build::check_program(code, "", &["icmp ult i32 %a.0.arg, %b.1.arg", "udiv i32 %a.0.arg, %b.1.arg"], "2000000000\n");
+}
+
+#[test]
+fn test_debug_info() {
+    let mut driver = crate::driver::Driver::new();
+    driver.debug = true;
+    driver.file = "sum.gizmo".to_string();
+    let ir = driver.compile("func one(): int {\n    ret 1;\n}\nwrite(one());\n".to_string()).unwrap();
+    assert!(ir.contains("define i32 @gz_one() !dbg !6 {\nentry:\n\tret i32 1, !dbg !7\n}"));
+    assert!(ir.contains("!6 = distinct !DISubprogram(name: \"one\", linkageName: \"gz_one\", scope: !0, file: !0, line: 1,"));
+    assert!(ir.contains("!7 = !DILocation(line: 2, scope: !6)"));
+    assert!(ir.contains("call i32 @gz_one(), !dbg !8"));
+    assert!(ir.contains("!llvm.dbg.cu = !{!1}"));
+}
+
+#[test]
+fn test_debug_info_explicit_main() {
+    // The implicit main is never generated, so it gets no subprogram, and
+    // the instructions on a line share one location
+    let mut driver = crate::driver::Driver::new();
+    driver.debug = true;
+    let ir = driver.compile("let g = 5;\nfunc main() {\n    write(g, len(\"ab\"));\n}\n".to_string()).unwrap();
+    assert_eq!(ir.matches("name: \"main\"").count(), 1);
+    assert_eq!(ir.matches("!DILocation(line: 3,").count(), 1);
+}
+
+#[test]
+fn test_mangling() {
+    // A function named like one from libc doesn't replace it
+    let code = "func printf(x: int) {\n    write(x);\n}\nfunc main() {\n    printf(1);\n}\n";
+    crate::build::check_program(code, "", &["define void @gz_printf(i32 %x.0.arg)"], "1\n");
+}
+
+#[test]
+fn test_ir_comments() {
+    let mut driver = crate::driver::Driver::new();
+    driver.ir_comments = true;
+    let ir = driver.compile("var x = 5;\nx = x % 2;\nwrite(x);\n".to_string()).unwrap();
+    assert!(ir.contains("entry:\n\t; var x = 5; (line 1)\n\t; x = x % 2; (line 2)\n\t%0 = load i32, i32* @x.0\n"));
+    assert!(ir.contains("\t; write(x); (line 3)\n"));
+}
+
+#[test]
+fn test_write_promotion() {
+    let code = "let d = 2.5;\nlet c = 'a';\nlet b = d > 1.0;\nwrite(d, c, b);\nwritef(\"{}\\n\", c);\n";
+    crate::build::check_program(code, "", &["@fmt0 = constant [8 x i8] c\"%f%c%s\\0A\\00\"", "zext i8 %"], "2.500000atrue\na\n");
+}
+
+#[test]
+fn test_write_bool() {
+    // Bools are written as the same words 'to_string' gives
+    let code = "write(true, 1 > 2);\nwritef(\"{}\\n\", false);\n";
+    let fragments = ["@fmt0 = constant [6 x i8] c\"%s%s\\0A\\00\"", "select i1 true, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.true, i32 0, i32 0)"];
+    crate::build::check_program(code, "", &fragments, "truefalse\nfalse\n");
+}
+
+#[test]
+fn test_module_metadata() {
+    let mut driver = crate::driver::Driver::new();
+    driver.file = "sum.gizmo".to_string();
+    let ir = driver.compile("write(1);\n".to_string()).unwrap();
+    assert!(ir.starts_with("; ModuleID = 'sum.gizmo'\nsource_filename = \"sum.gizmo\"\ntarget"));
+    assert!(ir.contains("!llvm.ident = !{!1}\n!llvm.module.flags = !{!0}\n"));
+    assert!(ir.contains(format!("!1 = !{{!\"gizmoc version {}\"}}", env!("CARGO_PKG_VERSION")).as_str()));
+}
+
+#[test]
+fn test_env() {
+    let code = "write(len(env(\"GIZMO_UNSET_VARIABLE\")));\nwrite(len(env(\"PATH\")) > 0);\n";
+    crate::build::check_program(code, "", &["call i8* @getenv(i8* %", "icmp eq i8* %"], "0\ntrue\n");
+}
+
+#[test]
+fn test_parse_numbers() {
+    let code = "write(parse_int(\"42\") + 1);\nwrite(parse_dec(\"2.5\") * 2.0);\n";
+    let fragments = ["call i32 @atoi(i8* %", "call double @atof(i8* %", "declare i32 @atoi(i8*)", "declare double @atof(i8*)"];
+    crate::build::check_program(code, "", &fragments, "43\n5.000000\n");
+}
+
+#[test]
+fn test_to_string() {
+    let code = "let s = to_string(7);\nwrite(len(s), s, to_string(true), to_string('c'), to_string(1.5));\n";
+    let fragments = [
+        "call i32 (i8*, i64, i8*, ...) @snprintf(i8* %",
+        "i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.true, i32 0, i32 0), i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.false, i32 0, i32 0)"
+    ];
+    crate::build::check_program(code, "", &fragments, "17truec1.500000\n");
+}
+
+#[test]
+fn test_read() {
+    // 'read_line' takes the whole line, 'read' skips spaces and takes a word
+    let code = "let line = read_line();\nlet word = read();\nwrite(line);\nwrite(word);\n";
+    let fragments = ["call i8* @fgets(i8* %", "@scanf(i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.word, i32 0, i32 0), i8* %"];
+    crate::build::check_program(code, "hello world\n  next word\n", &fragments, "hello world\nnext\n");
+}
+
+#[test]
+fn test_math() {
+    let code = "write(sqrt(16.0), pow(2.0, 10.0));\nwrite(abs(-3.5), floor(2.7), ceil(2.1), round(2.5));\nwrite(exp(0.0), log(1.0), sin(0.0), cos(0.0), tan(0.0));\n";
+    let fragments = ["call double @llvm.sqrt.f64(double 16.0)", "call double @llvm.round.f64(double 2.5)", "call double @tan(double 0.0)"];
+    crate::build::check_program(code, "", &fragments, "4.0000001024.000000\n3.5000002.0000003.0000003.000000\n1.0000000.0000000.0000001.0000000.000000\n");
+}
+
+#[test]
+fn test_clock() {
+    let code = "let start = clock();\nwrite(clock() >= start, time() > 1600000000.0);\n";
+    crate::build::check_program(code, "", &["= call i64 @clock()\n", ", 1000000.0\n", "call i64 @time(i64* null)"], "truetrue\n");
+}
+
+#[test]
+fn test_writef() {
+    let code = "writef(\"{} and {}\\n\", 1, \"two\");\nwritef(\"no newline\");\nwritef(\"\\n\");\n";
+    let fragments = ["@fmt0 = constant [13 x i8] c\"%d and %.*s\\0A\\00\"", "@fmt1 = constant [11 x i8] c\"no newline\\00\""];
+    crate::build::check_program(code, "", &fragments, "1 and two\nno newline\n");
+}
+
+#[test]
+fn test_chr_ord() {
+    let code = "write(chr(65), ord('a'));\n";
+    crate::build::check_program(code, "", &["@fmt0, i32 0, i32 0), i32 65, i32 97)"], "A97\n");
+}
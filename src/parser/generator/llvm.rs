@@ -0,0 +1,104 @@
+//! A backend that hands the generated ir to llvm in process, instead of
+//! writing it to a file for 'llc'
+//!
+//! The ir is parsed into a real module, which llvm verifies before it is
+//! turned into an object file for the target in the module's header
+
+use inkwell::context::Context;
+use inkwell::memory_buffer::MemoryBuffer;
+use inkwell::module::Module;
+use inkwell::support::load_library_permanently;
+use inkwell::targets::CodeModel;
+use inkwell::targets::FileType;
+use inkwell::targets::InitializationConfig;
+use inkwell::targets::RelocMode;
+use inkwell::targets::Target;
+use inkwell::passes::PassManager;
+use inkwell::passes::PassManagerBuilder;
+use inkwell::OptimizationLevel;
+use std::path::Path;
+
+/// Parses llvm ir into a module of a context
+fn parse<'ctx>(context: &'ctx Context, ir: &str) -> Result<Module<'ctx>, String> {
+    let buffer = MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "gizmo");
+    return context.create_module_from_ir(buffer).map_err(|e| format!("llvm couldn't parse the generated ir:\n{}", e.to_string()));
+}
+
+/// Finds llvm's optimization level for a level from 0 to 3
+fn level_of(opt_level: u32) -> OptimizationLevel {
+    match opt_level {
+        0 => OptimizationLevel::None,
+        1 => OptimizationLevel::Less,
+        2 => OptimizationLevel::Default,
+        _ => OptimizationLevel::Aggressive
+    }
+}
+
+/// Parses and verifies generated ir, running llvm's optimizer over it at
+/// '-O2' and up like 'opt' would
+fn prepare<'ctx>(context: &'ctx Context, ir: &str, opt_level: u32) -> Result<Module<'ctx>, String> {
+    let module = parse(context, ir)?;
+    module.verify().map_err(|e| format!("The generated ir is invalid:\n{}", e.to_string()))?;
+    if opt_level >= 2 {
+        let builder = PassManagerBuilder::create();
+        builder.set_optimization_level(level_of(opt_level));
+        let passes = PassManager::create(());
+        builder.populate_module_pass_manager(&passes);
+        passes.run_on(&module);
+    }
+    return Ok(module);
+}
+
+/// Checks generated ir with llvm's verifier, giving back what is wrong with it
+pub fn verify(ir: &str) -> Result<(), String> {
+    let context = Context::create();
+    let module = parse(&context, ir)?;
+    return module.verify().map_err(|e| format!("The generated ir is invalid:\n{}", e.to_string()));
+}
+
+/// Verifies and optimizes generated ir, and writes it as an object file for
+/// the target in its header
+pub fn write_object(ir: &str, obj_path: &Path, opt_level: u32) -> Result<(), String> {
+    let context = Context::create();
+    let module = prepare(&context, ir, opt_level)?;
+
+    Target::initialize_all(&InitializationConfig::default());
+    let triple = module.get_triple();
+    let target = Target::from_triple(&triple).map_err(|e| format!("llvm doesn't know the target '{}': {}", triple, e.to_string()))?;
+
+    // The same settings 'llc' is called with
+    let machine = target.create_target_machine(&triple, "generic", "", level_of(opt_level), RelocMode::PIC, CodeModel::Default)
+        .ok_or(format!("llvm can't compile for '{}'", triple))?;
+    return machine.write_to_file(&module, FileType::Object, obj_path).map_err(|e| format!("llvm couldn't write '{}': {}", obj_path.display(), e.to_string()));
+}
+
+/// Runs generated ir with llvm's jit after loading the given shared
+/// libraries, giving back the exit code of its main function
+/// The program shares the standard input and output of the compiler
+pub fn run(ir: &str, libraries: &[&str], opt_level: u32) -> Result<i32, String> {
+    let context = Context::create();
+    let module = prepare(&context, ir, opt_level)?;
+    for library in libraries {
+        // Returns true when it fails
+        if load_library_permanently(library) {
+            return Err(format!("Couldn't load '{}'", library));
+        }
+    }
+    let engine = module.create_jit_execution_engine(level_of(opt_level)).map_err(|e| format!("llvm couldn't start the jit: {}", e.to_string()))?;
+    let main = engine.get_function_value("main").map_err(|e| format!("Couldn't find 'main': {:?}", e))?;
+    return Ok(unsafe {engine.run_function_as_main(main, &[])});
+}
+
+#[test]
+fn test_backend() {
+    let ir = crate::driver::Driver::new().compile("write(1 + 2);\n".to_string()).unwrap();
+    let obj = std::env::temp_dir().join(format!("gizmo-llvm-test-{}.o", std::process::id()));
+    write_object(ir.as_str(), &obj, 2).unwrap();
+    assert!(std::fs::metadata(&obj).unwrap().len() > 0);
+    std::fs::remove_file(&obj).unwrap();
+
+    let ir = crate::driver::Driver::new().compile("func main(): int {\n    ret 3;\n}\n".to_string()).unwrap();
+    assert_eq!(run(ir.as_str(), &[], 0), Ok(3));
+
+    assert!(verify("define i32 @main() {\nentry:\n\tret i64 0\n}\n").is_err());
+}
@@ -1,14 +1,22 @@
 pub mod token;
 pub mod error;
+pub mod explain;
+pub mod sarif;
+pub mod source;
+
+use std::rc::Rc;
 
 use self::token::Token;
 use self::error::ErrorType;
 use self::error::error;
+use self::error::Error;
+use self::error::WarningGroup;
 use self::token::TokenType;
+use self::source::SourceMap;
 
 /// Stores information for a "Lexer"
 pub struct Lexer {
-    /// Current position in `code`
+    /// Current position in `code`, in bytes
     pub pos: usize,
 
     /// Input string
@@ -16,17 +24,31 @@ pub struct Lexer {
 
     /// Current column in the code
     pub col: usize,
+
+    /// Doc comments ('///') and the index of the token they come before
+    pub docs: Vec<(usize, String)>,
+
+    /// Name of the file the code comes from
+    pub file: String,
+
+    /// Lines whose warnings are turned off by a '// gizmo: allow(...)'
+    /// comment, and the groups that are turned off
+    pub allows: Vec<(usize, WarningGroup)>,
+
+    /// Text of the file shared by the tokens and whoever else needs its
+    /// lines, made the first time it is asked for
+    pub source: Option<Rc<SourceMap>>,
 }
 
 impl Lexer {
     /// Returns whether or not "c" is a valid identifier start
     fn is_identifier_start(&self, c: char) -> bool {
-        (c >= 'A' && c <= 'Z') || (c >= 'a' && c <= 'z') || c == '_'
+        c.is_ascii_uppercase() || c.is_ascii_lowercase() || c == '_'
     }
 
     /// Returns whether or not "c" is a valid digit
     fn is_digit(&self, c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
     }
 
     /// Returns whether or not "c" is a valid continuing identifier character
@@ -34,19 +56,27 @@ impl Lexer {
         self.is_identifier_start(c) || self.is_digit(c)
     }
 
-    /// Advances current place in code by incrementing position and column
+    /// Gives the source map of the code, making it the first time
+    pub fn source(&mut self) -> Rc<SourceMap> {
+        if self.source.is_none() {
+            self.source = Some(Rc::new(SourceMap::new(self.file.as_str(), self.code.as_str())));
+        }
+        return self.source.clone().unwrap();
+    }
+
+    /// Advances current place in code by 'sight' characters, moving the
+    /// position past each one's bytes and the column by one
     fn advance(&mut self, sight: usize) {
-        self.pos += sight;
-        self.col += sight;
+        for _ in 0..sight {
+            self.pos += self.peek(0).len_utf8();
+            self.col += 1;
+        }
     }
 
     /// Peeks in code for an upcoming character
     fn peek(&self, sight: usize) -> char {
         // If the character is past the end of the input, return ' '
-        if self.pos + sight >= self.code.len() {
-            return ' ';
-        }
-        return self.code.chars().nth(self.pos + sight).unwrap();
+        return self.code.get(self.pos..).and_then(|rest| rest.chars().nth(sight)).unwrap_or(' ');
     }
 
     /// Parses a character
@@ -67,23 +97,49 @@ impl Lexer {
         }
     }
 
+    /// Reads a '// gizmo: allow(unused, shadow)' comment, which turns the
+    /// groups of warnings off for the line it ends, or for the next line
+    /// when it is on a line of its own
+    fn allow_pragma(&mut self, comment: &str, lineno: usize, tokens: &[Token]) {
+        let groups = match comment.trim().strip_prefix("gizmo:") {
+            Some(rest) => match rest.trim().strip_prefix("allow(").and_then(|r| r.strip_suffix(')')) {
+                Some(groups) => groups,
+                None => return
+            },
+            None => return
+        };
+        let line = match tokens.last() {
+            Some(token) if token.lineno == lineno => lineno,
+            _ => lineno + 1
+        };
+        for name in groups.split(',') {
+            let name = name.trim();
+            match WarningGroup::from_name(name) {
+                Some(group) => self.allows.push((line, group)),
+                None if name == "all" => {
+                    for group in WarningGroup::ALL {
+                        self.allows.push((line, *group));
+                    }
+                },
+                None => {}
+            }
+        }
+    }
+
     /// Loops through the input and collects the tokens
-    pub fn lex(&mut self) -> Vec<Token> {
+    /// Gives back the first error instead if there is one
+    pub fn lex(&mut self) -> Result<Vec<Token>, Vec<Error>> {
         // Initialize a new vector to store the tokens
         let mut tokens: Vec<Token> = Vec::new();
 
         // Stores the current line number
         let mut lineno: usize = 1;
 
-        // Clones the code so that splitting it into different lines doesn't borrow "self.code"
-        let cloned = self.code.clone();
-        let lines: Vec<&str> = cloned.split('\n').collect();
+        // Every token shares the text of the file, for printing errors
+        let source = self.source();
 
         // Loop while our position is not at the end of the input
         while self.pos < self.code.len() {
-            // Stores the current line
-            let line: &str = lines[lineno - 1];
-
             // Stores the current character
             let mut c: char = self.peek(0);
 
@@ -99,9 +155,31 @@ impl Lexer {
 
             // Match the character and get the token's type and value
             let (value, typ): (&str, TokenType) = match c {
+                '/' if self.peek(1) == '/' && self.peek(2) == '/' && self.peek(3) != '/' => {
+                    // Skip over the '///'
+                    self.advance(3);
+
+                    // Collect the rest of the line as documentation for the
+                    // next token
+                    let mut doc = String::new();
+                    while self.pos < self.code.len() && self.peek(0) != '\n' {
+                        doc.push(self.peek(0));
+                        self.advance(1);
+                    }
+                    let doc = doc.strip_prefix(' ').unwrap_or(doc.as_str()).to_string();
+                    match self.docs.last_mut() {
+                        Some((index, text)) if *index == tokens.len() => {
+                            text.push('\n');
+                            text.push_str(doc.as_str());
+                        },
+                        _ => self.docs.push((tokens.len(), doc))
+                    }
+                    continue;
+                },
                 '/' if self.peek(1) == '/' => {
                     // Skip over the '//'
                     self.advance(2);
+                    let start = self.pos;
 
                     // Contine advancing until a newline is found or the end
                     // of the input is reached
@@ -112,6 +190,10 @@ impl Lexer {
                         c = self.peek(1);
                         self.advance(1);
                     }
+
+                    // The comment might turn warnings off
+                    let comment = self.code[start..self.pos.min(self.code.len())].to_string();
+                    self.allow_pragma(comment.as_str(), lineno, &tokens);
                     continue;
                 },
                 '!' if self.peek(1) == '=' => {self.advance(2); ("!=", TokenType::NotEqual)},
@@ -119,6 +201,7 @@ impl Lexer {
                 '-' => {self.advance(1); ("-", TokenType::Dash)},
                 '*' => {self.advance(1); ("*", TokenType::Star)},
                 '/' => {self.advance(1); ("/", TokenType::Slash)},
+                '%' => {self.advance(1); ("%", TokenType::Percent)},
                 '(' => {self.advance(1); ("(", TokenType::LeftParen)},
                 ')' => {self.advance(1); (")", TokenType::RightParen)},
                 '{' => {self.advance(1); ("{", TokenType::LeftBrace)},
@@ -163,11 +246,11 @@ impl Lexer {
 
                     // If the second single quote wasn't found, print an error
                     if self.peek(0) != '\'' {
-                        let empty_token = Token {typ: TokenType::Error, value: " ".to_string(), lineno: lineno, col: self.col, line: lines[lineno - 1].to_string()};
-                        error(ErrorType::ExpectedToken, &empty_token)
-                            .note("Expected a single quote")
-                            .help("Insert a single quote after this character")
-                            .emit();
+                        let empty_token = Token {typ: TokenType::Error, value: " ".to_string(), lineno: lineno, col: self.col, len: 1, source: source.clone()};
+                        let mut err = error(ErrorType::ExpectedToken, &empty_token);
+                        err.note("Expected a single quote")
+                            .help("Insert a single quote after this character");
+                        return Err(vec![err]);
                     }
 
                     // Skip over the second '
@@ -181,18 +264,17 @@ impl Lexer {
                     // Skip over the "
                     self.advance(1);
 
-                    let mut len = 0;
-
                     // Loop until the end of the string
                     while c != '"' {
-                        // If it reaches the end of the line without finding
-                        // a second '"', give error
-                        if c == '\n' || c == '\0' {
-                            let empty_token = Token {typ: TokenType::Error, value: " ".to_string(), lineno: lineno, col: self.col, line: lines[lineno - 1].to_string()};
-                            error(ErrorType::UnexpectedEOF, &empty_token)
-                                .note("Closing double quote was not found")
-                                .help("Add a closing double quote to signal the end of the string")
-                                .emit();
+                        // If it reaches the end of the line or the input
+                        // without finding a second '"', give error
+                        // Past the end, 'peek' gives ' ', so check the position
+                        if c == '\n' || c == '\0' || self.pos >= self.code.len() {
+                            let empty_token = Token {typ: TokenType::Error, value: " ".to_string(), lineno: lineno, col: self.col, len: 1, source: source.clone()};
+                            let mut err = error(ErrorType::UnexpectedEOF, &empty_token);
+                            err.note("Closing double quote was not found")
+                                .help("Add a closing double quote to signal the end of the string");
+                            return Err(vec![err]);
                         }
 
                         // Add the character to allocated "string" variable
@@ -203,13 +285,13 @@ impl Lexer {
 
                         // Advance our position and column
                         self.advance(1);
-                        len += 1;
                     }
 
                     // Skip the second "
                     self.advance(1);
 
-                    string = format!("{}.{}", len, string);
+                    // The value is the contents with the escapes already
+                    // turned into the characters they stand for
                     (string.as_str(), TokenType::Str)
                 },
                 id if self.is_identifier_start(id) => {
@@ -228,6 +310,7 @@ impl Lexer {
                     // Match the identifier against all the keywords to find the appropriate token type
                     let id_type: TokenType = match name.as_str() {
                         "let"            => TokenType::Let,
+                        "var"            => TokenType::Var,
                         "use"            => TokenType::Use,
                         "if"             => TokenType::If,
                         "else"           => TokenType::Else,
@@ -235,14 +318,16 @@ impl Lexer {
                         "break"          => TokenType::Break,
                         "continue"       => TokenType::Continue,
                         "func"           => TokenType::Func,
+                        "ref"            => TokenType::Ref,
                         "while"          => TokenType::While,
                         "new"            => TokenType::New,
                         "struct"         => TokenType::Struct,
+                        "as"             => TokenType::As,
                         "not"            => TokenType::Not,
                         "and"            => TokenType::And,
                         "or"             => TokenType::Or,
                         "true" | "false" => TokenType::Bool,
-                        "int" | "string" | "char" | "bool" | "dec" => TokenType::Type,
+                        "int" | "long" | "uint" | "string" | "char" | "bool" | "dec" => TokenType::Type,
                         _                => TokenType::Id,
                     };
                     (name.as_str(), id_type)
@@ -275,11 +360,11 @@ impl Lexer {
 
                         // If a digit is not found after the dot, print an error
                         if !self.is_digit(c) {
-                            let empty_token = Token {typ: TokenType::Error, value: "".to_string(), lineno: lineno, col: 0, line: lines[lineno - 1].to_string()};
-                            error(ErrorType::DecNotFound, &empty_token)
-                                .note("Expected number after dot")
-                                .help("Take away the dot or insert a number after the dot")
-                                .emit();
+                            let empty_token = Token {typ: TokenType::Error, value: "".to_string(), lineno: lineno, col: 0, len: 0, source: source.clone()};
+                            let mut err = error(ErrorType::DecNotFound, &empty_token);
+                            err.note("Expected number after dot")
+                                .help("Take away the dot or insert a number after the dot");
+                            return Err(vec![err]);
                         }
 
                         // Otherwise, continue to collect digits and add to the
@@ -295,82 +380,155 @@ impl Lexer {
                         if c != '.' {
                             typ = TokenType::Dec;
                         } else {
-                            let empty_token = Token {typ: TokenType::Error, value: "".to_string(), lineno: lineno, col: 0, line: lines[lineno - 1].to_string()};
-                            error(ErrorType::DecTooManyDots, &empty_token)
-                                .note("Unexpected dot")
-                                .help("Take away this dot")
-                                .emit();
+                            let empty_token = Token {typ: TokenType::Error, value: "".to_string(), lineno: lineno, col: 0, len: 0, source: source.clone()};
+                            let mut err = error(ErrorType::DecTooManyDots, &empty_token);
+                            err.note("Unexpected dot")
+                                .help("Take away this dot");
+                            return Err(vec![err]);
                         };
                     }
-                    
+
+                    // Make sure the number fits in its type, whole numbers too
+                    // big for an int are longs, which are 64 bits, and decs
+                    // are doubles
+                    let number_token = Token {typ: TokenType::Error, value: digit.clone(), lineno: lineno, col: begin, len: self.col - begin, source: source.clone()};
+                    if typ == TokenType::Int && digit.parse::<i64>().is_err() {
+                        let mut err = error(ErrorType::NumberOutOfRange, &number_token);
+                        err.note(format!("A 'long' can't be larger than {}", i64::MAX).as_str())
+                            .help("Use a 'dec' if a larger number is needed");
+                        return Err(vec![err]);
+                    } else if typ == TokenType::Dec {
+                        let value = digit.parse::<f64>().unwrap_or(f64::INFINITY);
+                        if value.is_infinite() {
+                            let mut err = error(ErrorType::NumberOutOfRange, &number_token);
+                            err.note(format!("A 'dec' can't be larger than {:e}", f64::MAX).as_str());
+                            return Err(vec![err]);
+                        } else if value == 0.0 && digit.chars().any(|d| d != '0' && d != '.') {
+                            let mut err = error(ErrorType::NumberOutOfRange, &number_token);
+                            err.note("This number is too small for a 'dec', and would become 0");
+                            return Err(vec![err]);
+                        }
+                    }
+
                     // Return the number token
                     (digit.as_str(), typ)
                 },
                 _ => {
                     // If there is an unknown character, print an error
-                    let empty_token = Token {typ: TokenType::Error, value: c.to_string(), lineno: lineno, col: begin, line: line.to_string()};
-                    error(ErrorType::UnknownChar, &empty_token)
-                        .note(format!("Unknown character '{}'", c).as_str())
-                        .help("")
-                        .emit();
-                    continue;
+                    let empty_token = Token {typ: TokenType::Error, value: c.to_string(), lineno: lineno, col: begin, len: 1, source: source.clone()};
+                    let mut err = error(ErrorType::UnknownChar, &empty_token);
+                    err.note(format!("Unknown character '{}'", c).as_str())
+                        .help("");
+                    return Err(vec![err]);
                 },
             };
 
             // Add the token to the tokens vector
-            tokens.push(Token {typ: typ, value : value.to_string(), lineno: lineno, col: begin, line: line.to_string()});
+            tokens.push(Token {typ: typ, value : value.to_string(), lineno: lineno, col: begin, len: self.col - begin, source: source.clone()});
         }
 
         // Returns the tokens vector
-        return tokens;
+        return Ok(tokens);
     }
 }
 
 #[test]
 fn test_operators() {
-    let mut lexer = Lexer {code: "+ - * / == != < > <= >=".to_string(), col: 0, pos: 0};
-    assert_eq!(lexer.lex(), vec![
-        Token {typ: TokenType::Plus, value: "+".to_string(), lineno: 1, col: 0, line: lexer.code.clone()},
-        Token {typ: TokenType::Dash, value: "-".to_string(), lineno: 1, col: 2, line: lexer.code.clone()},
-        Token {typ: TokenType::Star, value: "*".to_string(), lineno: 1, col: 4, line: lexer.code.clone()},
-        Token {typ: TokenType::Slash, value: "/".to_string(), lineno: 1, col: 6, line: lexer.code.clone()},
-        Token {typ: TokenType::EqualEqual, value: "==".to_string(), lineno: 1, col: 8, line: lexer.code.clone()},
-        Token {typ: TokenType::NotEqual, value: "!=".to_string(), lineno: 1, col: 11, line: lexer.code.clone()},
-        Token {typ: TokenType::LessThan, value: "<".to_string(), lineno: 1, col: 14, line: lexer.code.clone()},
-        Token {typ: TokenType::GreaterThan, value: ">".to_string(), lineno: 1, col: 16, line: lexer.code.clone()},
-        Token {typ: TokenType::LessEqual, value: "<=".to_string(), lineno: 1, col: 18, line: lexer.code.clone()},
-        Token {typ: TokenType::GreaterEqual, value: ">=".to_string(), lineno: 1, col: 21, line: lexer.code.clone()},
+    let mut lexer = Lexer {code: "+ - * / == != < > <= >=".to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None};
+    let source = Rc::new(SourceMap::new("test.gizmo", lexer.code.as_str()));
+    assert_eq!(lexer.lex().unwrap(), vec![
+        Token {typ: TokenType::Plus, value: "+".to_string(), lineno: 1, col: 0, len: 1, source: source.clone()},
+        Token {typ: TokenType::Dash, value: "-".to_string(), lineno: 1, col: 2, len: 1, source: source.clone()},
+        Token {typ: TokenType::Star, value: "*".to_string(), lineno: 1, col: 4, len: 1, source: source.clone()},
+        Token {typ: TokenType::Slash, value: "/".to_string(), lineno: 1, col: 6, len: 1, source: source.clone()},
+        Token {typ: TokenType::EqualEqual, value: "==".to_string(), lineno: 1, col: 8, len: 2, source: source.clone()},
+        Token {typ: TokenType::NotEqual, value: "!=".to_string(), lineno: 1, col: 11, len: 2, source: source.clone()},
+        Token {typ: TokenType::LessThan, value: "<".to_string(), lineno: 1, col: 14, len: 1, source: source.clone()},
+        Token {typ: TokenType::GreaterThan, value: ">".to_string(), lineno: 1, col: 16, len: 1, source: source.clone()},
+        Token {typ: TokenType::LessEqual, value: "<=".to_string(), lineno: 1, col: 18, len: 2, source: source.clone()},
+        Token {typ: TokenType::GreaterEqual, value: ">=".to_string(), lineno: 1, col: 21, len: 2, source: source.clone()},
     ]);
 }
 
 #[test]
 fn test_identifiers_keywords_types() {
-    let mut lexer = Lexer {code: "abc int dec bool string let while struct new and or not".to_string(), col: 0, pos: 0};
-    assert_eq!(lexer.lex(), vec![
-		Token {typ: TokenType::Id, value: "abc".to_string(), lineno: 1, col: 0, line: lexer.code.clone()},
-		Token {typ: TokenType::Type, value: "int".to_string(), lineno: 1, col: 4, line: lexer.code.clone()},
-		Token {typ: TokenType::Type, value: "dec".to_string(), lineno: 1, col: 8, line: lexer.code.clone()},
-		Token {typ: TokenType::Type, value: "bool".to_string(), lineno: 1, col: 12, line: lexer.code.clone()},
-		Token {typ: TokenType::Type, value: "string".to_string(), lineno: 1, col: 17, line: lexer.code.clone()},
-		Token {typ: TokenType::Let, value: "let".to_string(), lineno: 1, col: 24, line: lexer.code.clone()},
-		Token {typ: TokenType::While, value: "while".to_string(), lineno: 1, col: 28, line: lexer.code.clone()},
-		Token {typ: TokenType::Struct, value: "struct".to_string(), lineno: 1, col: 34, line: lexer.code.clone()},
-		Token {typ: TokenType::New, value: "new".to_string(), lineno: 1, col: 41, line: lexer.code.clone()},
-		Token {typ: TokenType::And, value: "and".to_string(), lineno: 1, col: 45, line: lexer.code.clone()},
-		Token {typ: TokenType::Or, value: "or".to_string(), lineno: 1, col: 49, line: lexer.code.clone()},
-		Token {typ: TokenType::Not, value: "not".to_string(), lineno: 1, col: 52, line: lexer.code.clone()}
+    let mut lexer = Lexer {code: "abc int dec bool string let while struct new and or not".to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None};
+    let source = Rc::new(SourceMap::new("test.gizmo", lexer.code.as_str()));
+    assert_eq!(lexer.lex().unwrap(), vec![
+		Token {typ: TokenType::Id, value: "abc".to_string(), lineno: 1, col: 0, len: 3, source: source.clone()},
+		Token {typ: TokenType::Type, value: "int".to_string(), lineno: 1, col: 4, len: 3, source: source.clone()},
+		Token {typ: TokenType::Type, value: "dec".to_string(), lineno: 1, col: 8, len: 3, source: source.clone()},
+		Token {typ: TokenType::Type, value: "bool".to_string(), lineno: 1, col: 12, len: 4, source: source.clone()},
+		Token {typ: TokenType::Type, value: "string".to_string(), lineno: 1, col: 17, len: 6, source: source.clone()},
+		Token {typ: TokenType::Let, value: "let".to_string(), lineno: 1, col: 24, len: 3, source: source.clone()},
+		Token {typ: TokenType::While, value: "while".to_string(), lineno: 1, col: 28, len: 5, source: source.clone()},
+		Token {typ: TokenType::Struct, value: "struct".to_string(), lineno: 1, col: 34, len: 6, source: source.clone()},
+		Token {typ: TokenType::New, value: "new".to_string(), lineno: 1, col: 41, len: 3, source: source.clone()},
+		Token {typ: TokenType::And, value: "and".to_string(), lineno: 1, col: 45, len: 3, source: source.clone()},
+		Token {typ: TokenType::Or, value: "or".to_string(), lineno: 1, col: 49, len: 2, source: source.clone()},
+		Token {typ: TokenType::Not, value: "not".to_string(), lineno: 1, col: 52, len: 3, source: source.clone()}
     ]);
 }
 
 #[test]
 fn test_const_values() {
-    let mut lexer = Lexer {code: "5 'a' 5.5 true false \"abc\"".to_string(), col: 0, pos: 0};
-    assert_eq!(lexer.lex(), vec![
-		Token {typ: TokenType::Int, value: "5".to_string(), lineno: 1, col: 0, line: lexer.code.clone()},
-		Token {typ: TokenType::Char, value: "a".to_string(), lineno: 1, col: 2, line: lexer.code.clone()},
-		Token {typ: TokenType::Dec, value: "5.5".to_string(), lineno: 1, col: 6, line: lexer.code.clone()},
-		Token {typ: TokenType::Bool, value: "true".to_string(), lineno: 1, col: 10, line: lexer.code.clone()},
-		Token {typ: TokenType::Bool, value: "false".to_string(), lineno: 1, col: 15, line: lexer.code.clone()},
-		Token {typ: TokenType::Str, value: "3.abc".to_string(), lineno: 1, col: 21, line: lexer.code.clone()},
+    let mut lexer = Lexer {code: "5 'a' 5.5 true false \"abc\"".to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None};
+    let source = Rc::new(SourceMap::new("test.gizmo", lexer.code.as_str()));
+    assert_eq!(lexer.lex().unwrap(), vec![
+		Token {typ: TokenType::Int, value: "5".to_string(), lineno: 1, col: 0, len: 1, source: source.clone()},
+		Token {typ: TokenType::Char, value: "a".to_string(), lineno: 1, col: 2, len: 3, source: source.clone()},
+		Token {typ: TokenType::Dec, value: "5.5".to_string(), lineno: 1, col: 6, len: 3, source: source.clone()},
+		Token {typ: TokenType::Bool, value: "true".to_string(), lineno: 1, col: 10, len: 4, source: source.clone()},
+		Token {typ: TokenType::Bool, value: "false".to_string(), lineno: 1, col: 15, len: 5, source: source.clone()},
+		Token {typ: TokenType::Str, value: "abc".to_string(), lineno: 1, col: 21, len: 5, source: source.clone()},
+    ]);
+}
+
+#[test]
+fn test_doc_comments() {
+    let mut lexer = Lexer {code: "/// Adds\n/// two numbers\nfunc // not a doc\n//// not either\nstruct".to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None};
+    let tokens = lexer.lex().unwrap();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(lexer.docs, vec![(0, "Adds\ntwo numbers".to_string())]);
+}
+
+#[test]
+fn test_allow_pragma() {
+    let mut lexer = Lexer {code: "// gizmo: allow(unused)\nfunc // gizmo: allow(shadow, nope)\n// gizmo: deny(unused)\nstruct".to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None};
+    lexer.lex().unwrap();
+    assert_eq!(lexer.allows, vec![(2, WarningGroup::Unused), (2, WarningGroup::Shadow)]);
+}
+
+#[test]
+fn test_number_out_of_range() {
+    let lex = |code: &str| Lexer {code: code.to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None}.lex();
+    assert_eq!(lex("99999999999999999999").unwrap_err()[0].code(), "E0019");
+    assert_eq!(lex(format!("1{}.0", "0".repeat(400)).as_str()).unwrap_err()[0].code(), "E0019");
+
+    // Too big for an int is still a long
+    assert!(lex("3000000000").is_ok());
+}
+
+#[test]
+fn test_unterminated_string() {
+    let lex = |code: &str| Lexer {code: code.to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None}.lex();
+    assert_eq!(lex("write(\"abc\n").unwrap_err()[0].code(), "E0001");
+
+    // The input can end inside the string too
+    assert_eq!(lex("write(\"abc").unwrap_err()[0].code(), "E0001");
+}
+
+#[test]
+fn test_non_ascii() {
+    // Columns count characters, not bytes
+    let mut lexer = Lexer {code: "// café\nwrite(\"héllo\"); // gizmo: allow(unused)\n".to_string(), col: 0, pos: 0, docs: Vec::new(), file: "test.gizmo".to_string(), allows: Vec::new(), source: None};
+    let source = Rc::new(SourceMap::new("test.gizmo", lexer.code.as_str()));
+    assert_eq!(lexer.lex().unwrap(), vec![
+        Token {typ: TokenType::Id, value: "write".to_string(), lineno: 2, col: 0, len: 5, source: source.clone()},
+        Token {typ: TokenType::LeftParen, value: "(".to_string(), lineno: 2, col: 5, len: 1, source: source.clone()},
+        Token {typ: TokenType::Str, value: "héllo".to_string(), lineno: 2, col: 6, len: 7, source: source.clone()},
+        Token {typ: TokenType::RightParen, value: ")".to_string(), lineno: 2, col: 13, len: 1, source: source.clone()},
+        Token {typ: TokenType::SemiColon, value: ";".to_string(), lineno: 2, col: 14, len: 1, source: source.clone()},
     ]);
+    assert_eq!(lexer.allows, vec![(2, WarningGroup::Unused)]);
 }
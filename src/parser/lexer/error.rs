@@ -1,34 +1,326 @@
 use colored::Colorize;
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+use std::process::Command;
+use std::process::Stdio;
+use std::fs::File;
 
 use super::token::Token;
+use super::token::Span;
+
+/// Most errors printed when '--error-limit' isn't given
+pub const DEFAULT_ERROR_LIMIT: usize = 20;
+
+/// Groups of warnings that are turned on and off together with '-W' flags
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WarningGroup {
+    /// Functions and values that are never used
+    Unused,
+
+    /// Variables that hide ones from outer scopes
+    Shadow,
+
+    /// Ints that are made into chars without an 'as'
+    Narrowing,
+}
+
+/// Implement functions for a warning group
+impl WarningGroup {
+    /// Every group, turned on by '-Wall'
+    pub const ALL: &'static [WarningGroup] = &[WarningGroup::Unused, WarningGroup::Shadow, WarningGroup::Narrowing];
+
+    /// The groups that are on without any flags
+    pub const DEFAULT: &'static [WarningGroup] = &[WarningGroup::Unused, WarningGroup::Narrowing];
+
+    /// The name of the group, used in '-W<name>' and '-Wno-<name>'
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarningGroup::Unused => "unused",
+            WarningGroup::Shadow => "shadow",
+            WarningGroup::Narrowing => "narrowing",
+        }
+    }
+
+    /// Finds the group with the given name
+    pub fn from_name(name: &str) -> Option<WarningGroup> {
+        return WarningGroup::ALL.iter().find(|g| g.name() == name).copied();
+    }
+}
+
+/// The ways errors can be printed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MessageFormat {
+    /// A colored snippet of the code with the notes under it
+    Human,
+
+    /// One JSON object per line, for editors and other tools
+    Json,
+}
+
+/// Turns the colors of printed errors on or off
+pub fn set_color(color: bool) {
+    colored::control::set_override(color);
+}
+
+/// Whether errors should be colored when no flag says otherwise
+/// Colors are left out when 'NO_COLOR' is set or stderr isn't a terminal, so
+/// that logs and files get plain text
+pub fn color_from_env() -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    return !no_color && io::stderr().is_terminal();
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    /// Stops compilation
+    Error,
+
+    /// Only printed, compilation carries on
+    Warning,
+}
+
+/// Errors and warnings are both diagnostics, which only differ in severity
+pub type Diagnostic = Error;
+
+/// line number, column, line, value
+type Location = (usize, usize, String, String);
 
 /// Stores information for an error
+#[derive(Debug, Clone)]
 pub struct Error {
     /// Type of the error
     typ: ErrorType,
 
     /// Location of the error
     /// line number, column, line, value
-    location: (usize, usize, String, String),
+    /// Boxed so that results carrying an error stay small
+    location: Box<Location>,
+
+    /// File that the error is in
+    file: String,
 
     /// Side notes and suggestions
-    helpers: String
+    helpers: String,
+
+    /// Other locations that have to do with the error
+    labels: Vec<Label>,
+
+    /// Edits that fix the error, which tools can apply on their own
+    fixes: Vec<Fix>,
+
+    /// Whether this is an error or only a warning
+    severity: Severity
+}
+
+/// Another location that has to do with an error, with a note about it
+#[derive(Debug, Clone)]
+struct Label {
+    /// Where the label points
+    location: Location,
+
+    /// What the location has to do with the error
+    text: String
+}
+
+/// An edit to the file that fixes an error
+#[derive(Debug, Clone)]
+struct Fix {
+    /// Line number and column where the edit starts
+    start: (usize, usize),
+
+    /// Number of characters that are replaced, 0 to only insert
+    length: usize,
+
+    /// Text put in place of the replaced characters
+    replacement: String,
+
+    /// What the edit does
+    message: String
 }
 
 /// Implement functions for an error
 impl Error {
-    /// Emits an error
-    pub fn emit(&mut self) {
-        eprintln!("{}: {}", "Error".bright_red(), message_for(self.typ.clone()).bright_white());
-        eprintln!("  {} {}", "-->".bright_blue(), format!("In abc.gizmo:{}:{}", self.location.0, self.location.1).bright_white());
-        eprintln!("{} {}", " ".repeat(self.location.0.to_string().len()), "|".bright_blue());
-        eprintln!("{} {} {}", self.location.0.to_string().bright_blue(), "|".bright_blue(), self.location.2);
-        eprint!("{} {} ", " ".repeat(self.location.0.to_string().len()), "|".bright_blue());
-        eprintln!("{}{}", " ".repeat(self.location.1), "^".repeat(self.location.3.len()));
+    /// How serious the diagnostic is
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Whether this is only a warning
+    pub fn is_warning(&self) -> bool {
+        self.severity == Severity::Warning
+    }
+
+    /// The group a warning can be turned off with
+    pub fn group(&self) -> Option<WarningGroup> {
+        self.typ.group()
+    }
+
+    /// The line number the error is on
+    pub fn line(&self) -> usize {
+        self.location.0
+    }
+
+    /// Turns a warning into an error, because its group is denied by 'flag'
+    pub fn deny(&mut self, flag: &str) {
+        self.severity = Severity::Error;
+        self.note(format!("This warning is an error because of '{}'", flag).as_str());
+    }
+
+    /// Formats the error as a colored snippet of the code with the notes
+    /// under it
+    /// Lines longer than 'columns' are cut down to the part around the error
+    pub fn to_human(&self, columns: Option<usize>) -> String {
+        let mut text = String::new();
+        let label = if self.is_warning() {"Warning".bright_yellow()} else {"Error".bright_red()};
+        text.push_str(format!("{}{}: {}\n", label, format!("[{}]", self.typ.code()).bright_white(), message_for(self.typ.clone()).bright_white()).as_str());
+        text.push_str(format!("  {} {}\n", "-->".bright_blue(), format!("In {}:{}:{}", self.file, self.location.0, self.location.1).bright_white()).as_str());
+
+        // The error itself is marked with '^' and the labels with '-', with
+        // each line shown once in the order of the file
+        let mut spans: Vec<(&Location, &str, &str)> = vec![(self.location.as_ref(), "^", "")];
+        for l in self.labels.iter() {
+            spans.push((&l.location, "-", l.text.as_str()));
+        }
+        let mut lines: Vec<usize> = spans.iter().map(|s| s.0.0).collect();
+        lines.sort();
+        lines.dedup();
+        let width = lines.iter().map(|l| l.to_string().len()).max().unwrap_or(1);
+        let columns = columns.map(|c| c.saturating_sub(width + 3));
+        text.push_str(format!("{} {}\n", " ".repeat(width), "|".bright_blue()).as_str());
+        for i in 0..lines.len() {
+            if i > 0 && lines[i - 1] + 1 < lines[i] {
+                text.push_str(format!("{}\n", "...".bright_blue()).as_str());
+            }
+            let on_line: Vec<&(&Location, &str, &str)> = spans.iter().filter(|s| s.0.0 == lines[i]).collect();
+
+            // Long lines are cut down to the part around the spans
+            let line = &on_line[0].0.2;
+            let from = on_line.iter().map(|s| s.0.1).min().unwrap();
+            let to = on_line.iter().map(|s| s.0.1 + s.0.3.len()).max().unwrap();
+            let (line, start, prefix) = match columns {
+                Some(columns) => window(line, from, to, columns),
+                None => (line.clone(), 0, 0)
+            };
+            let num = lines[i].to_string();
+            text.push_str(format!("{}{} {} {}\n", " ".repeat(width - num.len()), num.bright_blue(), "|".bright_blue(), line).as_str());
+            for (location, mark, label) in on_line {
+                let col = location.1.saturating_sub(start) + prefix;
+                let len = std::cmp::min(location.3.len(), line.chars().count().saturating_sub(col));
+                text.push_str(format!("{} {} ", " ".repeat(width), "|".bright_blue()).as_str());
+                text.push_str(format!("{}{}", " ".repeat(col), mark.repeat(len)).as_str());
+                if label.is_empty() {
+                    text.push('\n');
+                } else {
+                    text.push_str(format!(" {}\n", label.bright_white()).as_str());
+                }
+            }
+        }
         for h in self.helpers.split('\n') {
-            eprintln!("{}{}", " ".repeat((self.location.0 as i32).to_string().len() + 3), h.bright_white());
+            text.push_str(format!("{}{}\n", " ".repeat(width + 3), h.bright_white()).as_str());
         }
-        std::process::exit(1);
+        return text;
+    }
+
+    /// Formats the error as a single line JSON object
+    /// # Example
+    /// {"code": "E0008", "severity": "error", "message": "...", "file": "a.gizmo", "line": 1, "column": 8, "length": 1, "notes": [], "help": [], "labels": [], "fixes": []}
+    pub fn to_json(&self) -> String {
+        let helpers = |prefix: &str| {
+            let list: Vec<String> = self.helpers.lines().filter_map(|h| h.strip_prefix(prefix)).map(json_string).collect();
+            format!("[{}]", list.join(", "))
+        };
+        let labels: Vec<String> = self.labels.iter().map(|l| {
+            format!("{{\"line\": {}, \"column\": {}, \"length\": {}, \"text\": {}}}", l.location.0, l.location.1, l.location.3.len(), json_string(l.text.as_str()))
+        }).collect();
+        let fixes: Vec<String> = self.fixes.iter().map(|f| {
+            format!("{{\"message\": {}, \"line\": {}, \"column\": {}, \"length\": {}, \"replacement\": {}}}", json_string(f.message.as_str()), f.start.0, f.start.1, f.length, json_string(f.replacement.as_str()))
+        }).collect();
+        return format!(
+            "{{\"code\": {}, \"severity\": {}, \"message\": {}, \"file\": {}, \"line\": {}, \"column\": {}, \"length\": {}, \"notes\": {}, \"help\": {}, \"labels\": [{}], \"fixes\": [{}]}}",
+            json_string(self.typ.code()),
+            json_string(if self.is_warning() {"warning"} else {"error"}),
+            json_string(message_for(self.typ.clone()).as_str()),
+            json_string(self.file.as_str()),
+            self.location.0,
+            self.location.1,
+            self.location.3.len(),
+            helpers("note: "),
+            helpers("help: "),
+            labels.join(", "),
+            fixes.join(", ")
+        );
+    }
+
+    /// Formats the error as a SARIF result object
+    pub fn to_sarif_result(&self) -> String {
+        let mut text = message_for(self.typ.clone());
+        for h in self.helpers.lines() {
+            text.push('\n');
+            text.push_str(h);
+        }
+        let physical = |location: &Location| {
+            format!(
+                "{{\"artifactLocation\": {{\"uri\": {}}}, \"region\": {{\"startLine\": {}, \"startColumn\": {}, \"endColumn\": {}}}}}",
+                json_string(self.file.as_str()),
+                location.0,
+                location.1 + 1,
+                location.1 + 1 + location.3.len()
+            )
+        };
+        let mut result = format!(
+            "{{\"ruleId\": {}, \"level\": {}, \"message\": {{\"text\": {}}}, \"locations\": [{{\"physicalLocation\": {}}}]",
+            json_string(self.typ.code()),
+            json_string(if self.is_warning() {"warning"} else {"error"}),
+            json_string(text.as_str()),
+            physical(&self.location)
+        );
+        if !self.labels.is_empty() {
+            let related: Vec<String> = self.labels.iter().enumerate().map(|(i, l)| {
+                format!("{{\"id\": {}, \"physicalLocation\": {}, \"message\": {{\"text\": {}}}}}", i, physical(&l.location), json_string(l.text.as_str()))
+            }).collect();
+            result.push_str(format!(", \"relatedLocations\": [{}]", related.join(", ")).as_str());
+        }
+        if !self.fixes.is_empty() {
+            let fixes: Vec<String> = self.fixes.iter().map(|f| {
+                format!(
+                    "{{\"description\": {{\"text\": {}}}, \"artifactChanges\": [{{\"artifactLocation\": {{\"uri\": {}}}, \"replacements\": [{{\"deletedRegion\": {{\"startLine\": {}, \"startColumn\": {}, \"endColumn\": {}}}, \"insertedContent\": {{\"text\": {}}}}}]}}]}}",
+                    json_string(f.message.as_str()),
+                    json_string(self.file.as_str()),
+                    f.start.0,
+                    f.start.1 + 1,
+                    f.start.1 + 1 + f.length,
+                    json_string(f.replacement.as_str())
+                )
+            }).collect();
+            result.push_str(format!(", \"fixes\": [{}]", fixes.join(", ")).as_str());
+        }
+        result.push('}');
+        return result;
+    }
+
+    /// The code of the error's type
+    pub fn code(&self) -> &'static str {
+        self.typ.code()
+    }
+
+    /// Points at another location that has to do with the error
+    pub fn label(&mut self, token: &Token, text: &str) -> &mut Self {
+        self.labels.push(Label {location: (token.lineno, token.col, token.line().to_string(), token.text()), text: text.to_string()});
+        self
+    }
+
+    /// Suggests replacing a token with other text
+    pub fn fix_replace(&mut self, token: &Token, replacement: &str, message: &str) -> &mut Self {
+        self.fixes.push(Fix {start: (token.lineno, token.col), length: token.len, replacement: replacement.to_string(), message: message.to_string()});
+        self
+    }
+
+    /// Suggests inserting text right after a token
+    pub fn fix_insert_after(&mut self, token: &Token, text: &str, message: &str) -> &mut Self {
+        self.fixes.push(Fix {start: (token.lineno, token.col + token.len), length: 0, replacement: text.to_string(), message: message.to_string()});
+        self
     }
 
     /// Adds a suggestion to the error
@@ -48,12 +340,159 @@ impl Error {
     }
 }
 
+/// Formats errors and warnings for printing
+/// Errors are counted, and the ones past the limit are left out
+pub struct Renderer {
+    /// How errors are formatted
+    pub format: MessageFormat,
+
+    /// Most errors that are rendered, or 0 to render all of them
+    pub limit: usize,
+
+    /// Number of errors seen so far, including ones past the limit
+    pub errors: usize,
+
+    /// Width of the terminal, if errors go to one
+    pub columns: Option<usize>
+}
+
+/// Implement functions for a renderer
+impl Renderer {
+    /// Constructs a renderer that hasn't seen any errors
+    pub fn new(format: MessageFormat, limit: usize) -> Renderer {
+        Renderer {format: format, limit: limit, errors: 0, columns: terminal_width()}
+    }
+
+    /// Formats an error or warning, or gives None for an error past the limit
+    pub fn render(&mut self, error: &Error) -> Option<String> {
+        if !error.is_warning() {
+            self.errors += 1;
+            if self.limit != 0 && self.errors > self.limit {
+                return None;
+            }
+        }
+        if self.format == MessageFormat::Json {
+            return Some(format!("{}\n", error.to_json()));
+        }
+        return Some(error.to_human(self.columns));
+    }
+
+    /// Formats how many errors stopped compilation, which is only shown to
+    /// humans
+    pub fn summary(&self) -> Option<String> {
+        if self.format != MessageFormat::Human {
+            return None;
+        }
+        return Some(format!("{}: {}\n", "Error".bright_red(), summary(self.errors, self.limit).bright_white()));
+    }
+}
+
+/// The last line printed when compiling stops because of errors
+fn summary(count: usize, limit: usize) -> String {
+    let mut line = if count == 1 {
+        "Aborting due to 1 previous error".to_string()
+    } else {
+        format!("Aborting due to {} previous errors", count)
+    };
+    if limit != 0 && count > limit {
+        line.push_str(format!(", only the first {} are shown", limit).as_str());
+    }
+    return line;
+}
+
+/// Finds how many columns the terminal has, if errors are printed to one
+/// 'COLUMNS' is used when it is set, otherwise the terminal is asked with
+/// 'stty'
+fn terminal_width() -> Option<usize> {
+    if let Some(columns) = env::var("COLUMNS").ok().and_then(|c| c.parse::<usize>().ok()) {
+        return Some(columns);
+    }
+    if !io::stderr().is_terminal() {
+        return None;
+    }
+    let tty = File::open("/dev/tty").ok()?;
+    let output = Command::new("stty").arg("size").stdin(Stdio::from(tty)).stderr(Stdio::null()).output().ok()?;
+    let size = String::from_utf8_lossy(&output.stdout).to_string();
+    return size.split_whitespace().nth(1).and_then(|c| c.parse::<usize>().ok());
+}
+
+/// Cuts a line down to at most 'columns' characters around the part from
+/// 'from' to 'to', marking what was cut with '...'
+/// Gives the shown text, the first column shown, and how many characters
+/// come before it
+fn window(line: &str, from: usize, to: usize, columns: usize) -> (String, usize, usize) {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.len() <= columns {
+        return (line.to_string(), 0, 0);
+    }
+
+    // Center the spans in what is left after the markers
+    let room = std::cmp::max(columns.saturating_sub(6), 10);
+    let wanted = std::cmp::min(to.saturating_sub(from), room);
+    let start = if to <= room {0} else {from.saturating_sub((room - wanted) / 2)};
+    let start = std::cmp::min(start, chars.len().saturating_sub(room));
+    let end = std::cmp::min(chars.len(), start + room);
+
+    let mut text = String::new();
+    let prefix = if start > 0 {"..."} else {""};
+    text.push_str(prefix);
+    text.extend(chars[start..end].iter());
+    if end < chars.len() {
+        text.push_str("...");
+    }
+    return (text, start, prefix.len());
+}
+
+/// Quotes a string for JSON, escaping the characters that need it
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"'  => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+
 /// Creates an error
 pub fn error(t: ErrorType, token: &Token) -> Error {
     return Error {
         typ: t,
-        location: (token.lineno, token.col, token.line.clone(), token.value.clone()),
-        helpers: String::new()
+        location: Box::new((token.lineno, token.col, token.line().to_string(), token.text())),
+        file: token.file().to_string(),
+        helpers: String::new(),
+        labels: Vec::new(),
+        fixes: Vec::new(),
+        severity: Severity::Error
+    }
+}
+
+/// Creates an error that underlines a whole span instead of a single token
+pub fn error_at(t: ErrorType, span: &Span) -> Error {
+    let mut err = error(t, &span.start);
+    err.location.3 = span.start.line().chars().skip(span.start.col).take(span.width()).collect();
+    return err;
+}
+
+/// Creates a warning
+pub fn warning(t: ErrorType, token: &Token) -> Error {
+    return Error {
+        severity: Severity::Warning,
+        ..error(t, token)
+    }
+}
+
+/// Creates a warning that underlines a whole span
+pub fn warning_at(t: ErrorType, span: &Span) -> Error {
+    return Error {
+        severity: Severity::Warning,
+        ..error_at(t, span)
     }
 }
 
@@ -70,11 +509,130 @@ pub enum ErrorType {
     ExpectedToken,
     MismatchedTypes,
     UndefinedArray,
-    UndefinedSymbol
+    UndefinedSymbol,
+    UndefinedField,
+    UninitializedField,
+    ImmutableAssign,
+    MissingReturn,
+    InvalidMain,
+    MissingMain,
+    StatementOutsideMain,
+    DuplicateName,
+    PauseOutsideLoop,
+    VoidValue,
+    NumberOutOfRange,
+    CyclicStruct,
+    MismatchedNumbers,
+    UninitializedVariable,
+    DivisionByZero,
+    ArgumentCount,
+    FormatNotLiteral,
+    RefNotVariable,
+
+    /// Code generation errors
+    InvalidIR,
+
+    /// Warnings
+    UnusedFunction,
+    ShadowedVariable,
+    DeadStore,
+    CharNarrowing
+}
+
+/// Implement functions for an error type
+impl ErrorType {
+    /// Every error type, in the order of their codes
+    pub const ALL: &'static [ErrorType] = &[
+        ErrorType::UnexpectedEOF,
+        ErrorType::UnknownChar,
+        ErrorType::DecTooManyDots,
+        ErrorType::DecNotFound,
+        ErrorType::ExpectedToken,
+        ErrorType::MismatchedTypes,
+        ErrorType::UndefinedArray,
+        ErrorType::UndefinedSymbol,
+        ErrorType::UndefinedField,
+        ErrorType::UninitializedField,
+        ErrorType::ImmutableAssign,
+        ErrorType::MissingReturn,
+        ErrorType::InvalidMain,
+        ErrorType::MissingMain,
+        ErrorType::StatementOutsideMain,
+        ErrorType::DuplicateName,
+        ErrorType::PauseOutsideLoop,
+        ErrorType::VoidValue,
+        ErrorType::NumberOutOfRange,
+        ErrorType::CyclicStruct,
+        ErrorType::MismatchedNumbers,
+        ErrorType::UninitializedVariable,
+        ErrorType::DivisionByZero,
+        ErrorType::InvalidIR,
+        ErrorType::ArgumentCount,
+        ErrorType::FormatNotLiteral,
+        ErrorType::RefNotVariable,
+        ErrorType::UnusedFunction,
+        ErrorType::ShadowedVariable,
+        ErrorType::DeadStore,
+        ErrorType::CharNarrowing
+    ];
+
+    /// Finds the group a warning can be turned off with
+    pub fn group(&self) -> Option<WarningGroup> {
+        match self {
+            ErrorType::UnusedFunction   => Some(WarningGroup::Unused),
+            ErrorType::DeadStore        => Some(WarningGroup::Unused),
+            ErrorType::ShadowedVariable => Some(WarningGroup::Shadow),
+            ErrorType::CharNarrowing    => Some(WarningGroup::Narrowing),
+            _ => None
+        }
+    }
+
+    /// Finds the stable code of an error type, shown in its header
+    /// Codes are never reused, new error types get the next free code
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorType::UnexpectedEOF         => "E0001",
+            ErrorType::UnknownChar           => "E0002",
+            ErrorType::DecTooManyDots        => "E0003",
+            ErrorType::DecNotFound           => "E0004",
+            ErrorType::ExpectedToken         => "E0005",
+            ErrorType::MismatchedTypes       => "E0006",
+            ErrorType::UndefinedArray        => "E0007",
+            ErrorType::UndefinedSymbol       => "E0008",
+            ErrorType::UndefinedField        => "E0009",
+            ErrorType::UninitializedField    => "E0010",
+            ErrorType::ImmutableAssign       => "E0011",
+            ErrorType::MissingReturn         => "E0012",
+            ErrorType::InvalidMain           => "E0013",
+            ErrorType::MissingMain           => "E0014",
+            ErrorType::StatementOutsideMain  => "E0015",
+            ErrorType::DuplicateName         => "E0016",
+            ErrorType::PauseOutsideLoop      => "E0017",
+            ErrorType::VoidValue             => "E0018",
+            ErrorType::NumberOutOfRange      => "E0019",
+            ErrorType::CyclicStruct          => "E0020",
+            ErrorType::MismatchedNumbers     => "E0021",
+            ErrorType::UninitializedVariable => "E0022",
+            ErrorType::DivisionByZero        => "E0023",
+            ErrorType::InvalidIR             => "E0024",
+            ErrorType::ArgumentCount         => "E0025",
+            ErrorType::FormatNotLiteral      => "E0026",
+            ErrorType::RefNotVariable        => "E0027",
+            ErrorType::UnusedFunction        => "W0001",
+            ErrorType::ShadowedVariable      => "W0002",
+            ErrorType::DeadStore             => "W0003",
+            ErrorType::CharNarrowing         => "W0004"
+        }
+    }
+
+    /// Finds the error type with a code like "E0003"
+    pub fn from_code(code: &str) -> Option<ErrorType> {
+        return ErrorType::ALL.iter().find(|t| t.code() == code).cloned();
+    }
 }
 
 /// finds the correct error message for a given ErrorType
-fn message_for(e: ErrorType) -> String {
+pub fn message_for(e: ErrorType) -> String {
     match e {
         ErrorType::UnexpectedEOF   => "Unexpected end of input",
         ErrorType::UnknownChar     => "Unexpected character",
@@ -84,6 +642,117 @@ fn message_for(e: ErrorType) -> String {
         ErrorType::ExpectedToken   => "Expected token",
         ErrorType::MismatchedTypes => "Mismatched types",
         ErrorType::UndefinedArray  => "This array has no explicit type",
-        ErrorType::UndefinedSymbol => "This symbol is undefined"
+        ErrorType::UndefinedSymbol => "This symbol is undefined",
+        ErrorType::UndefinedField  => "This field is undefined",
+        ErrorType::UninitializedField => "This field is used before it is given a value",
+        ErrorType::ImmutableAssign => "This variable can't be changed",
+        ErrorType::MissingReturn   => "This function doesn't always return a value",
+        ErrorType::InvalidMain     => "This 'main' function has the wrong signature",
+        ErrorType::MissingMain     => "This program has no 'main' function",
+        ErrorType::StatementOutsideMain => "This statement is outside of 'main'",
+        ErrorType::DuplicateName   => "This name is already taken",
+        ErrorType::PauseOutsideLoop => "This statement is outside of a loop",
+        ErrorType::VoidValue       => "This call doesn't give a value",
+        ErrorType::NumberOutOfRange => "This number is out of range",
+        ErrorType::CyclicStruct    => "This struct contains itself",
+        ErrorType::MismatchedNumbers => "Mismatched number types",
+        ErrorType::UninitializedVariable => "This variable is used before it is given a value",
+        ErrorType::DivisionByZero  => "This divides by zero",
+        ErrorType::ArgumentCount   => "Wrong number of arguments",
+        ErrorType::FormatNotLiteral => "This format isn't a string literal",
+        ErrorType::RefNotVariable  => "This argument has to be a variable",
+        ErrorType::InvalidIR       => "The ir generated for this statement is invalid",
+        ErrorType::UnusedFunction  => "This function is never called",
+        ErrorType::ShadowedVariable => "This variable hides another one with the same name",
+        ErrorType::DeadStore       => "This value is never read",
+        ErrorType::CharNarrowing   => "This int is narrowed to a char"
     }.to_string()
 }
+
+#[test]
+fn test_codes() {
+    assert_eq!(ErrorType::MismatchedTypes.code(), "E0006");
+    assert_eq!(ErrorType::from_code("E0006"), Some(ErrorType::MismatchedTypes));
+    assert_eq!(ErrorType::from_code("E9999"), None);
+    for typ in ErrorType::ALL {
+        assert_eq!(ErrorType::ALL.iter().filter(|t| t.code() == typ.code()).count(), 1);
+    }
+}
+
+#[test]
+fn test_warning_groups() {
+    assert_eq!(WarningGroup::from_name("shadow"), Some(WarningGroup::Shadow));
+    assert_eq!(WarningGroup::from_name("all"), None);
+    for typ in ErrorType::ALL.iter() {
+        assert_eq!(typ.group().is_some(), typ.code().starts_with('W'), "{:?}", typ);
+    }
+}
+
+#[test]
+fn test_json() {
+    use super::token::TokenType;
+    use super::source::SourceMap;
+    use std::rc::Rc;
+    let token = Token {typ: TokenType::Id, value: "ab".to_string(), lineno: 2, col: 4, len: 2, source: Rc::new(SourceMap::new("a.gizmo", "\nlet ab"))};
+    let json = error(ErrorType::UndefinedSymbol, &token).note("'ab' is \"undefined\"").help("Declare it").to_json();
+    assert_eq!(json, "{\"code\": \"E0008\", \"severity\": \"error\", \"message\": \"This symbol is undefined\", \"file\": \"a.gizmo\", \"line\": 2, \"column\": 4, \"length\": 2, \"notes\": [\"'ab' is \\\"undefined\\\"\"], \"help\": [\"Declare it\"], \"labels\": [], \"fixes\": []}");
+}
+
+#[test]
+fn test_error_at() {
+    use super::token::TokenType;
+    use super::source::SourceMap;
+    use std::rc::Rc;
+    let line = "let x = a + b;".to_string();
+    let token = |value: &str, col: usize| Token {typ: TokenType::Id, value: value.to_string(), lineno: 1, col: col, len: value.chars().count(), source: Rc::new(SourceMap::new("a.gizmo", &line))};
+    let json = error_at(ErrorType::MismatchedTypes, &Span {start: token("a", 8), end: token("b", 12)}).to_json();
+    assert!(json.contains("\"column\": 8, \"length\": 5"));
+}
+
+#[test]
+fn test_fixes() {
+    use super::token::TokenType;
+    use super::source::SourceMap;
+    use std::rc::Rc;
+    let token = Token {typ: TokenType::Int, value: "5".to_string(), lineno: 1, col: 8, len: 1, source: Rc::new(SourceMap::new("a.gizmo", "let x = 5"))};
+    let json = error(ErrorType::ExpectedToken, &token)
+        .fix_insert_after(&token, ";", "Add the ';'")
+        .fix_replace(&token, "5.0", "Write it as a dec")
+        .to_json();
+    assert!(json.ends_with("\"fixes\": [{\"message\": \"Add the ';'\", \"line\": 1, \"column\": 9, \"length\": 0, \"replacement\": \";\"}, {\"message\": \"Write it as a dec\", \"line\": 1, \"column\": 8, \"length\": 1, \"replacement\": \"5.0\"}]}"));
+}
+
+#[test]
+fn test_window() {
+    let line = format!("let x = {} + y;", "1 + ".repeat(30));
+    assert_eq!(window("let x = 5;", 8, 9, 40), ("let x = 5;".to_string(), 0, 0));
+    assert_eq!(window(line.as_str(), 0, 3, 30), ("let x = 1 + 1 + 1 + 1 + ...".to_string(), 0, 0));
+    let (text, start, prefix) = window(line.as_str(), 131, 132, 30);
+    assert!(text.starts_with("...") && text.ends_with(" + y;"));
+    assert_eq!(text.chars().count(), 27);
+    assert_eq!(text.chars().nth(131 - start + prefix), Some('y'));
+}
+
+#[test]
+fn test_summary() {
+    assert_eq!(summary(1, 20), "Aborting due to 1 previous error");
+    assert_eq!(summary(3, 20), "Aborting due to 3 previous errors");
+    assert_eq!(summary(25, 20), "Aborting due to 25 previous errors, only the first 20 are shown");
+    assert_eq!(summary(25, 0), "Aborting due to 25 previous errors");
+}
+
+#[test]
+fn test_error_limit() {
+    use super::token::TokenType;
+    use super::source::SourceMap;
+    use std::rc::Rc;
+    let token = Token {typ: TokenType::Id, value: "a".to_string(), lineno: 1, col: 0, len: 1, source: Rc::new(SourceMap::new("", "a"))};
+    let mut renderer = Renderer::new(MessageFormat::Json, 1);
+    assert!(renderer.render(&error(ErrorType::UndefinedSymbol, &token)).is_some());
+    assert!(renderer.render(&warning(ErrorType::DeadStore, &token)).is_some());
+    assert_eq!(renderer.render(&error(ErrorType::ExpectedToken, &token)), None);
+    assert_eq!(renderer.errors, 2);
+
+    // Each renderer counts its own errors
+    assert!(Renderer::new(MessageFormat::Json, 1).render(&error(ErrorType::ExpectedToken, &token)).is_some());
+}
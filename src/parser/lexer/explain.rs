@@ -0,0 +1,360 @@
+use super::error::ErrorType;
+
+/// Finds the longer description of an error type, shown by '--explain'
+pub fn explain(typ: &ErrorType) -> &'static str {
+    match typ {
+        ErrorType::UnexpectedEOF => "\
+The file ended in the middle of something, like a string or a character.
+
+    let s = \"abc
+
+Close the string or character before the end of the file:
+
+    let s = \"abc\";
+",
+        ErrorType::UnknownChar => "\
+A character that isn't part of the language was found outside of a string
+or a comment.
+
+    let x = 5 $ 6;
+
+Remove the character, or put it inside of a string.
+",
+        ErrorType::DecTooManyDots => "\
+A number can only have one '.' in it.
+
+    let d = 1.2.3;
+
+Remove the extra dots:
+
+    let d = 1.23;
+",
+        ErrorType::DecNotFound => "\
+A '.' in a number has to be followed by more digits.
+
+    let d = 5.;
+
+Add the digits after the dot, or remove the dot:
+
+    let d = 5.0;
+",
+        ErrorType::ExpectedToken => "\
+The code doesn't fit the grammar of the language here. The error says what
+was expected instead.
+
+    let x 5;
+
+Add the missing token:
+
+    let x = 5;
+",
+        ErrorType::MismatchedTypes => "\
+A value has a different type than the one needed where it is used.
+
+    var a = 5;
+    a = \"hi\";
+
+Give the variable a value of its own type, or declare a new variable with
+the other type:
+
+    let b = \"hi\";
+",
+        ErrorType::UndefinedArray => "\
+The type of the elements of an array can't be known.
+
+Give the array at least one element so its type is known.
+",
+        ErrorType::UndefinedSymbol => "\
+A name is used that isn't declared where it is used. Variables, functions
+and structs have to be declared before they are used, and variables only
+live until the end of their block.
+
+    write(count);
+
+Declare the name first:
+
+    let count = 5;
+    write(count);
+",
+        ErrorType::UndefinedField => "\
+A struct doesn't have a field with this name.
+
+    struct Point { x: int, y: int }
+    let p = new Point(1, 2);
+    write(p.z);
+
+Use one of the fields that the struct declares.
+",
+        ErrorType::UninitializedField => "\
+A field of a struct is read before it is given a value.
+
+    struct Point { x: int, y: int }
+    var p: Point;
+    p.x = 1;
+    write(p.y);
+
+A struct declared without a value, or made with an empty 'new', is given
+one field by field. Assign the field before reading it:
+
+    var p: Point;
+    p.x = 1;
+    p.y = 2;
+    write(p.y);
+",
+        ErrorType::ImmutableAssign => "\
+A variable declared with 'let' can only be given a value once, and an
+argument can only be changed if it is a 'ref' argument.
+
+    let a = 5;
+    a = 6;
+
+Declare the variable with 'var' to be able to change it:
+
+    var a = 5;
+    a = 6;
+",
+        ErrorType::MissingReturn => "\
+A function that returns a value can reach its end without a 'ret'.
+
+    func sign(x: int): int {
+        if x < 0 {
+            ret -1;
+        }
+    }
+
+Return a value on every path through the function:
+
+    func sign(x: int): int {
+        if x < 0 {
+            ret -1;
+        }
+        ret 1;
+    }
+",
+        ErrorType::InvalidMain => "\
+A 'main' function can't take any arguments, and can only return 'int'.
+
+    func main(args: string[]) {
+    }
+
+Use one of the allowed signatures:
+
+    func main() {
+    }
+
+    func main(): int {
+        ret 0;
+    }
+",
+        ErrorType::MissingMain => "\
+Since the 2024 edition, a program needs a 'main' function where it starts.
+
+    write(\"Hello\");
+
+Put the statements inside of 'main':
+
+    func main() {
+        write(\"Hello\");
+    }
+",
+        ErrorType::StatementOutsideMain => "\
+When a program has a 'main' function, only functions, structs and variables
+can be declared outside of it.
+
+    write(\"Hello\");
+    func main() {
+    }
+
+Move the statement into 'main':
+
+    func main() {
+        write(\"Hello\");
+    }
+",
+        ErrorType::DuplicateName => "\
+Two fields of a struct, two arguments of a function, or two declarations in
+the same scope have the same name.
+
+    func add(a: int, a: int): int {
+        ret a + a;
+    }
+
+Give each one its own name:
+
+    func add(a: int, b: int): int {
+        ret a + b;
+    }
+",
+        ErrorType::PauseOutsideLoop => "\
+'break' and 'continue' can only be used inside of a 'while' loop, and not in
+a function declared inside of one.
+
+    if done {
+        break;
+    }
+
+Use 'ret' to leave a function instead.
+",
+        ErrorType::VoidValue => "\
+A function that doesn't return anything is called where a value is needed.
+
+    func greet() {
+        write(\"Hi\");
+    }
+    let x = greet();
+
+Call the function on its own, or give it a return type:
+
+    greet();
+",
+        ErrorType::NumberOutOfRange => "\
+A number is too large for its type. Whole numbers up to 2147483647 are
+'int's and larger ones up to 9223372036854775807 are 'long's, and a 'dec' is
+a 64 bit floating point number.
+
+    let big = 99999999999999999999;
+
+Use a 'dec' for larger numbers:
+
+    let big = 99999999999999999999.0;
+",
+        ErrorType::CyclicStruct => "\
+A struct contains itself, either directly or through the fields of other
+structs, so it would take up an infinite amount of space.
+
+    struct Node { next: Node }
+
+Remove one of the fields that leads back to the struct.
+",
+        ErrorType::MismatchedNumbers => "\
+Two kinds of numbers are mixed. 'int', 'dec' and 'char' aren't converted into
+each other automatically.
+
+    let a = 5;
+    let d = 2.5 + a;
+
+Convert the value with 'as':
+
+    let d = 2.5 + a as dec;
+",
+        ErrorType::UninitializedVariable => "\
+A variable declared without a value is read before it is given one on every
+path that leads to the read.
+
+    let x: int;
+    if ready {
+        x = 1;
+    }
+    write(x);
+
+Give the variable a value on every path:
+
+    let x: int;
+    if ready {
+        x = 1;
+    } else {
+        x = 0;
+    }
+    write(x);
+",
+        ErrorType::DivisionByZero => "\
+A number is divided by a constant 0, which would fail when the program runs.
+
+    let x = 10 / 0;
+
+Divide by a number other than 0.
+",
+        ErrorType::ArgumentCount => "\
+A call gives a different number of arguments than the function takes.
+
+    func add(a: int, b: int): int {
+        ret a + b;
+    }
+    write(add(1));
+
+Give a value for every parameter, and no more:
+
+    write(add(1, 2));
+",
+        ErrorType::FormatNotLiteral => "\
+The format of 'writef' has to be written out as a string literal, since its
+'{}' are matched with the values when the program is compiled.
+
+    let format = \"x = {}\\n\";
+    writef(format, x);
+
+Write the format in the call:
+
+    writef(\"x = {}\\n\", x);
+",
+        ErrorType::RefNotVariable => "\
+A 'ref' parameter points at the variable it is given, so the function can
+change it. Other values, like numbers or the results of calls, have nothing
+to point at.
+
+    func bump(ref a: int[]) {
+        a[0] = a[0] + 1;
+    }
+    bump([1, 2]);
+
+Store the value in a variable and pass the variable:
+
+    var a = [1, 2];
+    bump(a);
+",
+        ErrorType::InvalidIR => "\
+The compiler checks the llvm ir it generates before writing it out, and found
+a mistake in the ir of a statement, like an operand of the wrong type or a
+jump to a block that doesn't exist. llc would reject the file.
+
+This is a bug in the compiler rather than in the program. Please report it
+along with the statement, and rewrite the statement in another way until it
+is fixed.
+",
+        ErrorType::UnusedFunction => "\
+A function is declared but never called, so it can be removed.
+
+    func helper() {
+    }
+
+Call the function, or remove it.
+",
+        ErrorType::ShadowedVariable => "\
+A variable has the same name as one from an outer scope, which can't be used
+until the block ends. This is only reported with '-Wshadow'.
+
+    let x = 1;
+    if true {
+        let x = 2;
+    }
+
+Rename one of the variables if both are needed.
+",
+        ErrorType::DeadStore => "\
+A variable is given a value that is never read, because it is replaced or
+the variable goes out of scope first.
+
+    var x = 1;
+    x = 2;
+    write(x);
+
+Remove the assignment whose value is never read:
+
+    let x = 2;
+    write(x);
+",
+        ErrorType::CharNarrowing => "\
+Adding, subtracting or multiplying a char and an int gives a char, so the int
+is narrowed to fit. A char only holds 256 values, from 0 to 255, and anything
+outside of that wraps around.
+
+    let c = 'a' + 300;
+
+Convert the int with 'as' when the wrap around is wanted, or keep the result
+as an int by putting the int first:
+
+    let c = 'a' + 300 as char;
+    let i = 300 + 'a';
+",
+    }
+}
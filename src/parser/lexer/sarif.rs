@@ -0,0 +1,54 @@
+use super::error::Error;
+use super::error::ErrorType;
+use super::error::json_string;
+use super::error::message_for;
+
+/// Serializes errors as a SARIF 2.1.0 log with a single run
+/// Each error code used is listed once as a rule of the tool
+pub fn to_sarif(errors: &[Error]) -> String {
+    let mut codes: Vec<&str> = Vec::new();
+    for error in errors.iter() {
+        if !codes.contains(&error.code()) {
+            codes.push(error.code());
+        }
+    }
+    let rules: Vec<String> = codes.iter().map(|code| {
+        let typ = ErrorType::from_code(code).unwrap();
+        format!("{{\"id\": {}, \"shortDescription\": {{\"text\": {}}}}}", json_string(code), json_string(message_for(typ).as_str()))
+    }).collect();
+    let results: Vec<String> = errors.iter().map(|e| e.to_sarif_result()).collect();
+
+    let mut log = String::new();
+    log.push_str("{\n");
+    log.push_str("  \"$schema\": \"https://json.schemastore.org/sarif-2.1.0.json\",\n");
+    log.push_str("  \"version\": \"2.1.0\",\n");
+    log.push_str("  \"runs\": [{\n");
+    log.push_str(format!("    \"tool\": {{\"driver\": {{\"name\": \"gizmoc\", \"rules\": [{}]}}}},\n", rules.join(", ")).as_str());
+    log.push_str("    \"results\": [");
+    if !results.is_empty() {
+        log.push_str(format!("\n      {}\n    ", results.join(",\n      ")).as_str());
+    }
+    log.push_str("]\n");
+    log.push_str("  }]\n");
+    log.push_str("}\n");
+    return log;
+}
+
+#[test]
+fn test_sarif() {
+    use super::error::error;
+    use super::error::warning;
+    use super::token::Token;
+    use super::token::TokenType;
+    use super::source::SourceMap;
+    use std::rc::Rc;
+    let token = Token {typ: TokenType::Id, value: "ab".to_string(), lineno: 2, col: 4, len: 2, source: Rc::new(SourceMap::new("a.gizmo", "\nlet ab"))};
+    let errors = vec![error(ErrorType::UndefinedSymbol, &token).note("'ab' is undefined").clone(), warning(ErrorType::DeadStore, &token), error(ErrorType::UndefinedSymbol, &token)];
+    let log = to_sarif(&errors);
+    assert_eq!(log.matches("{\"id\": \"E0008\"").count(), 1);
+    assert_eq!(log.matches("\"ruleId\"").count(), 3);
+    assert!(log.contains("\"level\": \"warning\""));
+    assert!(log.contains("\"text\": \"This symbol is undefined\\nnote: 'ab' is undefined\""));
+    assert!(log.contains("\"region\": {\"startLine\": 2, \"startColumn\": 5, \"endColumn\": 7}"));
+    assert!(!log.contains("relatedLocations"));
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+
+/// Owns the text of a file and finds its lines and columns on demand, so
+/// tokens and errors can share it instead of copying lines around
+#[derive(PartialEq, Eq)]
+pub struct SourceMap {
+    /// Name of the file, shown in errors
+    pub file: String,
+
+    /// Text of the whole file
+    pub text: String,
+
+    /// Byte offset where each line starts
+    line_starts: Vec<usize>,
+}
+
+/// Implement functions for a source map
+impl SourceMap {
+    /// Constructs a source map, finding where each line starts
+    pub fn new(file: &str, text: &str) -> SourceMap {
+        let mut line_starts = vec![0];
+        for (i, c) in text.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        return SourceMap {file: file.to_string(), text: text.to_string(), line_starts: line_starts};
+    }
+
+    /// Number of lines in the text
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Finds the text of a line, without its '\n'
+    /// Lines are numbered from 1, and lines past the end are empty
+    pub fn line(&self, lineno: usize) -> &str {
+        if lineno == 0 || lineno > self.line_starts.len() {
+            return "";
+        }
+        let start = self.line_starts[lineno - 1];
+        let end = self.line_starts.get(lineno).map(|e| e - 1).unwrap_or(self.text.len());
+        return &self.text[start..end];
+    }
+}
+
+impl fmt::Debug for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SourceMap({:?})", self.file)
+    }
+}
+
+#[test]
+fn test_source_map() {
+    let source = SourceMap::new("a.gizmo", "let x = 1;\n\nwrite(x);");
+    assert_eq!(source.line_count(), 3);
+    assert_eq!(source.line(1), "let x = 1;");
+    assert_eq!(source.line(2), "");
+    assert_eq!(source.line(3), "write(x);");
+    assert_eq!(source.line(4), "");
+}
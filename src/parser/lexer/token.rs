@@ -1,3 +1,7 @@
+use std::rc::Rc;
+
+use super::source::SourceMap;
+
 /// Stores each token's information
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Token {
@@ -13,8 +17,61 @@ pub struct Token {
     /// Column that the token is on
     pub col: usize,
 
-    /// Line that the token is on (for printing errors)
-    pub line: String,
+    /// Number of characters the token takes up in the source, which is more
+    /// than its value for strings and chars with their quotes and escapes
+    pub len: usize,
+
+    /// Text of the file that the token is in (for printing errors)
+    pub source: Rc<SourceMap>,
+}
+
+/// Implement functions for a token
+impl Token {
+    /// Line that the token is on
+    pub fn line(&self) -> &str {
+        self.source.line(self.lineno)
+    }
+
+    /// File that the token is in
+    pub fn file(&self) -> &str {
+        self.source.file.as_str()
+    }
+
+    /// Text of the token as it is written
+    pub fn text(&self) -> String {
+        self.line().chars().skip(self.col).take(self.len).collect()
+    }
+}
+
+/// The first and last token of an expression or statement, so errors can
+/// underline all of it
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Span {
+    pub start: Token,
+    pub end: Token,
+}
+
+/// Implement functions for a span
+impl Span {
+    /// A span of a single token
+    pub fn token(token: &Token) -> Span {
+        return Span {start: token.clone(), end: token.clone()};
+    }
+
+    /// Number of characters from the start of the span to its end
+    /// A span over several lines only counts the first one
+    pub fn width(&self) -> usize {
+        if self.end.lineno != self.start.lineno {
+            return self.start.line().chars().count().saturating_sub(self.start.col);
+        }
+        return (self.end.col + self.end.len).saturating_sub(self.start.col);
+    }
+
+    /// Text of the span as it is written
+    /// A span over several lines only gives the first one
+    pub fn text(&self) -> String {
+        return self.start.line().chars().skip(self.start.col).take(self.width()).collect();
+    }
 }
 
 /// An enum with all the tokens for the language
@@ -24,6 +81,7 @@ pub enum TokenType {
     Dash,         // -
     Star,         // *
     Slash,        // /
+    Percent,      // %
     NotEqual,     // !=
     SemiColon,    // ;
     LeftParen,    // (
@@ -42,6 +100,7 @@ pub enum TokenType {
     Comma,        // ,
     Dot,          // .
     Let,
+    Var,
     If,
     Use,
     Else,
@@ -49,9 +108,11 @@ pub enum TokenType {
     Break,
     Continue,
     Func,
+    Ref,
     While,
     New,
     Struct,
+    As,
     Id,
     Int,
     Dec,
@@ -1,6 +1,8 @@
 use super::lexer::error::error;
+use super::lexer::error::Error;
 use super::lexer::error::ErrorType;
 use super::lexer::token::Token;
+use super::types::Type;
 
 /// An enum to store each kind of symbol
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -17,10 +19,34 @@ pub struct VarSymbol {
     pub id: String,
 
     /// Type of the symbol (i.e., int or string)
-    pub typ: String,
+    pub typ: Type,
 
     /// Stores the id of the symbol in ir for code generation
     pub gen_id: String,
+
+    /// Fields of a struct variable declared without a value that haven't been
+    /// given one yet, since the fields can be given theirs one by one
+    pub uninit_fields: Vec<String>,
+
+    /// Whether or not the variable can be assigned to (declared with 'var')
+    pub mutable: bool,
+
+    /// Where the variable is declared, None for arguments
+    pub token: Option<Token>,
+
+    /// Whether the variable might not have a value yet, for variables
+    /// declared without one
+    pub uninit: bool,
+
+    /// Whether the variable might have been given a value after it was
+    /// declared, or was declared with one
+    pub assigned: bool,
+
+    /// Number of loops around the declaration
+    pub loop_depth: usize,
+
+    /// Where the variable was last given a value that hasn't been read yet
+    pub store: Option<Token>,
 }
 
 /// Stores information for each function symbol
@@ -30,13 +56,22 @@ pub struct FuncSymbol {
     pub id: String,
 
     /// Type of the symbol (i.e., int or string)
-    pub typ: String,
+    pub typ: Type,
 
     /// Stores the id of the symbol in ir for code generation
     pub gen_id: String,
 
     /// Stores the types of the arguments
-    pub arg_types: Vec<String>
+    pub arg_types: Vec<Type>,
+
+    /// Stores whether or not each argument is passed by reference
+    pub arg_refs: Vec<bool>,
+
+    /// Where the function is declared, None for builtins
+    pub token: Option<Token>,
+
+    /// Number of calls to the function from outside of its own body
+    pub calls: usize
 }
 
 /// Stores information for each struct symbol
@@ -49,7 +84,36 @@ pub struct StructSymbol {
     pub gen_id: String,
 
     /// Stores the types of the arguments
-    pub arg_types: Vec<String>
+    pub arg_types: Vec<Type>,
+
+    /// Stores the names of the fields
+    pub field_ids: Vec<String>
+}
+
+/// Which variables might still be without a value, and which might have been
+/// given one, by their gen ids
+/// Taken before a branch and merged after it to follow every path
+pub type InitState = (Vec<String>, Vec<String>);
+
+/// Combines the states at the end of two paths that meet again
+/// A variable might be without a value or given one if it is on either path
+pub fn merge_init_states(a: &InitState, b: &InitState) -> InitState {
+    let mut merged = a.clone();
+    merged.0.extend(b.0.iter().filter(|id| !a.0.contains(id)).cloned());
+    merged.1.extend(b.1.iter().filter(|id| !a.1.contains(id)).cloned());
+    return merged;
+}
+
+/// Finds the name in ir of a function declared in Gizmo
+/// The prefix keeps it from clashing with libc functions when linking, but
+/// 'main' stays as it is since the program starts there
+/// # Example
+/// printf -> gz_printf
+pub fn mangle(id: &str) -> String {
+    if id == "main" {
+        return id.to_string();
+    }
+    return format!("gz_{}", id);
 }
 
 /// Stores information for each scope
@@ -67,6 +131,31 @@ pub struct Scope {
     pub struct_symbols: Vec<StructSymbol>
 }
 
+/// Builtin functions that have a fixed signature
+/// (identifier, return type, argument types)
+pub const BUILTINS: &[(&str, Type, &[Type])] = &[
+    ("env", Type::Str, &[Type::Str]),
+    ("parse_int", Type::Int, &[Type::Str]),
+    ("parse_dec", Type::Dec, &[Type::Str]),
+    ("read", Type::Str, &[]),
+    ("read_line", Type::Str, &[]),
+    ("sqrt", Type::Dec, &[Type::Dec]),
+    ("pow", Type::Dec, &[Type::Dec, Type::Dec]),
+    ("abs", Type::Dec, &[Type::Dec]),
+    ("floor", Type::Dec, &[Type::Dec]),
+    ("ceil", Type::Dec, &[Type::Dec]),
+    ("round", Type::Dec, &[Type::Dec]),
+    ("sin", Type::Dec, &[Type::Dec]),
+    ("cos", Type::Dec, &[Type::Dec]),
+    ("tan", Type::Dec, &[Type::Dec]),
+    ("exp", Type::Dec, &[Type::Dec]),
+    ("log", Type::Dec, &[Type::Dec]),
+    ("clock", Type::Dec, &[]),
+    ("time", Type::Dec, &[]),
+    ("chr", Type::Char, &[Type::Int]),
+    ("ord", Type::Int, &[Type::Char]),
+];
+
 /// Stores information about the symbol table
 pub struct SymbolController {
     /// The current scope in the symbol table
@@ -76,18 +165,186 @@ pub struct SymbolController {
 /// Implement functions for the symbol table
 impl SymbolController {
     /// Adds a symbol to the current scope of the symbol table
-    pub fn add_symbol(&mut self, id: String, typ: String, symtyp: SymbolType, gen_id: String, arg_types: Option<Vec<String>>) {
-        // If the symbol already exists, print an error
-        if self.find(id.clone(), symtyp.clone()) != false {
-            eprintln!("Identifer '{}' already exists", id);
-            std::process::exit(1);
+    /// The token is where the symbol is declared, for reporting a duplicate
+    /// Symbols without one, like builtins and arguments, can hide another
+    pub fn add_symbol(&mut self, id: String, typ: Type, symtyp: SymbolType, gen_id: String, arg_types: Option<Vec<Type>>, token: Option<&Token>) -> Result<(), Error> {
+        // If the symbol already exists, report an error
+        if let (true, Some(token)) = (self.find(id.clone(), symtyp.clone()), token) {
+            let first = match symtyp {
+                SymbolType::Var => self.current.var_symbols.iter().find(|s| s.id == id).and_then(|s| s.token.clone()),
+                SymbolType::Func => self.current.func_symbols.iter().find(|s| s.id == id).and_then(|s| s.token.clone()),
+                SymbolType::Struct => None
+            };
+            let mut err = error(ErrorType::DuplicateName, token);
+            if let Some(first) = &first {
+                err.label(first, "first declared here");
+            }
+            err.note(format!("'{}' is already declared in this scope", id).as_str())
+                .help("Rename one of them");
+            return Err(err);
         }
 
         // Add the new symbol to the current scope
         match symtyp.clone() {
-            SymbolType::Var    => self.current.var_symbols.push(VarSymbol {id: id, typ: typ, gen_id: gen_id}),
-            SymbolType::Func   => self.current.func_symbols.push(FuncSymbol {id: id, typ: typ, gen_id: gen_id, arg_types: arg_types.unwrap_or(Vec::new())}),
-            SymbolType::Struct => self.current.struct_symbols.push(StructSymbol {id: id, gen_id: gen_id, arg_types: arg_types.unwrap_or(Vec::new())}),
+            SymbolType::Var    => self.current.var_symbols.push(VarSymbol {id: id, typ: typ, gen_id: gen_id, uninit_fields: Vec::new(), mutable: false, token: None, uninit: false, assigned: true, loop_depth: 0, store: None}),
+            SymbolType::Func   => self.current.func_symbols.push(FuncSymbol {id: id, typ: typ, gen_id: gen_id, arg_refs: Vec::new(), arg_types: arg_types.unwrap_or_default(), token: None, calls: 0}),
+            SymbolType::Struct => self.current.struct_symbols.push(StructSymbol {id: id, gen_id: gen_id, arg_types: arg_types.unwrap_or_default(), field_ids: Vec::new()}),
+        }
+        return Ok(());
+    }
+
+    /// Adds a variable symbol that can or can't be assigned to
+    /// The token is where the variable is declared, None for arguments
+    pub fn add_var_symbol(&mut self, id: String, typ: Type, gen_id: String, mutable: bool, token: Option<Token>) -> Result<(), Error> {
+        self.add_symbol(id, typ, SymbolType::Var, gen_id, None, token.as_ref())?;
+        let sym = self.current.var_symbols.last_mut().unwrap();
+        sym.mutable = mutable;
+        sym.token = token;
+        return Ok(());
+    }
+
+    /// Adds a struct symbol along with its field names and types
+    /// The token is where the struct is declared
+    pub fn add_struct_symbol(&mut self, id: String, fields: Vec<(String, Type)>, token: Option<Token>) -> Result<(), Error> {
+        self.add_symbol(id.clone(), Type::Struct(id.clone()), SymbolType::Struct, id, Some(fields.iter().map(|f| f.1.clone()).collect()), token.as_ref())?;
+        self.current.struct_symbols.last_mut().unwrap().field_ids = fields.iter().map(|f| f.0.clone()).collect();
+        return Ok(());
+    }
+
+    /// Adds a function symbol along with its arguments
+    /// The token is where the function is declared, None for builtins
+    /// Builtins keep their name in ir, since the generator recognizes them by
+    /// it
+    pub fn add_func_symbol(&mut self, id: String, typ: Type, args: Vec<(Type, bool)>, token: Option<Token>) -> Result<(), Error> {
+        let gen_id = if token.is_none() {id.clone()} else {mangle(id.as_str())};
+        self.add_symbol(id.clone(), typ, SymbolType::Func, gen_id, Some(args.iter().map(|a| a.0.clone()).collect()), token.as_ref())?;
+        let sym = self.current.func_symbols.last_mut().unwrap();
+        sym.arg_refs = args.iter().map(|a| a.1).collect();
+        sym.token = token;
+        return Ok(());
+    }
+
+    /// Declares a variable that is given a value later
+    pub fn defer_init(&mut self, id: String, loop_depth: usize) {
+        if let Some(sym) = self.find_global_var_mut(id) {
+            sym.uninit = true;
+            sym.assigned = false;
+            sym.loop_depth = loop_depth;
+        }
+    }
+
+    /// Records that a variable is given a value
+    pub fn give_value(&mut self, id: String) {
+        if let Some(sym) = self.find_global_var_mut(id) {
+            sym.uninit = false;
+            sym.assigned = true;
+        }
+    }
+
+    /// Records that a variable is given a value at a token
+    /// Returns where it was given the previous one if that was never read
+    pub fn record_store(&mut self, id: String, token: Token) -> Option<Token> {
+        let sym = self.find_global_var_mut(id)?;
+        return sym.store.replace(token);
+    }
+
+    /// Records that the value of a variable is read
+    pub fn record_read(&mut self, id: String) {
+        if let Some(sym) = self.find_global_var_mut(id) {
+            sym.store = None;
+        }
+    }
+
+    /// Forgets the values that haven't been read in every visible scope
+    /// Used where control flow splits or joins, since the value might be read
+    /// on another path
+    pub fn clear_stores(&mut self) {
+        let mut current: Option<&mut Scope> = Some(&mut self.current);
+        while let Some(cur) = current {
+            for sym in cur.var_symbols.iter_mut() {
+                sym.store = None;
+            }
+            current = cur.parent.as_deref_mut();
+        }
+    }
+
+    /// Finds the variables in the current scope whose last value is never read
+    pub fn unread_stores(&self) -> Vec<(String, Token)> {
+        return self.current.var_symbols.iter().filter_map(|sym| Some((sym.id.clone(), sym.store.clone()?))).collect();
+    }
+
+    /// Finds which of the visible variables might be without a value, and
+    /// which might have been given one
+    pub fn init_state(&self) -> InitState {
+        let mut state: InitState = (Vec::new(), Vec::new());
+        let mut current: Option<&Scope> = Some(&self.current);
+        while let Some(cur) = current {
+            for sym in cur.var_symbols.iter() {
+                if sym.uninit {
+                    state.0.push(sym.gen_id.clone());
+                }
+                for field in sym.uninit_fields.iter() {
+                    state.0.push(format!("{}.{}", sym.gen_id, field));
+                }
+                if sym.assigned {
+                    state.1.push(sym.gen_id.clone());
+                }
+            }
+            current = cur.parent.as_deref();
+        }
+        return state;
+    }
+
+    /// Restores the state of the visible variables
+    pub fn set_init_state(&mut self, state: &InitState) {
+        let mut current: Option<&mut Scope> = Some(&mut self.current);
+        while let Some(cur) = current {
+            for sym in cur.var_symbols.iter_mut() {
+                sym.uninit = state.0.contains(&sym.gen_id);
+                sym.assigned = state.1.contains(&sym.gen_id);
+                let prefix = format!("{}.", sym.gen_id);
+                sym.uninit_fields = state.0.iter().filter_map(|id| id.strip_prefix(prefix.as_str())).map(|f| f.to_string()).collect();
+            }
+            current = cur.parent.as_deref_mut();
+        }
+    }
+
+    /// Records a call to a function
+    pub fn call_func(&mut self, id: String) {
+        let mut current: Option<&mut Scope> = Some(&mut self.current);
+        while let Some(cur) = current {
+            if let Some(sym) = cur.func_symbols.iter_mut().find(|sym| sym.id == id) {
+                sym.calls += 1;
+                return;
+            }
+            current = cur.parent.as_deref_mut();
+        }
+    }
+
+    /// Finds the declared functions in the current scope that are never called
+    pub fn uncalled_funcs(&self) -> Vec<FuncSymbol> {
+        return self.current.func_symbols.iter().filter(|sym| sym.token.is_some() && sym.calls == 0).cloned().collect();
+    }
+
+    /// Adds the builtin functions to the current scope
+    pub fn add_builtins(&mut self) {
+        for (id, typ, args) in BUILTINS.iter() {
+            // Builtins have no token, so they are never reported as duplicates
+            let _ = self.add_func_symbol(id.to_string(), typ.clone(), args.iter().map(|a| (a.clone(), false)).collect(), None);
+        }
+    }
+
+    /// Records which fields of a struct variable haven't been given a value
+    pub fn set_uninit_fields(&mut self, id: String, fields: Vec<String>) {
+        if let Some(sym) = self.find_global_var_mut(id) {
+            sym.uninit_fields = fields;
+        }
+    }
+
+    /// Marks a field of a struct variable as initialized
+    pub fn init_field(&mut self, id: String, field: String) {
+        if let Some(sym) = self.find_global_var_mut(id) {
+            sym.uninit_fields.retain(|f| *f != field);
         }
     }
 
@@ -102,6 +359,17 @@ impl SymbolController {
         self.current = new.clone();
     }
 
+    /// Finds how many scopes are around the current one
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut current = self.current.parent.as_deref();
+        while let Some(cur) = current {
+            depth += 1;
+            current = cur.parent.as_deref();
+        }
+        return depth;
+    }
+
     /// Pops a scope from the symbol table
     pub fn pop_scope(&mut self) {
         // Set the current to the parent of the current
@@ -148,7 +416,7 @@ impl SymbolController {
     pub fn find_global_var(&self, id: String) -> Option<VarSymbol> {
         // Loop through the current symbols
         let mut current: Option<Box<Scope>> = Some(Box::new(self.current.clone()));
-        while current != None {
+        while current.is_some() {
             let cur = *(current.clone().unwrap());
             for sym in cur.var_symbols.iter() {
                 // If the symbol matches, return the symbol
@@ -163,12 +431,27 @@ impl SymbolController {
         return None;
     }
 
+    /// Finds a mutable reference to a variable identifier in the global scope
+    /// Returns None if it doesn't exist
+    fn find_global_var_mut(&mut self, id: String) -> Option<&mut VarSymbol> {
+        let mut current: Option<&mut Scope> = Some(&mut self.current);
+        while let Some(cur) = current {
+            if let Some(pos) = cur.var_symbols.iter().position(|sym| sym.id == id) {
+                return Some(&mut cur.var_symbols[pos]);
+            }
+            current = cur.parent.as_deref_mut();
+        }
+
+        // The symbol wasn't found, return None
+        return None;
+    }
+
     /// Finds a function identifier in the global scope
     /// Returns None if it doesn't exist
     pub fn find_global_func(&self, id: String) -> Option<FuncSymbol> {
         // Loop through the current symbols
         let mut current: Option<Box<Scope>> = Some(Box::new(self.current.clone()));
-        while current != None {
+        while current.is_some() {
             let cur = *(current.clone().unwrap());
             for sym in cur.func_symbols.iter() {
                 // If the symbol matches, return the symbol
@@ -188,7 +471,7 @@ impl SymbolController {
     pub fn find_global_struct(&self, id: String) -> Option<StructSymbol> {
         // Loop through the current symbols
         let mut current: Option<Box<Scope>> = Some(Box::new(self.current.clone()));
-        while current != None {
+        while current.is_some() {
             let cur = *(current.clone().unwrap());
             for sym in cur.struct_symbols.iter() {
                 // If the symbol matches, return the symbol
@@ -203,11 +486,34 @@ impl SymbolController {
         return None;
     }
 
+    /// Finds a chain of struct fields that leads from the fields of a new
+    /// struct back to the struct itself
+    /// Such a struct would contain itself, so it could never be laid out
+    /// Returns None if there is no cycle
+    pub fn find_struct_cycle(&self, id: String, fields: &[Type]) -> Option<Vec<String>> {
+        for field in fields.iter() {
+            if let Type::Struct(field_id) = field {
+                if *field_id == id {
+                    return Some(vec![id.clone()]);
+                }
+
+                // Structs that aren't declared yet are checked once they are
+                if let Some(sym) = self.find_global_struct(field_id.clone()) {
+                    if let Some(mut chain) = self.find_struct_cycle(id.clone(), &sym.arg_types) {
+                        chain.insert(0, field_id.clone());
+                        return Some(chain);
+                    }
+                }
+            }
+        }
+        return None;
+    }
+
     /// Finds a variable identifier in the global scope
-    /// Prints an error if it doesn't exist
-    pub fn find_global_var_error(&self, id: String, token: &Token) -> VarSymbol {
+    /// Gives back an error if it doesn't exist
+    pub fn find_global_var_error(&self, id: String, token: &Token) -> Result<VarSymbol, Error> {
         let sym = self.find_global_var(id.clone());
-        if sym == None {
+        if sym.is_none() {
             // If the symbol isn't found, print an error
             let mut cur_var_ids: Vec<String> = Vec::new();
             for symbol in self.current.var_symbols.clone() {
@@ -218,21 +524,19 @@ impl SymbolController {
             } else {
                 format!("Perhaps you meant '{}'", autoc(id.clone(), cur_var_ids))
             };
-            error(ErrorType::UndefinedSymbol, token)
-                .note(format!("Undefined symbol '{}'", id).as_str())
-                .help(helper.as_str())
-                .emit();
-            std::process::exit(1);
-        } else {
-            return sym.unwrap();
+            let mut err = error(ErrorType::UndefinedSymbol, token);
+            err.note(format!("Undefined symbol '{}'", id).as_str())
+                .help(helper.as_str());
+            return Err(err);
         }
+        return Ok(sym.unwrap());
     }
 
     /// Finds a function identifier in the global scope
-    /// Prints an error if it doesn't exist
-    pub fn find_global_func_error(&self, id: String, token: &Token) -> FuncSymbol {
+    /// Gives back an error if it doesn't exist
+    pub fn find_global_func_error(&self, id: String, token: &Token) -> Result<FuncSymbol, Error> {
         let sym = self.find_global_func(id.clone());
-        if sym == None {
+        if sym.is_none() {
             // If the symbol isn't found, print an error
             let mut cur_func_ids: Vec<String> = Vec::new();
             for symbol in self.current.func_symbols.clone() {
@@ -243,21 +547,19 @@ impl SymbolController {
             } else {
                 format!("Perhaps you meant '{}'", autoc(id.clone(), cur_func_ids))
             };
-            error(ErrorType::UndefinedSymbol, token)
-                .note(format!("Undefined symbol '{}'", id).as_str())
-                .help(helper.as_str())
-                .emit();
-            std::process::exit(1);
-        } else {
-            return sym.unwrap();
+            let mut err = error(ErrorType::UndefinedSymbol, token);
+            err.note(format!("Undefined symbol '{}'", id).as_str())
+                .help(helper.as_str());
+            return Err(err);
         }
+        return Ok(sym.unwrap());
     }
 
     /// Finds a struct symbol in the global scope
-    /// Prints an error if it doesn't exist
-    pub fn find_global_struct_error(&self, id: String, token: &Token) -> StructSymbol {
+    /// Gives back an error if it doesn't exist
+    pub fn find_global_struct_error(&self, id: String, token: &Token) -> Result<StructSymbol, Error> {
         let sym = self.find_global_struct(id.clone());
-        if sym == None {
+        if sym.is_none() {
             // If the symbol isn't found, print an error
             let mut cur_struct_ids: Vec<String> = Vec::new();
             for symbol in self.current.struct_symbols.clone() {
@@ -268,14 +570,12 @@ impl SymbolController {
             } else {
                 format!("Perhaps you meant '{}'", autoc(id.clone(), cur_struct_ids))
             };
-            error(ErrorType::UndefinedSymbol, token)
-                .note(format!("'{}' is undefined", id).as_str())
-                .help(helper.as_str())
-                .emit();
-            std::process::exit(1);
-        } else {
-            return sym.unwrap();
+            let mut err = error(ErrorType::UndefinedSymbol, token);
+            err.note(format!("'{}' is undefined", id).as_str())
+                .help(helper.as_str());
+            return Err(err);
         }
+        return Ok(sym.unwrap());
     }
 }
 
@@ -304,3 +604,13 @@ fn autoc(word: String, names: Vec<String>) -> String {
 
     return most_sim;
 }
+
+/// Finds the name most like 'word', if it is close enough that 'word' is
+/// likely a typo of it
+pub fn did_you_mean(word: &str, names: &[String]) -> Option<String> {
+    let best = autoc(word.to_string(), names.to_vec());
+    if best != word && similarity(best.clone(), word.to_string()) >= 0.75 {
+        return Some(best);
+    }
+    return None;
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+
+/// An enum to store each type of the language
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Type {
+    Int,
+    Long,
+    UInt,
+    Dec,
+    Bool,
+    Char,
+    Str,
+    Void,
+
+    /// Array of another type
+    /// # Example
+    /// `int[]`
+    Array(Box<Type>),
+
+    /// Struct with its identifier
+    Struct(String),
+
+    /// Type of an expression that doesn't type check
+    Error,
+}
+
+/// Implement functions for a type
+impl Type {
+    /// Finds the type for a name like "int" or "int[]"
+    /// Names that aren't builtin types are structs
+    pub fn from_name(name: &str) -> Type {
+        if let Some(element) = name.strip_suffix("[]") {
+            return Type::Array(Box::new(Type::from_name(element)));
+        }
+        match name {
+            "int"    => Type::Int,
+            "long"   => Type::Long,
+            "uint"   => Type::UInt,
+            "dec"    => Type::Dec,
+            "bool"   => Type::Bool,
+            "char"   => Type::Char,
+            "string" => Type::Str,
+            "void"   => Type::Void,
+            _ => Type::Struct(name.to_string())
+        }
+    }
+
+    /// Whether a value of this type can be converted to another with 'as'
+    /// Only numbers convert, between 'int', 'long', 'uint' and 'dec' or
+    /// 'char' and 'int'
+    pub fn casts_to(&self, to: &Type) -> bool {
+        return self == to || matches!((self, to),
+            (Type::Int, Type::Dec) | (Type::Dec, Type::Int) |
+            (Type::Int, Type::Long) | (Type::Long, Type::Int) |
+            (Type::Long, Type::Dec) | (Type::Dec, Type::Long) |
+            (Type::UInt, Type::Int | Type::Long | Type::Dec) |
+            (Type::Int | Type::Long | Type::Dec, Type::UInt) |
+            (Type::Char, Type::Int) | (Type::Int, Type::Char));
+    }
+
+    /// Returns the type of the elements when indexing into this type
+    pub fn element(&self) -> Option<Type> {
+        match self {
+            Type::Str => Some(Type::Char),
+            Type::Array(element) => Some(*element.clone()),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int    => write!(f, "int"),
+            Type::Long   => write!(f, "long"),
+            Type::UInt   => write!(f, "uint"),
+            Type::Dec    => write!(f, "dec"),
+            Type::Bool   => write!(f, "bool"),
+            Type::Char   => write!(f, "char"),
+            Type::Str    => write!(f, "string"),
+            Type::Void   => write!(f, "void"),
+            Type::Array(element) => write!(f, "{}[]", element),
+            Type::Struct(id) => write!(f, "{}", id),
+            Type::Error  => write!(f, "error"),
+        }
+    }
+}
+
+#[test]
+fn test_from_name() {
+    assert_eq!(Type::from_name("int"), Type::Int);
+    assert_eq!(Type::from_name("string"), Type::Str);
+    assert_eq!(Type::from_name("long[]").to_string(), "long[]");
+    assert!(Type::Long.casts_to(&Type::Int) && !Type::Long.casts_to(&Type::Char));
+    assert!(Type::UInt.casts_to(&Type::Long) && !Type::Char.casts_to(&Type::UInt));
+    assert_eq!(Type::from_name("int[][]"), Type::Array(Box::new(Type::Array(Box::new(Type::Int)))));
+    assert_eq!(Type::from_name("Foo"), Type::Struct("Foo".to_string()));
+    assert_eq!(Type::from_name("dec[]").to_string(), "dec[]");
+    assert_eq!(Type::Str.element(), Some(Type::Char));
+}
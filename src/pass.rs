@@ -0,0 +1,175 @@
+use crate::parser::ast::Node;
+use crate::parser::ast::Expr;
+use crate::parser::lexer::error::Error;
+
+/// The ast handed to passes
+/// Expressions carry their types once the semantic pass is done
+pub type TypedAst = Vec<Box<Node>>;
+
+/// A custom pass that runs over the ast before code generation
+/// # Example
+/// ```ignore
+/// struct NoWrite;
+/// impl CompilerPass for NoWrite {
+///     fn name(&self) -> &str { "no-write" }
+///     fn run(&mut self, ast: &mut TypedAst, diagnostics: &mut Diagnostics) { ... }
+/// }
+/// driver.register_pass(Box::new(NoWrite));
+/// ```
+pub trait CompilerPass {
+    /// Name of the pass
+    fn name(&self) -> &str;
+
+    /// Runs the pass, reporting problems through "diagnostics"
+    fn run(&mut self, ast: &mut TypedAst, diagnostics: &mut Diagnostics);
+}
+
+/// Stores the errors reported by passes
+pub struct Diagnostics {
+    /// Errors reported so far
+    pub errors: Vec<Error>,
+}
+
+impl Default for Diagnostics {
+    fn default() -> Diagnostics {
+        Diagnostics::new()
+    }
+}
+
+impl Diagnostics {
+    /// Constructs an empty list of diagnostics
+    pub fn new() -> Diagnostics {
+        Diagnostics {errors: Vec::new()}
+    }
+
+    /// Adds an error
+    pub fn push(&mut self, error: Error) {
+        self.errors.push(error);
+    }
+}
+
+/// Walks the ast, visiting every node and expression
+/// Override a method to act on a node, and call the matching 'walk_' function
+/// to keep visiting its children
+pub trait Visitor {
+    fn visit_node(&mut self, node: &mut Node) {
+        walk_node(self, node);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr(self, expr);
+    }
+}
+
+/// Visits the children of a node
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &mut Node) {
+    match node {
+        Node::Let {expr, ..} => visitor.visit_expr(expr),
+        Node::FuncDecl {body, ..} => visitor.visit_node(body),
+        Node::Ret {expr, ..} => visitor.visit_expr(expr),
+        Node::If {cond, body, else_body, ..} => {
+            visitor.visit_expr(cond);
+            visitor.visit_node(body);
+            if let Some(e) = else_body {
+                visitor.visit_node(e);
+            }
+        },
+        Node::Assign {id, expr, ..} => {
+            visitor.visit_expr(id);
+            visitor.visit_expr(expr);
+        },
+        Node::FuncCall {args, ..} => {
+            for arg in args.iter_mut() {
+                visitor.visit_expr(arg);
+            }
+        },
+        Node::Block {statements} => {
+            for statement in statements.iter_mut() {
+                visitor.visit_node(statement);
+            }
+        },
+        Node::While {cond, body, ..} => {
+            visitor.visit_expr(cond);
+            visitor.visit_node(body);
+        },
+        _ => {}
+    }
+}
+
+/// Visits the children of an expression
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Array {values, ..} => {
+            for value in values.iter_mut() {
+                visitor.visit_expr(value);
+            }
+        },
+        Expr::IndexedValue {src, index, ..} => {
+            visitor.visit_expr(src);
+            visitor.visit_expr(index);
+        },
+        Expr::BinaryOperator {left, right, ..} => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        },
+        Expr::UnaryOperator {child, ..} => visitor.visit_expr(child),
+        Expr::Cast {expr, ..} => visitor.visit_expr(expr),
+        Expr::NewStruct {fields, ..} => {
+            for field in fields.iter_mut() {
+                visitor.visit_expr(field);
+            }
+        },
+        Expr::StructDot {id, ..} => visitor.visit_expr(id),
+        Expr::FuncCall {args, ..} => {
+            for arg in args.iter_mut() {
+                visitor.visit_expr(arg);
+            }
+        },
+        Expr::If {cond, body, else_body, ..} => {
+            visitor.visit_expr(cond);
+            visitor.visit_expr(body);
+            visitor.visit_expr(else_body);
+        },
+        _ => {}
+    }
+}
+
+#[test]
+fn test_visitor() {
+    struct CountInts(usize);
+    impl Visitor for CountInts {
+        fn visit_expr(&mut self, expr: &mut Expr) {
+            if let Expr::Int(_) = expr {
+                self.0 += 1;
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    use crate::parser::lexer::token::Token;
+    use crate::parser::lexer::token::Span;
+    use crate::parser::lexer::token::TokenType;
+    use crate::parser::lexer::source::SourceMap;
+
+    let token = Token {typ: TokenType::Id, value: "a".to_string(), lineno: 1, col: 0, len: 1, source: std::rc::Rc::new(SourceMap::new("", "let a = 1 + 2;"))};
+    let mut node = Node::Let {
+        id: "a".to_string(),
+        typ: crate::parser::types::Type::Int,
+        expr: Expr::BinaryOperator {
+            oper: "+".to_string(),
+            left: Box::new(Expr::Int("1".to_string())),
+            right: Box::new(Expr::Int("2".to_string())),
+            token: token.clone(),
+            span: Span::token(&token)
+        },
+        gen_id: "%a.0".to_string(),
+        mutable: false,
+        line: 1,
+        token: token.clone(),
+        type_span: None,
+        span: Span::token(&token)
+    };
+    let mut counter = CountInts(0);
+    counter.visit_node(&mut node);
+    assert_eq!(counter.0, 2);
+}
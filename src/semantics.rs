@@ -0,0 +1,1400 @@
+use crate::parser::ast::Node;
+use crate::parser::ast::Expr;
+use crate::parser::generator;
+use crate::parser::lexer::error::error;
+use crate::parser::lexer::error::error_at;
+use crate::parser::lexer::error::warning;
+use crate::parser::lexer::error::warning_at;
+use crate::parser::lexer::error::Error;
+use crate::parser::lexer::error::ErrorType;
+use crate::parser::lexer::token::Token;
+use crate::parser::lexer::token::Span;
+use crate::parser::symbol::SymbolController;
+use crate::parser::symbol::SymbolType;
+use crate::parser::symbol::Scope;
+use crate::parser::symbol::FuncSymbol;
+use crate::parser::symbol::VarSymbol;
+use crate::parser::symbol::merge_init_states;
+use crate::parser::symbol::did_you_mean;
+use crate::parser::types::Type;
+use crate::fold::ConstantFolding;
+use crate::pass::CompilerPass;
+use crate::pass::Diagnostics;
+use crate::pass::TypedAst;
+use crate::pass::Visitor;
+use crate::pass::walk_expr;
+use crate::pass::walk_node;
+
+/// Suggests how to make a variable changeable
+/// Parameters have no token, and can only be changed when they are 'ref'
+fn immutable_help(var: &VarSymbol) -> String {
+    if var.token.is_none() {
+        return format!("Make it a 'ref' parameter with 'ref {}' to be able to change it", var.id);
+    }
+    return format!("Declare it with 'var {}' to be able to change it", var.id);
+}
+
+/// Creates a mismatched types error for an expression that should have another
+/// type
+/// When both types are numbers, the error says so and suggests an 'as' cast
+fn mismatch(span: &Span, note: &str, expr: &Expr, to: &Type) -> Error {
+    let (typ, cast) = match expr.cast_suggestion(to) {
+        Some(cast) => (ErrorType::MismatchedNumbers, Some(cast)),
+        None => (ErrorType::MismatchedTypes, None)
+    };
+    let mut err = error_at(typ, span);
+    err.note(note);
+    if let Some((cast_note, help)) = cast {
+        err.note(cast_note.as_str()).help(help.as_str());
+
+        // A value that is a single token can be converted right after it
+        let single = match expr {
+            Expr::Id(id, ..) => *id == span.start.value,
+            Expr::Int(i) | Expr::Dec(i) => *i == span.start.value,
+            _ => false
+        };
+        if single && span.start == span.end {
+            err.fix_insert_after(&span.end, format!(" as {}", to).as_str(), format!("Convert it to '{}'", to).as_str());
+        }
+    }
+    return err;
+}
+
+/// Checks the finished ast, collecting every problem instead of stopping at
+/// the first one
+/// The parser only builds the trees, so names are looked up and types are
+/// found and checked here
+/// A type that can't be found is Error, and checks skip it so that one
+/// mistake is only reported once
+pub struct Semantics {
+    /// Identifier and return type of the function being checked
+    function: Option<(String, Type)>,
+
+    /// Problems found so far
+    errors: Vec<Error>,
+
+    /// Variables, functions and structs in scope
+    symtable: SymbolController,
+
+    /// Number of loops around the current statement
+    loops: usize,
+
+    /// Structs declared at the top level, so they can be used before their
+    /// declaration
+    structs: Vec<String>,
+}
+
+impl Default for Semantics {
+    fn default() -> Semantics {
+        Semantics::new()
+    }
+}
+
+impl Semantics {
+    /// Constructs a new semantic analyzer
+    pub fn new() -> Semantics {
+        let mut symtable = SymbolController {current: Scope {parent: None, children: Vec::new(), var_symbols: Vec::new(), func_symbols: Vec::new(), struct_symbols: Vec::new()}};
+        symtable.add_builtins();
+        Semantics {function: None, errors: Vec::new(), symtable: symtable, loops: 0, structs: Vec::new()}
+    }
+
+    /// Declares the top level structs first, so the order they are declared in
+    /// doesn't matter
+    fn declare_structs(&mut self, ast: &TypedAst) {
+        for node in ast.iter() {
+            if let Node::Struct {id, ..} = &**node {
+                self.structs.push(id.clone());
+            }
+        }
+        for node in ast.iter() {
+            if let Node::Struct {id, fields, token, ..} = &**node {
+                self.declare_struct(id, fields, token);
+            }
+        }
+    }
+
+    /// Adds a struct to the current scope
+    /// A struct can't contain itself, even through other structs
+    fn declare_struct(&mut self, id: &str, fields: &[(String, Type)], token: &Token) {
+        let types: Vec<Type> = fields.iter().map(|f| f.1.clone()).collect();
+        if let Some(chain) = self.symtable.find_struct_cycle(id.to_string(), &types) {
+            let mut err = error(ErrorType::CyclicStruct, token);
+            err.note(format!("'{}' contains {}, so it would have an infinite size", id, chain.iter().map(|c| format!("'{}'", c)).collect::<Vec<String>>().join(", which contains ")).as_str())
+                .help(format!("Remove one of the fields that leads back to '{}'", id).as_str());
+            self.errors.push(err);
+            return;
+        }
+        if let Err(err) = self.symtable.add_struct_symbol(id.to_string(), fields.to_vec(), Some(token.clone())) {
+            self.errors.push(err);
+        }
+    }
+
+    /// Checks that the structs in a written type exist
+    /// Gives false if one doesn't
+    fn check_type(&mut self, typ: &Type, span: &Span) -> bool {
+        match typ {
+            Type::Array(element) => return self.check_type(element, span),
+            Type::Struct(id) => {
+                if self.structs.contains(id) || self.symtable.find_global_struct(id.clone()).is_some() {
+                    return true;
+                }
+
+                // A misspelled type is more likely than a missing struct
+                let mut names: Vec<String> = ["int", "long", "uint", "string", "char", "bool", "dec"].iter().map(|t| t.to_string()).collect();
+                names.extend(self.structs.iter().cloned());
+                match did_you_mean(id.as_str(), &names) {
+                    Some(name) => {
+                        let mut err = error(ErrorType::UndefinedSymbol, &span.start);
+                        err.note(format!("'{}' isn't a type", id).as_str())
+                            .help(format!("Perhaps you meant '{}'", name).as_str())
+                            .fix_replace(&span.start, name.as_str(), format!("Change it to '{}'", name).as_str());
+                        self.errors.push(err);
+                    },
+                    None => if let Err(err) = self.symtable.find_global_struct_error(id.clone(), &span.start) {
+                        self.errors.push(err);
+                    }
+                }
+                return false;
+            },
+            _ => return true
+        }
+    }
+
+    /// Returns the size and alignment in bytes of a type
+    fn layout_of(&self, typ: &Type) -> (usize, usize) {
+        match typ {
+            Type::Int | Type::UInt   => (4, 4),
+            Type::Long | Type::Dec   => (8, 8),
+            Type::Bool | Type::Char  => (1, 1),
+            Type::Str                => (16, 8),
+            Type::Array(_)           => (16, 8),
+            Type::Void | Type::Error => (0, 1),
+            Type::Struct(id) => {
+                // Lay the fields out one after another, padding each to its
+                // alignment
+                let fields = self.symtable.find_global_struct(id.clone()).map(|s| s.arg_types).unwrap_or_default();
+                let mut size: usize = 0;
+                let mut align = 1;
+                for field in fields.iter() {
+                    let (field_size, field_align) = self.layout_of(field);
+                    size = size.div_ceil(field_align) * field_align + field_size;
+                    align = std::cmp::max(align, field_align);
+                }
+                (size.div_ceil(align) * align, align)
+            }
+        }
+    }
+
+    /// Checks the condition of an 'if' or 'while', which has to be a bool
+    fn condition(&mut self, cond: &mut Expr, span: &Span, keyword: &str) {
+        self.visit_expr(cond);
+        let typ = cond.validate();
+        if typ != Type::Bool && typ != Type::Error {
+            let mut err = error(ErrorType::MismatchedTypes, &span.start);
+            err.note(format!("The condition of this '{}' is '{}', but it has to be a 'bool'", keyword, typ).as_str());
+            if typ == Type::Int {
+                err.help("Compare it to get a bool, like 'x != 0'");
+            }
+            self.errors.push(err);
+        }
+    }
+
+    /// Checks that a variable has a value on every path to where it is used
+    fn check_init(&mut self, symbol: &VarSymbol, token: &Token) {
+        if !symbol.uninit {
+            return;
+        }
+        let mut err = error(ErrorType::UninitializedVariable, token);
+        if let Some(decl) = &symbol.token {
+            err.label(decl, "declared here without a value");
+        }
+        if symbol.assigned {
+            err.note(format!("'{}' is only given a value on some of the paths that lead here", symbol.id).as_str());
+        } else {
+            err.note(format!("'{}' isn't given a value before this", symbol.id).as_str());
+        }
+        err.help(format!("Give '{}' a value on every path before using it", symbol.id).as_str());
+        self.errors.push(err);
+    }
+
+    /// Records a call to a function, unless it is a call from the function's
+    /// own body, which doesn't make the function used
+    fn record_call(&mut self, id: String) {
+        if self.function.as_ref().map(|f| &f.0) != Some(&id) {
+            self.symtable.call_func(id);
+        }
+
+        // The function might read variables from outside of it
+        self.symtable.clear_stores();
+    }
+
+    /// Finds the variable that an expression like 'a.b[0]' is stored in
+    fn root_variable(&self, expr: &Expr) -> Option<VarSymbol> {
+        match expr {
+            Expr::Id(id, ..) => self.symtable.find_global_var(id.clone()),
+            Expr::StructDot {id, ..} => self.root_variable(id),
+            Expr::IndexedValue {src, ..} => self.root_variable(src),
+            _ => None
+        }
+    }
+
+    /// Warns about the variables in the current scope whose last value is
+    /// never read
+    fn warn_unread(&mut self) {
+        for (id, token) in self.symtable.unread_stores() {
+            let mut warn = warning(ErrorType::DeadStore, &token);
+            warn.note(format!("'{}' isn't read again before the end of its block", id).as_str())
+                .help("Remove this assignment, or use the value");
+            self.errors.push(warn);
+        }
+    }
+
+    /// Warns about the functions in the current scope that are never called
+    fn warn_uncalled(&mut self) {
+        for sym in self.symtable.uncalled_funcs() {
+            if sym.id == "main" {
+                continue;
+            }
+            let mut warn = warning(ErrorType::UnusedFunction, &sym.token.unwrap());
+            warn.note(format!("'{}' is declared here, but nothing calls it", sym.id).as_str())
+                .help("Remove the function, or call it");
+            self.errors.push(warn);
+        }
+    }
+
+    /// Checks the arguments of a call against the parameters of the function
+    /// Arguments passed to 'ref' parameters must be variables of the same type
+    fn call_arguments(&mut self, symbol: &FuncSymbol, args: &mut [Box<Expr>], spans: &[Span], span: &Span) {
+        for (i, arg) in args.iter_mut().enumerate() {
+            self.visit_expr(arg);
+
+            // Check the argument against a 'ref' parameter
+            if symbol.arg_refs.get(i) == Some(&true) {
+                let is_variable = matches!(**arg, Expr::Id(..) | Expr::StructDot {..} | Expr::IndexedValue {..});
+                if !is_variable {
+                    let mut err = error_at(ErrorType::RefNotVariable, &spans[i]);
+                    err.note(format!("Argument {} of '{}' is a 'ref' parameter and needs a variable", i + 1, symbol.id).as_str())
+                        .help("Store the value in a variable with 'let' and pass that instead");
+                    self.errors.push(err);
+                }
+                if let Some(var) = self.root_variable(arg) {
+                    if !var.mutable {
+                        let mut err = error(ErrorType::ImmutableAssign, &spans[i].start);
+                        err.note(format!("'{}' is passed to a 'ref' parameter, which can change it", var.id).as_str())
+                            .help(immutable_help(&var).as_str());
+                        self.errors.push(err);
+                    }
+                }
+            }
+
+            // Every argument has to have the type of its parameter
+            if let Some(typ) = symbol.arg_types.get(i) {
+                let found = arg.validate();
+                if found != *typ && found != Type::Error && *typ != Type::Error {
+                    let note = format!("Argument {} of '{}' expects '{}', found '{}'", i + 1, symbol.id, typ, found);
+                    self.errors.push(mismatch(&spans[i], note.as_str(), arg, typ));
+                }
+            }
+        }
+
+        // The call has to give a value for every parameter
+        if args.len() != symbol.arg_types.len() {
+            let plural = if symbol.arg_types.len() == 1 {"argument"} else {"arguments"};
+            let were = if args.len() == 1 {"was"} else {"were"};
+            let mut err = error(ErrorType::ArgumentCount, &span.end);
+            err.note(format!("'{}' takes {} {}, but {} {} given", symbol.id, symbol.arg_types.len(), plural, args.len(), were).as_str());
+            self.errors.push(err);
+        }
+    }
+
+    /// Checks a call used as a value
+    /// 'typeof' is resolved right here, so it gives back the string to put in
+    /// place of the call
+    fn call_expression(&mut self, expr: &mut Expr) -> Option<Expr> {
+        let (id, typ, args, span, arg_spans) = match expr {
+            Expr::FuncCall {id, typ, args, span, arg_spans} => (id, typ, args, span, arg_spans),
+            _ => return None
+        };
+        match id.as_str() {
+            "typeof" => {
+                // The expression is only looked at for its type, so it never
+                // runs
+                self.visit_expr(&mut args[0]);
+                return Some(Expr::Str(args[0].validate().to_string()));
+            },
+            "len" => {
+                for arg in args.iter_mut() {
+                    self.visit_expr(arg);
+                }
+                *typ = Type::Int;
+                let found = args.first().map(|a| a.validate()).unwrap_or(Type::Void);
+                let has_length = found == Type::Str || matches!(found, Type::Array(_));
+                if found != Type::Error && (args.len() != 1 || !has_length) {
+                    let mut err = error(ErrorType::MismatchedTypes, arg_spans.first().map_or(&span.end, |s| &s.start));
+                    err.note("'len' takes a single 'string' or array");
+                    self.errors.push(err);
+                }
+            },
+            "to_string" => {
+                for arg in args.iter_mut() {
+                    self.visit_expr(arg);
+                }
+                *typ = Type::Str;
+                let found = args.first().map(|a| a.validate()).unwrap_or(Type::Void);
+                if found != Type::Error && (args.len() != 1 || ![Type::Int, Type::Long, Type::UInt, Type::Dec, Type::Bool, Type::Char].contains(&found)) {
+                    let mut err = error(ErrorType::MismatchedTypes, arg_spans.first().map_or(&span.end, |s| &s.start));
+                    err.note("'to_string' takes a single 'int', 'long', 'uint', 'dec', 'bool' or 'char'");
+                    self.errors.push(err);
+                }
+            },
+            _ => {
+                let symbol = match self.symtable.find_global_func_error(id.clone(), &span.start) {
+                    Ok(symbol) => symbol,
+                    Err(err) => {
+                        self.errors.push(err);
+                        for arg in args.iter_mut() {
+                            self.visit_expr(arg);
+                        }
+                        return None;
+                    }
+                };
+                self.record_call(symbol.id.clone());
+
+                // Calls used as values need something to be returned
+                if symbol.typ == Type::Void {
+                    let mut err = error(ErrorType::VoidValue, &span.start);
+                    err.note(format!("'{}' doesn't return anything, so its call can't be used as a value", symbol.id).as_str())
+                        .help(format!("Call it on its own line, or declare '{}' with a return type", symbol.id).as_str());
+                    self.errors.push(err);
+                } else {
+                    *typ = symbol.typ.clone();
+                }
+                self.call_arguments(&symbol, args, arg_spans, span);
+                *id = symbol.gen_id;
+            }
+        }
+        return None;
+    }
+
+    /// Checks a binary operator whose sides are already checked
+    /// Comparisons of string literals are resolved right here, so checks
+    /// like 'typeof(x) == "int"' work, and the result is given back to put in
+    /// place of the operator
+    fn binary(&mut self, expr: &Expr) -> Option<Expr> {
+        let (oper, left, right, token, span) = match expr {
+            Expr::BinaryOperator {oper, left, right, token, span} => (oper, left, right, token, span),
+            _ => return None
+        };
+        if left.validate() == Type::Error || right.validate() == Type::Error {
+            return None;
+        }
+        if expr.validate() != Type::Error {
+            // Dividing by a constant zero would only fail once the program runs
+            if oper == "/" || oper == "%" {
+                let mut divisor = (**right).clone();
+                ConstantFolding.visit_expr(&mut divisor);
+                let zero = match &divisor {
+                    Expr::Int(i) => i.parse::<i32>() == Ok(0),
+                    Expr::Dec(d) => generator::dec_constant(d) == Some(0.0),
+                    _ => false
+                };
+                if zero {
+                    let mut err = error(ErrorType::DivisionByZero, token);
+                    err.note(format!("The right side of this '{}' is always 0", oper).as_str())
+                        .help(format!("Put a number other than 0 on the right side of the '{}'", oper).as_str());
+                    self.errors.push(err);
+                }
+            }
+
+            // A char holds 256 values, so an int that is made into one by
+            // arithmetic can wrap around
+            let arithmetic = ["+", "-", "*"].contains(&oper.as_str());
+            if arithmetic && left.validate() == Type::Char && right.validate() == Type::Int {
+                let mut amount = (**right).clone();
+                ConstantFolding.visit_expr(&mut amount);
+                let mut warn = warning_at(ErrorType::CharNarrowing, span);
+                let amount = match &amount {
+                    Expr::Int(i) => i.parse::<i64>().ok(),
+                    _ => None
+                };
+                match (&**left, amount) {
+                    (Expr::Chr(c), Some(i)) => {
+                        let value = match oper.as_str() {
+                            "+" => *c as i64 + i,
+                            "-" => *c as i64 - i,
+                            "*" => *c as i64 * i,
+                            _ => unreachable!("only arithmetic narrows")
+                        };
+                        if !(0..=255).contains(&value) {
+                            warn.note(format!("{:?} {} {} is {}, which wraps around to {}", c, oper, i, value, value.rem_euclid(256)).as_str());
+                        } else {
+                            warn.note(format!("{:?} {} {} is {}", c, oper, i, value).as_str());
+                        }
+                    },
+                    (_, Some(i)) => {
+                        warn.note(format!("The int {} is used as a char", i).as_str());
+                    },
+                    _ => {
+                        warn.note("The int on the right is used as a char");
+                    }
+                }
+                warn.note("A char only holds values from 0 to 255, anything else wraps around")
+                    .help("Convert the int with 'as char' if this is wanted, or put the int first to get an int");
+                self.errors.push(warn);
+            }
+
+            if let (Expr::Str(l), Expr::Str(r)) = (&**left, &**right) {
+                match oper.as_str() {
+                    "==" => return Some(Expr::Bool(l == r)),
+                    "!=" => return Some(Expr::Bool(l != r)),
+                    _ => {}
+                }
+            }
+            return None;
+        }
+
+        // Mixed numbers can be fixed by converting the narrower one
+        let rank = |t: Type| [Type::Char, Type::Int, Type::UInt, Type::Long, Type::Dec].iter().position(|n| *n == t);
+        let (narrow, wide) = if rank(left.validate()) < rank(right.validate()) {(left, right)} else {(right, left)};
+        let note = format!("The left side is '{}' and the right side is '{}', which '{}' can't combine", left.validate(), right.validate(), oper);
+        let mut err = mismatch(span, note.as_str(), narrow, &wide.validate());
+        err.label(token, "this operator");
+
+        // Point out the usual mix-ups of quotes
+        if oper == "==" || oper == "!=" {
+            for (side, other) in [(left, right), (right, left)] {
+                if let Expr::Str(s) = &**other {
+                    let contents = s.as_str();
+                    if side.validate() == Type::Char && contents.chars().count() == 1 {
+                        err.note("A char can't be compared to a string");
+                        err.help(format!("Use single quotes for a char: '{}'", contents).as_str());
+                    } else if [Type::Int, Type::Long, Type::UInt, Type::Dec].contains(&side.validate()) && !contents.is_empty() && contents.parse::<f64>().is_ok() {
+                        let article = if side.validate() == Type::Int {"An"} else {"A"};
+                        err.note(format!("{} {} can't be compared to a string", article, side.validate()).as_str());
+                        err.help(format!("Take away the quotes: {}", contents).as_str());
+                    }
+                }
+            }
+        }
+        self.errors.push(err);
+        return None;
+    }
+
+    /// Finds the field that a '.' picks out of a struct
+    /// 'read' is false for the target of an assignment, whose fields don't
+    /// need a value yet
+    fn struct_dot(&mut self, expr: &mut Expr, read: bool) {
+        let (id, id2, typ, field_num, token) = match expr {
+            Expr::StructDot {id, id2, typ, field_num, token} => (id, id2, typ, field_num, token),
+            _ => return
+        };
+
+        // The fields of a struct that is given a value field by field can be
+        // read once they have one, before the struct as a whole does
+        let by_field = match &**id {
+            Expr::Id(var, ..) => self.symtable.find_global_var(var.clone()).is_some_and(|v| !v.uninit_fields.is_empty()),
+            _ => false
+        };
+        if read && !by_field {
+            self.visit_expr(id);
+        } else {
+            self.visit_target(id);
+            if let (true, Expr::Id(var, ..)) = (read, &**id) {
+                self.symtable.record_read(var.clone());
+            }
+        }
+        if id.validate() == Type::Error {
+            return;
+        }
+        let symbol = match self.symtable.find_global_struct_error(id.validate().to_string(), token) {
+            Ok(symbol) => symbol,
+            Err(err) => {
+                self.errors.push(err);
+                return;
+            }
+        };
+        match symbol.field_ids.iter().position(|f| f == id2) {
+            Some(num) => {
+                *typ = symbol.arg_types[num].clone();
+                *field_num = num as i32;
+            },
+            None => {
+                let mut err = error(ErrorType::UndefinedField, token);
+                err.note(format!("Struct '{}' has no field '{}'", symbol.id, id2).as_str());
+                self.errors.push(err);
+                return;
+            }
+        }
+
+        // Reading a field that was never given a value is an error
+        if let (true, Expr::Id(var, ..)) = (read, &**id) {
+            let uninit = self.symtable.find_global_var(var.clone()).is_some_and(|v| v.uninit_fields.contains(id2));
+            if uninit {
+                let mut err = error(ErrorType::UninitializedField, token);
+                err.note(format!("Field '{}' of '{}' is never given a value before this", id2, var).as_str())
+                    .help(format!("Assign '{}.{}' before reading it", var, id2).as_str());
+                self.errors.push(err);
+            }
+        }
+    }
+
+    /// Finds the element type that an index picks out of an array or a string
+    /// 'read' is false for the target of an assignment
+    fn index(&mut self, expr: &mut Expr, read: bool) {
+        let (src, index, new_typ, token, span) = match expr {
+            Expr::IndexedValue {src, index, new_typ, token, span} => (src, index, new_typ, token, span),
+            _ => return
+        };
+        if read {
+            self.visit_expr(src);
+        } else {
+            self.visit_target(src);
+        }
+        self.visit_expr(index);
+
+        // Elements are counted with whole numbers
+        let found = index.validate();
+        if found != Type::Int && found != Type::UInt && found != Type::Error {
+            let note = format!("An index has to be an 'int' or a 'uint', but this is '{}'", found);
+            let mut err = mismatch(span, note.as_str(), index, &Type::Int);
+            if found == Type::Str {
+                err.help("Use 'parse_int' to read the index from a string");
+            }
+            self.errors.push(err);
+        }
+        let typ = src.validate();
+        if typ == Type::Error {
+            return;
+        }
+
+        // Elements of arrays can be changed, but strings are constant
+        if !read && typ == Type::Str {
+            let mut err = error(ErrorType::ImmutableAssign, token);
+            err.note("The characters of a string can't be changed")
+                .help("Build a new string instead");
+            self.errors.push(err);
+            return;
+        }
+        match typ.element() {
+            Some(element) => *new_typ = element,
+            None => {
+                let mut err = error(ErrorType::MismatchedTypes, token);
+                err.note(format!("A '{}' can't be indexed", typ).as_str());
+                self.errors.push(err);
+            }
+        }
+    }
+
+    /// Checks what an assignment stores into, without reading the variable
+    /// itself
+    fn visit_target(&mut self, target: &mut Expr) {
+        match target {
+            Expr::Id(id, typ, gen_id, _) => {
+                if let Some(symbol) = self.symtable.find_global_var(id.clone()) {
+                    *typ = symbol.typ;
+                    *gen_id = symbol.gen_id;
+                }
+            },
+            Expr::StructDot {..} => self.struct_dot(target, false),
+            Expr::IndexedValue {..} => self.index(target, false),
+            _ => self.visit_expr(target)
+        }
+    }
+
+    /// Checks an assignment to a variable, a field or an element
+    fn assign(&mut self, target: &mut Expr, expr: &mut Expr, target_span: &Span, span: &Span) {
+        let name = target_span.start.value.clone();
+        let symbol = match self.symtable.find_global_var_error(name.clone(), &target_span.start) {
+            Ok(symbol) => symbol,
+            Err(err) => {
+                self.errors.push(err);
+                self.visit_expr(expr);
+                return;
+            }
+        };
+
+        // A 'let' declared without a value can be given one once, outside of
+        // loops that would give it another
+        let whole = matches!(target, Expr::Id(..));
+        let in_loop = self.loops > symbol.loop_depth;
+        if !symbol.mutable && (!whole || symbol.assigned || in_loop) {
+            let mut err = error(ErrorType::ImmutableAssign, &target_span.start);
+            if let Some(decl) = &symbol.token {
+                err.label(decl, "declared here");
+            }
+            if symbol.token.is_none() {
+                err.note(format!("'{}' is a parameter without 'ref', so it can't be changed", name).as_str());
+            } else if whole && !symbol.assigned {
+                err.note(format!("'{}' is declared outside of this loop, so it would be given a value more than once", name).as_str());
+            } else {
+                err.note(format!("'{}' was declared with 'let', so it can only be given a value once", name).as_str());
+            }
+            err.help(immutable_help(&symbol).as_str());
+            self.errors.push(err);
+            self.visit_expr(expr);
+            return;
+        }
+
+        // Giving a field of a struct declared without a value its value doesn't
+        // read the struct
+        let by_field = !symbol.uninit_fields.is_empty() && matches!(target, Expr::StructDot {id, ..} if matches!(**id, Expr::Id(..)));
+        if !whole && !by_field {
+            self.check_init(&symbol, &target_span.start);
+            self.symtable.record_read(name.clone());
+        }
+        self.visit_target(target);
+        self.visit_expr(expr);
+
+        // The value has to fit in the variable
+        let (to, found) = (target.validate(), expr.validate());
+        if found != to && found != Type::Error && to != Type::Error {
+            let note = format!("'{}' is '{}', but it is given '{}'", target_span.text(), to, found);
+            let mut err = mismatch(span, note.as_str(), expr, &to);
+            if let (true, Some(decl)) = (whole, &symbol.token) {
+                err.label(decl, format!("declared as '{}' here", to).as_str());
+            }
+            self.errors.push(err);
+            return;
+        }
+
+        if whole {
+            self.symtable.give_value(name.clone());
+            self.symtable.set_uninit_fields(name.clone(), Vec::new());
+
+            // The value it had before is lost if nothing read it
+            if let Some(old) = self.symtable.record_store(name.clone(), target_span.start.clone()) {
+                let mut warn = warning(ErrorType::DeadStore, &old);
+                warn.label(&target_span.start, "replaced here")
+                    .note(format!("'{}' is given another value before this one is read", name).as_str())
+                    .help("Remove this assignment, or use the value before it is replaced");
+                self.errors.push(warn);
+            }
+        }
+
+        // Assigning a field directly gives it a value, and the struct has one
+        // once all of its fields do
+        if let Expr::StructDot {id, id2, ..} = target {
+            if let Expr::Id(var, ..) = &**id {
+                self.symtable.init_field(var.clone(), id2.clone());
+                if by_field && self.symtable.find_global_var(var.clone()).is_some_and(|v| v.uninit_fields.is_empty()) {
+                    self.symtable.give_value(var.clone());
+                }
+            }
+        }
+    }
+
+    /// Checks a call on its own line
+    /// Other than the builtins that take any arguments, the function has to be
+    /// declared
+    #[allow(clippy::vec_box)]
+    fn function_call(&mut self, id: &mut String, args: &mut Vec<Box<Expr>>, line: usize, span: &Span, arg_spans: &[Span]) {
+        if !["write", "writef", "assert"].contains(&id.as_str()) {
+            match self.symtable.find_global_func_error(id.clone(), &span.start) {
+                Ok(symbol) => {
+                    self.record_call(symbol.id.clone());
+                    self.call_arguments(&symbol, args, arg_spans, span);
+                    *id = symbol.gen_id;
+                },
+                Err(err) => {
+                    self.errors.push(err);
+                    for arg in args.iter_mut() {
+                        self.visit_expr(arg);
+                    }
+                }
+            }
+            return;
+        }
+        for arg in args.iter_mut() {
+            self.visit_expr(arg);
+        }
+
+        if id == "assert" {
+            if args.len() != 2 {
+                let were = if args.len() == 1 {"was"} else {"were"};
+                let mut err = error(ErrorType::ArgumentCount, &span.end);
+                err.note(format!("'assert' takes 2 arguments, but {} {} given", args.len(), were).as_str())
+                    .help("Use 'assert(condition, \"message\");'");
+                self.errors.push(err);
+                return;
+            }
+            let found = args[0].validate();
+            if found != Type::Bool && found != Type::Error {
+                let note = format!("The condition of 'assert' has to be a 'bool', but it is '{}'", found);
+                self.errors.push(mismatch(&arg_spans[0], note.as_str(), &args[0], &Type::Bool));
+            }
+            let message = args[1].validate();
+            if message != Type::Str && message != Type::Error {
+                let note = format!("The message of 'assert' has to be a 'string', but it is '{}'", message);
+                self.errors.push(mismatch(&arg_spans[1], note.as_str(), &args[1], &Type::Str));
+            }
+
+            // Pass the line number along so a failure can report it
+            args.push(Box::new(Expr::Int(line.to_string())));
+        }
+
+        if id == "writef" {
+            // The format has to be known at compile time to build the printf
+            // format out of it
+            let holes = match args.first().map(|a| &**a) {
+                Some(Expr::Str(s)) => s.matches("{}").count(),
+                Some(_) => {
+                    let mut err = error_at(ErrorType::FormatNotLiteral, &arg_spans[0]);
+                    err.note("'writef' takes a string literal format, followed by the values for it")
+                        .help("Use 'writef(\"x = {}\\n\", x);'");
+                    self.errors.push(err);
+                    return;
+                },
+                None => {
+                    let mut err = error(ErrorType::ArgumentCount, &span.end);
+                    err.note("'writef' takes a format, followed by the values for it")
+                        .help("Use 'writef(\"x = {}\\n\", x);'");
+                    self.errors.push(err);
+                    return;
+                }
+            };
+            if holes != args.len() - 1 {
+                let values = if args.len() - 1 == 1 {"value was"} else {"values were"};
+                let mut err = error(ErrorType::ArgumentCount, &span.end);
+                err.note(format!("The format has {} '{{}}', but {} {} given", holes, args.len() - 1, values).as_str());
+                self.errors.push(err);
+            }
+        }
+    }
+}
+
+impl CompilerPass for Semantics {
+    fn name(&self) -> &str {
+        "semantics"
+    }
+
+    fn run(&mut self, ast: &mut TypedAst, diagnostics: &mut Diagnostics) {
+        self.declare_structs(ast);
+        for node in ast.iter_mut() {
+            self.visit_node(node);
+        }
+        self.warn_uncalled();
+        for error in self.errors.drain(..) {
+            diagnostics.push(error);
+        }
+    }
+}
+
+impl Visitor for Semantics {
+    fn visit_node(&mut self, node: &mut Node) {
+        match node {
+            Node::Let {id, typ, expr, gen_id, mutable, token, type_span, span, ..} => {
+                if let Some(type_span) = type_span {
+                    if !self.check_type(typ, type_span) {
+                        *typ = Type::Error;
+                    }
+                }
+                self.visit_expr(expr);
+
+                // Without a written type, the variable has the type of its
+                // value
+                let found = expr.validate();
+                match type_span {
+                    None => *typ = found.clone(),
+                    Some(type_span) => if *expr != Expr::Non && found != *typ && found != Type::Error && *typ != Type::Error {
+                        let note = format!("'{}' is declared as '{}', but it is given '{}'", id, typ, found);
+                        let mut err = mismatch(span, note.as_str(), expr, typ);
+                        if type_span.start == type_span.end {
+                            err.fix_replace(&type_span.start, found.to_string().as_str(), format!("Declare '{}' as '{}'", id, found).as_str());
+                        }
+                        self.errors.push(err);
+                    }
+                }
+
+                // Reusing the name of a variable from an outer scope hides it
+                if !self.symtable.find(id.clone(), SymbolType::Var) {
+                    if let Some(outer) = self.symtable.find_global_var(id.clone()) {
+                        let mut warn = warning(ErrorType::ShadowedVariable, token);
+                        match &outer.token {
+                            Some(token) => warn.label(token, "the outer variable is declared here")
+                                .note(format!("'{}' is already declared, and is hidden until this block ends", id).as_str()),
+                            None => warn.note(format!("'{}' is already an argument, which is hidden until this block ends", id).as_str())
+                        };
+                        warn.help("Rename this variable if the outer one is still needed");
+                        self.errors.push(warn);
+                    }
+                }
+
+                if let Err(err) = self.symtable.add_var_symbol(id.clone(), typ.clone(), gen_id.clone(), *mutable, Some(token.clone())) {
+                    self.errors.push(err);
+                    return;
+                }
+                if *expr == Expr::Non {
+                    self.symtable.defer_init(id.clone(), self.loops);
+                } else {
+                    self.symtable.record_store(id.clone(), token.clone());
+                }
+
+                // A struct declared without a value, or made with an empty
+                // 'new', can be given one field by field
+                let fields = match &*expr {
+                    Expr::Non => Some(0),
+                    Expr::NewStruct {fields, ..} => Some(fields.len()),
+                    _ => None
+                };
+                if let (Some(fields), Type::Struct(struct_id)) = (fields, &*typ) {
+                    if let Some(symbol) = self.symtable.find_global_struct(struct_id.clone()) {
+                        if fields < symbol.field_ids.len() {
+                            self.symtable.set_uninit_fields(id.clone(), symbol.field_ids[fields..].to_vec());
+                        }
+                    }
+                }
+            },
+            Node::FuncDecl {id, typ, args, body, end, token, type_span, ..} => {
+                for (i, arg) in args.iter().enumerate() {
+                    if let Some(first) = args[..i].iter().find(|a| a.id == arg.id) {
+                        let mut err = error(ErrorType::DuplicateName, &arg.token);
+                        err.label(&first.token, "first declared here")
+                            .note(format!("Argument '{}' is already declared", arg.id).as_str())
+                            .help("Rename or remove one of the arguments");
+                        self.errors.push(err);
+                    }
+                }
+                for arg in args.iter_mut() {
+                    if !self.check_type(&arg.typ, &arg.type_span) {
+                        arg.typ = Type::Error;
+                    }
+                }
+                if let Some(type_span) = type_span {
+                    if !self.check_type(typ, type_span) {
+                        *typ = Type::Error;
+                    }
+                }
+
+                // The entry point takes nothing and can only return an exit code
+                if id == "main" && (!args.is_empty() || (*typ != Type::Void && *typ != Type::Int)) {
+                    let mut err = error(ErrorType::InvalidMain, token);
+                    err.note("'main' can't take any arguments and can only return 'int'")
+                        .help("Use 'func main() { ... }' or 'func main(): int { ... }'");
+                    self.errors.push(err);
+                }
+
+                // Add the function before the body so it can call itself
+                if let Err(err) = self.symtable.add_func_symbol(id.clone(), typ.clone(), args.iter().map(|a| (a.typ.clone(), a.is_ref)).collect(), Some(token.clone())) {
+                    self.errors.push(err);
+                }
+
+                // Loops around the declaration can't be left from inside of it
+                let loops = std::mem::take(&mut self.loops);
+                let enclosing = self.function.replace((id.clone(), typ.clone()));
+                self.symtable.clear_stores();
+
+                // The arguments live in the function's own scope
+                self.symtable.add_scope();
+                for arg in args.iter() {
+                    // Only 'ref' arguments can be assigned to
+                    // Arguments have no token, so they are never reported as
+                    // duplicates
+                    let _ = self.symtable.add_var_symbol(arg.id.clone(), arg.typ.clone(), arg.gen_id.clone(), arg.is_ref, None);
+                }
+                self.visit_node(body);
+                self.symtable.pop_scope();
+                self.loops = loops;
+                self.function = enclosing;
+                self.symtable.clear_stores();
+
+                // Functions that return a value can't reach their '}'
+                if *typ != Type::Void && *typ != Type::Error && !body.always_returns() {
+                    let mut e = error(ErrorType::MissingReturn, end);
+                    e.note(format!("'{}' returns '{}', but it can reach its end without a 'ret'", id, typ).as_str())
+                        .help("Add a 'ret' statement before this '}'");
+                    self.errors.push(e);
+                }
+            },
+            Node::Ret {expr, span} => {
+                // 'ref' arguments are read by the caller after the function
+                // ends
+                self.symtable.clear_stores();
+                self.visit_expr(expr);
+
+                // Outside of functions, 'ret' gives the exit code
+                let (id, typ) = self.function.clone().unwrap_or(("main".to_string(), Type::Int));
+                let found = if *expr == Expr::Non {Type::Void} else {expr.validate()};
+                if found == typ || found == Type::Error || typ == Type::Error || (self.function.is_none() && found == Type::Void) {
+                    return;
+                }
+                if found != Type::Void && typ != Type::Void {
+                    let note = format!("'{}' returns '{}', but this 'ret' gives '{}'", id, typ, found);
+                    self.errors.push(mismatch(span, note.as_str(), expr, &typ));
+                    return;
+                }
+                let mut e = error_at(ErrorType::MismatchedTypes, span);
+                if found == Type::Void {
+                    e.note(format!("'{}' returns '{}', but this 'ret' has no value", id, typ).as_str())
+                        .help(format!("Give the 'ret' a '{}' to return", typ).as_str());
+                } else {
+                    e.note(format!("'{}' doesn't return a value, but this 'ret' gives '{}'", id, found).as_str())
+                        .help(format!("Take away the value, or declare '{}' with ': {}'", id, found).as_str());
+                }
+                self.errors.push(e);
+            },
+            Node::If {cond, body, else_body, span, ..} => {
+                self.condition(cond, span, "if");
+                self.symtable.clear_stores();
+                let before = self.symtable.init_state();
+                self.visit_node(body);
+                let after_body = self.symtable.init_state();
+                self.symtable.set_init_state(&before);
+                self.symtable.clear_stores();
+                if let Some(else_body) = else_body {
+                    self.visit_node(else_body);
+                }
+                let after_else = self.symtable.init_state();
+
+                // Only the branches that can reach the end of the 'if' matter
+                // after it
+                let after = match (body.always_returns(), else_body.as_ref().is_some_and(|e| e.always_returns())) {
+                    (true, false) => after_else,
+                    (false, true) => after_body,
+                    _ => merge_init_states(&after_body, &after_else)
+                };
+                self.symtable.set_init_state(&after);
+                self.symtable.clear_stores();
+            },
+            Node::While {cond, body, span, ..} => {
+                self.condition(cond, span, "while");
+                self.loops += 1;
+                self.symtable.clear_stores();
+                let before = self.symtable.init_state();
+                self.visit_node(body);
+
+                // The body might not run at all
+                let after = merge_init_states(&before, &self.symtable.init_state());
+                self.symtable.set_init_state(&after);
+                self.symtable.clear_stores();
+                self.loops -= 1;
+            },
+            Node::Pause {token, ..} => {
+                self.symtable.clear_stores();
+                if self.loops == 0 {
+                    let mut err = error(ErrorType::PauseOutsideLoop, token);
+                    err.note(format!("'{}' can only be used inside of a 'while' loop", token.value).as_str());
+                    self.errors.push(err);
+                }
+            },
+            Node::Block {statements} => {
+                // Variables declared inside a block only live until its '}'
+                self.symtable.add_scope();
+                for stmt in statements.iter_mut() {
+                    self.visit_node(stmt);
+                }
+                self.warn_uncalled();
+                self.warn_unread();
+                self.symtable.pop_scope();
+            },
+            Node::Struct {id, fields, token, field_tokens, type_spans, ..} => {
+                for (i, field_token) in field_tokens.iter().enumerate() {
+                    if let Some(first) = field_tokens[..i].iter().find(|t| t.value == field_token.value) {
+                        let mut err = error(ErrorType::DuplicateName, field_token);
+                        err.label(first, "first declared here")
+                            .note(format!("Field '{}' is already declared", field_token.value).as_str())
+                            .help("Rename or remove one of the fields");
+                        self.errors.push(err);
+                    }
+                }
+                for (field, span) in fields.iter().zip(type_spans.iter()) {
+                    // A field of the struct's own type is reported as a cycle
+                    if field.1 != Type::Struct(id.clone()) {
+                        self.check_type(&field.1, span);
+                    }
+                }
+
+                // The top level structs are declared before anything else
+                if self.symtable.depth() > 0 {
+                    self.declare_struct(id, fields, token);
+                }
+            },
+            Node::Assign {id, expr, target, span, ..} => self.assign(id, expr, target, span),
+            Node::FuncCall {id, args, line, span, arg_spans} => self.function_call(id, args, *line, span, arg_spans),
+            _ => walk_node(self, node)
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Id(id, typ, gen_id, token) => {
+                match self.symtable.find_global_var_error(id.clone(), token) {
+                    Ok(symbol) => {
+                        self.check_init(&symbol, token);
+                        self.symtable.record_read(id.clone());
+                        *typ = symbol.typ;
+                        *gen_id = symbol.gen_id;
+                    },
+                    Err(err) => self.errors.push(err)
+                }
+            },
+            Expr::StructDot {..} => self.struct_dot(expr, true),
+            Expr::IndexedValue {..} => self.index(expr, true),
+            Expr::BinaryOperator {left, right, ..} => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+                if let Some(value) = self.binary(expr) {
+                    *expr = value;
+                }
+            },
+            Expr::UnaryOperator {oper, child, span} => {
+                self.visit_expr(child);
+                let typ = child.validate();
+                if oper == "-" && typ != Type::Error && ![Type::Int, Type::Long, Type::Dec].contains(&typ) {
+                    let mut err = error_at(ErrorType::MismatchedTypes, span);
+                    err.note(format!("A '{}' can't be negated", typ).as_str())
+                        .help("Only 'int', 'long' and 'dec' can be negated");
+                    self.errors.push(err);
+                }
+                if oper == "not" && typ != Type::Error && typ != Type::Bool {
+                    let mut err = error_at(ErrorType::MismatchedTypes, span);
+                    err.note(format!("'not' takes a 'bool', found '{}'", typ).as_str())
+                        .help("Compare the value to get a 'bool', like 'x != 0'");
+                    self.errors.push(err);
+                }
+            },
+            Expr::Cast {expr: child, typ, span} => {
+                self.visit_expr(child);
+                let from = child.validate();
+                if from != Type::Error && !from.casts_to(typ) {
+                    let mut err = error_at(ErrorType::MismatchedTypes, span);
+                    err.note(format!("A '{}' can't be converted to '{}'", from, typ).as_str());
+                    if from == Type::Str && (*typ == Type::Int || *typ == Type::Dec) {
+                        err.help(format!("Use 'parse_{}' to read a number from a string", typ).as_str());
+                    } else if *typ == Type::Str {
+                        err.help("Use 'to_string' to turn a value into a string");
+                    } else {
+                        err.help("Only 'int', 'long', 'uint' and 'dec', or 'char' and 'int', convert with 'as'");
+                    }
+                    self.errors.push(err);
+                }
+            },
+            Expr::If {cond, body, else_body, typ, token, span} => {
+                // Both branches must have the same type
+                self.condition(cond, span, "if");
+                self.visit_expr(body);
+                self.visit_expr(else_body);
+                let (found, other) = (body.validate(), else_body.validate());
+                if found != other && found != Type::Error && other != Type::Error {
+                    let mut err = error(ErrorType::MismatchedTypes, token);
+                    err.note(format!("The branches of this if have different types, '{}' and '{}'", found, other).as_str());
+                    self.errors.push(err);
+                }
+                *typ = found;
+            },
+            Expr::Array {values, typ, spans} => {
+                for value in values.iter_mut() {
+                    self.visit_expr(value);
+                }
+
+                // Every element must have the same type as the first one
+                let first = match values.first() {
+                    Some(first) => first.validate(),
+                    None => return
+                };
+                for (i, value) in values.iter().enumerate().skip(1) {
+                    let found = value.validate();
+                    if found != first && found != Type::Error && first != Type::Error {
+                        let mut err = error_at(ErrorType::MismatchedTypes, &spans[i]);
+                        err.note(format!("Element {} of this array is '{}', but the first element is '{}'", i + 1, found, first).as_str())
+                            .help("Every element of an array needs the same type");
+                        self.errors.push(err);
+                    }
+                }
+                *typ = Type::Array(Box::new(first));
+            },
+            Expr::NewStruct {id, fields, span, spans} => {
+                for field in fields.iter_mut() {
+                    self.visit_expr(field);
+                }
+                let symbol = match self.symtable.find_global_struct_error(id.clone(), &span.start) {
+                    Ok(symbol) => symbol,
+                    Err(err) => {
+                        self.errors.push(err);
+                        return;
+                    }
+                };
+
+                // Each value has to have the type of its field
+                for (i, field) in fields.iter().enumerate() {
+                    if let Some(typ) = symbol.arg_types.get(i) {
+                        let found = field.validate();
+                        if found != *typ && found != Type::Error {
+                            let note = format!("Field '{}' of '{}' is '{}', found '{}'", symbol.field_ids[i], id, typ, found);
+                            self.errors.push(mismatch(&spans[i], note.as_str(), field, typ));
+                        }
+                    }
+                }
+
+                // Either every field gets a value, or none do and they are
+                // assigned one by one afterwards
+                if !fields.is_empty() && fields.len() != symbol.arg_types.len() {
+                    let plural = if symbol.arg_types.len() == 1 {"field"} else {"fields"};
+                    let were = if fields.len() == 1 {"value was"} else {"values were"};
+                    let mut err = error(ErrorType::ArgumentCount, &span.end);
+                    err.note(format!("'{}' has {} {}, but {} {} given", id, symbol.arg_types.len(), plural, fields.len(), were).as_str())
+                        .help(format!("Give a value for every field, or use 'new {}()' and assign the fields afterwards", id).as_str());
+                    self.errors.push(err);
+                }
+            },
+            Expr::FuncCall {..} => {
+                if let Some(value) = self.call_expression(expr) {
+                    *expr = value;
+                }
+            },
+            Expr::SizeOf {typ, span} => {
+                if self.check_type(typ, span) {
+                    let size = self.layout_of(typ).0;
+                    *expr = Expr::Int(size.to_string());
+                }
+            },
+            _ => walk_expr(self, expr)
+        }
+    }
+}
+
+#[test]
+fn test_missing_return() {
+    use crate::parser::lexer::token::TokenType;
+    use crate::parser::lexer::source::SourceMap;
+    use std::rc::Rc;
+
+    let func = |id: &str, typ: Type, statements: Vec<Box<Node>>| Box::new(Node::FuncDecl {
+        id: id.to_string(),
+        gen_id: format!("gz_{}", id),
+        typ: typ,
+        args: Vec::new(),
+        body: Box::new(Node::Block {statements: statements}),
+        doc: None,
+        end: Token {typ: TokenType::RightBrace, value: "}".to_string(), lineno: 1, col: 0, len: 1, source: Rc::new(SourceMap::new("", "}"))},
+        line: 1,
+        token: Token {typ: TokenType::Id, value: id.to_string(), lineno: 1, col: 0, len: id.chars().count(), source: Rc::new(SourceMap::new("", id))},
+        type_span: None
+    });
+    let token = Token {typ: TokenType::Ret, value: "ret".to_string(), lineno: 1, col: 0, len: 3, source: Rc::new(SourceMap::new("", "ret 1;"))};
+    let ret = Box::new(Node::Ret {expr: Expr::Int("1".to_string()), span: Span::token(&token)});
+
+    // None of the functions are called, so only count the errors
+    let count = |mut ast: TypedAst| {
+        let mut diagnostics = Diagnostics::new();
+        Semantics::new().run(&mut ast, &mut diagnostics);
+        return diagnostics.errors.iter().filter(|e| !e.is_warning()).count();
+    };
+    assert_eq!(count(vec![func("f", Type::Int, Vec::new()), func("g", Type::Int, vec![ret.clone()]), func("h", Type::Void, Vec::new())]), 1);
+
+    // The value of a 'ret' has to match the return type
+    assert_eq!(count(vec![func("f", Type::Void, vec![ret.clone()]), func("g", Type::Str, vec![ret.clone()]), ret]), 2);
+}
+
+#[test]
+fn test_assert_condition() {
+    let errors = crate::driver::Driver::new().compile("assert(5, \"x\");\n".to_string()).unwrap_err();
+    assert_eq!(errors[0].code(), "E0006");
+    assert!(errors[0].to_json().contains("The condition of 'assert' has to be a 'bool', but it is 'int'"));
+    assert!(crate::driver::Driver::new().compile("assert(1 < 2, \"x\");\n".to_string()).is_ok());
+
+    // A missing message is diagnosed like a call to any other function
+    let errors = crate::driver::Driver::new().compile("assert(true);\n".to_string()).unwrap_err();
+    assert_eq!(errors[0].code(), "E0025");
+    assert!(errors[0].to_json().contains("'assert' takes 2 arguments, but 1 was given"));
+}
+
+#[test]
+fn test_char_narrowing_operators() {
+    use crate::parser::lexer::error::WarningGroup;
+    let mut driver = crate::driver::Driver::new();
+    driver.denied = vec![WarningGroup::Narrowing];
+    assert!(driver.compile("let c = 'a';\nif c < 100 {\n    write(c);\n}\n".to_string()).is_ok());
+    assert_eq!(driver.compile("let c = 'a' * 2;\n".to_string()).unwrap_err()[0].code(), "W0004");
+    assert_eq!(driver.compile("let c = 'a' + 300;\n".to_string()).unwrap_err()[0].code(), "W0004");
+    assert!(driver.compile("let c = 'a' + 300 as char;\nlet i = 300 + 'a';\n".to_string()).is_ok());
+}
+
+#[test]
+fn test_sizeof_string_field() {
+    // A string is a pointer and a length, so 16 bytes with the int padded
+    // after it
+    let code = "struct Named {\n    name: string,\n    age: int\n}\nfunc main(): int {\n    ret sizeof(Named);\n}\n";
+    let ir = crate::driver::Driver::new().compile(code.to_string()).unwrap();
+    assert!(ir.contains("define i32 @main() {\nentry:\n\tret i32 24\n}"));
+    if let Some((status, _)) = crate::build::run_program(code, "") {
+        assert_eq!(status, 24);
+    }
+}
+
+#[test]
+fn test_undefined_without_cascade() {
+    // 'z' still gets declared, so only the undefined 'x' is reported
+    let errors = crate::driver::Driver::new().compile("let z = x * 2;\nwrite(z);\n".to_string()).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_json().contains("Undefined symbol 'x'"));
+}
+
+#[test]
+fn test_forward_struct() {
+    let code = crate::driver::Driver::new().compile("struct A { b: B }\nstruct B { x: int }\n".to_string()).unwrap();
+    assert!(code.contains("%A = type {"));
+    assert!(code.contains("%B = type {"));
+}
+
+#[test]
+fn test_operand_types() {
+    let errors = crate::driver::Driver::new().compile("let s = \"a\" + 2.5;\n".to_string()).unwrap_err();
+    assert!(errors[0].to_json().contains("The left side is 'string' and the right side is 'dec', which '+' can't combine"));
+
+    // The underline reaches the closing quote of a string with escapes in it
+    let errors = crate::driver::Driver::new().compile("let s = 2.5 + \"a\\n\";\n".to_string()).unwrap_err();
+    assert!(errors[0].to_json().contains("\"column\": 8, \"length\": 11"));
+
+    // Mixing number types suggests the cast that makes them match
+    let errors = crate::driver::Driver::new().compile("let x = 5 + 3000000000;\n".to_string()).unwrap_err();
+    assert_eq!(errors[0].code(), "E0021");
+    assert!(errors[0].to_json().contains("'5 as long'"));
+}
+
+#[test]
+fn test_call_arguments() {
+    let errors = crate::driver::Driver::new().compile("func f(a: int) {\n}\nf(1, 2);\nf(\"x\");\n".to_string()).unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].code(), "E0025");
+    assert!(errors[0].to_json().contains("'f' takes 1 argument, but 2 were given"));
+    assert!(errors[1].to_json().contains("Argument 1 of 'f' expects 'int', found 'string'"));
+}
+
+#[test]
+fn test_immutable_parameter() {
+    let errors = crate::driver::Driver::new().compile("func f(a: int) {\n    a = 1;\n}\nf(2);\n".to_string()).unwrap_err();
+    assert_eq!(errors[0].code(), "E0011");
+    assert!(errors[0].to_json().contains("'a' is a parameter without 'ref', so it can't be changed"));
+    assert!(errors[0].to_json().contains("'ref a'"));
+}
+
+#[test]
+fn test_ref_arguments() {
+    let code = "func bump(ref a: int[]) {\n    a[0] = a[0] + 1;\n}\nbump([1, 2]);\n";
+    let errors = crate::driver::Driver::new().compile(code.to_string()).unwrap_err();
+    assert_eq!(errors[0].code(), "E0027");
+    assert!(errors[0].to_json().contains("\"line\": 4, \"column\": 5, \"length\": 6"));
+}
+
+#[test]
+fn test_writef_arguments() {
+    let errors = crate::driver::Driver::new().compile("writef(\"{} {}\", 1);\nlet f = \"{}\";\nwritef(f, 2);\n".to_string()).unwrap_err();
+    assert_eq!(errors.iter().map(|e| e.code()).collect::<Vec<&str>>(), vec!["E0025", "E0026"]);
+    assert!(errors[0].to_json().contains("The format has 2 '{}', but 1 value was given"));
+}
+
+#[test]
+fn test_len_argument() {
+    for code in ["let n = len();\n", "let n = len(5);\n", "let n = len(\"a\", \"b\");\n"] {
+        let errors = crate::driver::Driver::new().compile(code.to_string()).unwrap_err();
+        assert!(errors[0].to_json().contains("'len' takes a single 'string' or array"), "{}", code);
+    }
+    assert!(crate::driver::Driver::new().compile("let a = [1, 2];\nwrite(len(a) + len(\"ab\"));\n".to_string()).is_ok());
+}
+
+#[test]
+fn test_uninit_fields() {
+    let header = "struct P {\n    x: int,\n    y: int\n}\nvar c = true;\nvar p: P;\n";
+    let compile = |code: &str| crate::driver::Driver::new().compile(format!("{}{}", header, code));
+    assert!(compile("p.x = 1;\np.y = 2;\nwrite(p.x);\n").is_ok());
+
+    // Fields can be read once they have a value, but the struct only has one
+    // once all of its fields do
+    let errors = compile("p.x = 1;\nwrite(p.x);\nwrite(p.y);\n").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_json().contains("Field 'y' of 'p' is never given a value before this"));
+    assert_eq!(compile("p.x = 1;\nlet q = p;\n").unwrap_err()[0].code(), "E0022");
+
+    // A field given a value on only one path might still be without one
+    assert_eq!(compile("if c {\n    p.x = 1;\n}\np.y = 2;\nwrite(p.x);\n").unwrap_err()[0].code(), "E0010");
+}
+
+#[test]
+fn test_array_elements() {
+    let errors = crate::driver::Driver::new().compile("let a = [1, \"x\"];\n".to_string()).unwrap_err();
+    assert!(errors[0].to_json().contains("Element 2 of this array is 'string', but the first element is 'int'"));
+}
+
+#[test]
+fn test_new_struct() {
+    let code = "struct P {\n    x: int\n}\nlet p = new P(1, 2);\nlet q = new P(\"a\");\n".to_string();
+    let errors = crate::driver::Driver::new().compile(code).unwrap_err();
+    assert_eq!(errors[0].code(), "E0025");
+    assert!(errors[0].to_json().contains("'P' has 1 field, but 2 values were given"));
+    assert!(errors[1].to_json().contains("Field 'x' of 'P' is 'int', found 'string'"));
+}
+
+#[test]
+fn test_duplicate_names() {
+    let errors = crate::driver::Driver::new().compile("struct P {\n    x: int,\n    x: int\n}\nfunc f(a: int, a: int) {\n}\nf(1, 2);\n".to_string()).unwrap_err();
+    assert_eq!(errors.iter().map(|e| e.code()).collect::<Vec<&str>>(), vec!["E0016", "E0016"]);
+    assert!(errors[1].to_json().contains("Argument 'a' is already declared"));
+    assert!(errors[1].to_json().contains("first declared here"));
+}
+
+#[test]
+fn test_pause_outside_loop() {
+    let errors = crate::driver::Driver::new().compile("func f() {\n    continue;\n}\nf();\n".to_string()).unwrap_err();
+    assert_eq!(errors[0].code(), "E0017");
+    assert!(crate::driver::Driver::new().compile("while true {\n    break;\n}\n".to_string()).is_ok());
+}
+
+#[test]
+fn test_conditions() {
+    let errors = crate::driver::Driver::new().compile("if 1 {\n    write(1);\n}\nwhile \"a\" {\n}\n".to_string()).unwrap_err();
+    assert!(errors[0].to_json().contains("The condition of this 'if' is 'int', but it has to be a 'bool'"));
+    assert!(errors[1].to_json().contains("The condition of this 'while' is 'string', but it has to be a 'bool'"));
+
+    // 'not' only makes a 'bool' out of a 'bool'
+    let errors = crate::driver::Driver::new().compile("var a = 5;
+if not a {
+}
+while not a {
+}
+".to_string()).unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].to_json().contains("'not' takes a 'bool', found 'int'"));
+}
+
+#[test]
+fn test_void_value() {
+    let errors = crate::driver::Driver::new().compile("func f() {\n}\nlet x = f();\n".to_string()).unwrap_err();
+    assert_eq!(errors[0].code(), "E0018");
+}
+
+#[test]
+fn test_cyclic_struct() {
+    let errors = crate::driver::Driver::new().compile("struct A {\n    b: B\n}\nstruct B {\n    a: A\n}\n".to_string()).unwrap_err();
+    assert_eq!(errors[0].code(), "E0020");
+    assert!(errors[0].to_json().contains("'B' contains 'A', which contains 'B'"));
+
+    // An array of itself is only a pointer, so it has a size
+    assert!(crate::driver::Driver::new().compile("struct Tree {\n    children: Tree[]\n}\n".to_string()).is_ok());
+}
+
+#[test]
+fn test_division_by_zero() {
+    let errors = crate::driver::Driver::new().compile("let x = 1 / 0;\nlet y = 2.0 / 0.0;\n".to_string()).unwrap_err();
+    assert_eq!(errors.iter().map(|e| e.code()).collect::<Vec<&str>>(), vec!["E0023", "E0023"]);
+
+    // The note names the operator that was used
+    let errors = crate::driver::Driver::new().compile("let x = 7 % 0;\n".to_string()).unwrap_err();
+    assert!(errors[0].to_json().contains("The right side of this '%' is always 0"));
+}
+
+#[test]
+fn test_assign_types() {
+    let errors = crate::driver::Driver::new().compile("var x = 1;\nx = \"a\";\n".to_string()).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_json().contains("'x' is 'int', but it is given 'string'"));
+}
+
+#[test]
+fn test_warnings() {
+    use crate::parser::lexer::error::WarningGroup;
+    let code = "func unused() {\n}\nlet x = 1;\nif true {\n    let x = 2;\n    write(x);\n}\nwrite(x);\n".to_string();
+    let mut driver = crate::driver::Driver::new();
+    driver.warnings = WarningGroup::ALL.to_vec();
+    assert!(driver.compile(code).is_ok());
+    assert_eq!(driver.diagnostics.iter().map(|e| e.code()).collect::<Vec<&str>>(), vec!["W0002", "W0001"]);
+}
@@ -0,0 +1,56 @@
+/// Finds the llvm triple of the machine the compiler runs on, which is also
+/// where the programs it builds run unless '--target' says otherwise
+/// # Example
+/// x86_64-unknown-linux-gnu
+pub fn host_triple() -> String {
+    let arch = std::env::consts::ARCH;
+    match std::env::consts::OS {
+        "macos" => format!("{}-apple-macosx", arch),
+        "windows" if cfg!(target_env = "gnu") => format!("{}-pc-windows-gnu", arch),
+        "windows" => format!("{}-pc-windows-msvc", arch),
+        "linux" if cfg!(target_env = "musl") => format!("{}-unknown-linux-musl", arch),
+        "linux" => format!("{}-unknown-linux-gnu", arch),
+        os => format!("{}-unknown-{}", arch, os)
+    }
+}
+
+/// Finds the data layout llvm uses for a triple, or None if it isn't known,
+/// in which case llc falls back to the default of the target
+/// Only 64 bit targets are known, since the generated code sizes pointers as
+/// 8 bytes
+pub fn datalayout(triple: &str) -> Option<&'static str> {
+    let arch = triple.split('-').next().unwrap_or("");
+    let apple = triple.contains("-apple-");
+    let windows = triple.contains("-windows");
+    match arch {
+        "x86_64" if apple => Some("e-m:o-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"),
+        "x86_64" if windows => Some("e-m:w-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"),
+        "x86_64" => Some("e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"),
+        "aarch64" | "arm64" if apple => Some("e-m:o-i64:64-i128:128-n32:64-S128"),
+        "aarch64" if windows => Some("e-m:w-p:64:64-i32:32-i64:64-i128:128-n32:64-S128"),
+        "aarch64" => Some("e-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128"),
+        "riscv64" => Some("e-m:e-p:64:64-i64:64-i128:128-n64-S128"),
+        _ => None
+    }
+}
+
+/// Writes the lines at the top of a module that tell llvm what it is being
+/// compiled for
+/// # Example
+/// target datalayout = "e-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128"
+/// target triple = "aarch64-unknown-linux-gnu"
+pub fn module_header(triple: &str) -> String {
+    let mut header = String::new();
+    if let Some(layout) = datalayout(triple) {
+        header.push_str(format!("target datalayout = \"{}\"\n", layout).as_str());
+    }
+    header.push_str(format!("target triple = \"{}\"\n\n", triple).as_str());
+    return header;
+}
+
+#[test]
+fn test_module_header() {
+    assert!(host_triple().starts_with(std::env::consts::ARCH));
+    assert_eq!(module_header("aarch64-apple-macosx"), "target datalayout = \"e-m:o-i64:64-i128:128-n32:64-S128\"\ntarget triple = \"aarch64-apple-macosx\"\n\n");
+    assert_eq!(module_header("wasm32-unknown-unknown"), "target triple = \"wasm32-unknown-unknown\"\n\n");
+}
@@ -0,0 +1,402 @@
+use crate::parser::generator::Block;
+use std::collections::HashMap;
+
+/// Something wrong with the ir of a function, found before it is written out
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Problem {
+    /// Line of the statement the instruction was generated for, or 0 if it
+    /// wasn't generated for one
+    pub line: usize,
+
+    /// What is wrong
+    pub message: String,
+
+    /// The instruction that is wrong
+    pub instruction: String,
+}
+
+/// Checks the ir of a function the way llc would, but only for the mistakes
+/// the generator can make: operands whose types don't match, blocks that
+/// don't end with a terminator and labels that aren't defined
+/// Globals are given as their names and the types of pointers to them
+pub struct Verifier<'a> {
+    /// Types of the values defined in the function, None when the verifier
+    /// can't tell
+    types: HashMap<String, Option<String>>,
+
+    /// Types of pointers to the globals
+    globals: &'a HashMap<String, String>,
+
+    /// Type the function returns
+    ret: String,
+
+    /// Problems found so far
+    problems: Vec<Problem>,
+}
+
+/// Splits a type off the start of some ir
+/// # Example
+/// [3 x i32]* %0, i32 1 -> ([3 x i32]*, %0, i32 1)
+pub fn split_type(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let mut end = match s.chars().next() {
+        Some('[') | Some('{') | Some('<') => closing(s, 0),
+        _ => s.find([' ', ',', '*', ')']).unwrap_or(s.len())
+    };
+    while s[end..].starts_with('*') {
+        end += 1;
+    }
+    return (&s[..end], s[end..].trim_start());
+}
+
+/// Finds the end of the brackets that start at an index, skipping over
+/// strings and the brackets inside
+fn closing(s: &str, start: usize) -> usize {
+    let mut depth = 0;
+    let mut quoted = false;
+    for (i, c) in s.char_indices().skip_while(|(i, _)| *i < start) {
+        match c {
+            '"' => quoted = !quoted,
+            '[' | '{' | '<' | '(' if !quoted => depth += 1,
+            ']' | '}' | '>' | ')' if !quoted => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            },
+            _ => {}
+        }
+    }
+    return s.len();
+}
+
+/// Splits ir at the commas that aren't inside of brackets
+/// # Example
+/// i32 1, { i8*, i32 } %0 -> [i32 1, { i8*, i32 } %0]
+fn split_list(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < s.len() {
+        match s.as_bytes()[i] {
+            b'[' | b'{' | b'<' | b'(' | b'"' => {
+                i = closing(s, i);
+                continue;
+            },
+            b',' => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+    if !s[start..].trim().is_empty() {
+        parts.push(s[start..].trim());
+    }
+    return parts;
+}
+
+/// Splits a typed operand into its type and its value
+/// # Example
+/// i32* %x.0 -> (i32*, %x.0)
+fn split_operand(s: &str) -> (&str, &str) {
+    let (typ, value) = split_type(s);
+    return (typ, value.trim());
+}
+
+/// The labels an instruction jumps to or, for a phi, comes from
+/// # Example
+/// br i1 %0, label %l0, label %l1 -> [l0, l1]
+fn labels_in(inst: &str) -> Vec<&str> {
+    let mut labels: Vec<&str> = Vec::new();
+    if inst.contains(" = phi ") {
+        for incoming in inst.split('[').skip(1) {
+            if let Some(label) = incoming.split(',').nth(1).and_then(|l| l.split(']').next()) {
+                labels.push(label.trim().trim_start_matches('%'));
+            }
+        }
+        return labels;
+    }
+    for target in inst.split("label %").skip(1) {
+        labels.push(target.split([',', ' ', ']']).next().unwrap_or(""));
+    }
+    return labels;
+}
+
+/// Whether a constant can be a value of a type
+fn constant_fits(value: &str, typ: &str) -> bool {
+    let int = typ.starts_with('i') && typ[1..].chars().all(|c| c.is_ascii_digit());
+    let float = typ == "double" || typ == "float";
+    let digits = value.trim_start_matches('-');
+    if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        return int;
+    }
+    if digits.starts_with("0x") || (digits.starts_with(|c: char| c.is_ascii_digit()) && digits.contains(['.', 'e'])) {
+        return float;
+    }
+    return match value {
+        "true" | "false" => typ == "i1",
+        "null" => typ.ends_with('*'),
+        _ => true
+    };
+}
+
+/// Whether an instruction is an operation on two values of the same type
+fn is_binary(op: &str) -> bool {
+    return ["add", "sub", "mul", "sdiv", "udiv", "srem", "urem", "and", "or", "xor", "shl", "lshr", "ashr",
+        "fadd", "fsub", "fmul", "fdiv", "frem"].contains(&op);
+}
+
+/// Whether an instruction converts a value to another type
+fn is_cast(op: &str) -> bool {
+    return ["sext", "zext", "trunc", "sitofp", "uitofp", "fptosi", "fptoui", "fpext", "fptrunc", "bitcast",
+        "ptrtoint", "inttoptr"].contains(&op);
+}
+
+/// Removes the debug location at the end of an instruction
+/// # Example
+/// ret i32 0, !dbg !4 -> ret i32 0
+fn without_location(inst: &str) -> &str {
+    let inst = inst.trim();
+    return match inst.find(", !dbg ") {
+        Some(at) => &inst[..at],
+        None => inst
+    };
+}
+
+/// Finds the return type and the arguments of a function from its header
+/// # Example
+/// define void @gz_f(%P* sret(%P) %.ret, i32 %a.0.arg) {
+/// -> (void, [(%P*, %.ret), (i32, %a.0.arg)])
+fn parse_header(header: &str) -> (String, Vec<(String, String)>) {
+    let rest = header.trim().trim_start_matches("define ");
+    let (ret, rest) = split_type(rest);
+    let mut args = Vec::new();
+    if let Some(open) = rest.find('(') {
+        let inside = &rest[open + 1..closing(rest, open) - 1];
+        for arg in split_list(inside) {
+            let (typ, rest) = split_type(arg);
+            if let Some(name) = rest.rsplit(' ').next().filter(|n| n.starts_with('%')) {
+                args.push((typ.to_string(), name.to_string()));
+            }
+        }
+    }
+    return (ret.to_string(), args);
+}
+
+/// Checks a finished function, giving back what is wrong with it
+pub fn verify_function(header: &str, allocas: &str, blocks: &[&Block], globals: &HashMap<String, String>) -> Vec<Problem> {
+    let (ret, args) = parse_header(header);
+    let mut verifier = Verifier {types: HashMap::new(), globals: globals, ret: ret, problems: Vec::new()};
+    for (typ, name) in args {
+        verifier.types.insert(name, Some(typ));
+    }
+
+    // Every value has to be known before it is checked, since a phi can use
+    // a value defined in a later block
+    let mut insts: Vec<(&str, usize)> = allocas.lines().map(|l| (without_location(l), 0)).collect();
+    for block in blocks.iter() {
+        for (inst, line) in block.code.lines().zip(block.lines.iter()) {
+            if !inst.trim_start().starts_with(';') {
+                insts.push((without_location(inst), *line));
+            }
+        }
+    }
+    for (inst, _) in insts.iter() {
+        if let Some((name, body)) = inst.split_once(" = ").filter(|(name, _)| name.starts_with('%')) {
+            let typ = verifier.type_of(body);
+            verifier.types.insert(name.to_string(), typ);
+        }
+    }
+    for (inst, line) in insts.iter() {
+        verifier.check(inst, *line);
+    }
+
+    // Every block ends with a terminator and only jumps to blocks in the
+    // function
+    let names: Vec<&str> = blocks.iter().map(|b| b.name.as_str()).collect();
+    for block in blocks.iter() {
+        let last = block.code.lines().zip(block.lines.iter()).filter(|(l, _)| !l.trim_start().starts_with(';')).last();
+        let (inst, line) = match last {
+            Some((inst, line)) => (without_location(inst), *line),
+            None => ("", 0)
+        };
+        let op = inst.split(' ').next().unwrap_or("");
+        if !["br", "ret", "switch", "unreachable"].contains(&op) {
+            verifier.problem(line, format!("The block '{}' doesn't end with a 'br', 'ret', 'switch' or 'unreachable'", block.name), inst);
+        }
+    }
+    for (inst, line) in insts.iter() {
+        for label in labels_in(inst) {
+            if !names.contains(&label) {
+                verifier.problem(*line, format!("The label '%{}' isn't defined in this function", label), inst);
+            }
+        }
+    }
+    return verifier.problems;
+}
+
+impl<'a> Verifier<'a> {
+    /// Records a problem
+    fn problem(&mut self, line: usize, message: String, inst: &str) {
+        self.problems.push(Problem {line: line, message: message, instruction: inst.to_string()});
+    }
+
+    /// Finds the type of the value an instruction defines, from everything
+    /// after the '='
+    fn type_of(&self, body: &str) -> Option<String> {
+        let (op, rest) = body.split_once(' ').unwrap_or((body, ""));
+        let typ = match op {
+            "alloca" => format!("{}*", split_type(rest).0),
+            "icmp" | "fcmp" => "i1".to_string(),
+            "load" | "phi" | "insertvalue" => split_type(rest).0.to_string(),
+            "select" => split_type(split_list(rest).get(1)?).0.to_string(),
+            "call" => split_type(rest).0.to_string(),
+            _ if is_binary(op) => split_type(rest).0.to_string(),
+            _ if is_cast(op) => rest.rsplit_once(" to ")?.1.trim().to_string(),
+            _ => return None
+        };
+        return Some(typ);
+    }
+
+    /// Checks that a value has a type, giving back what is wrong if it
+    /// doesn't
+    fn expect(&self, value: &str, typ: &str, op: &str) -> Option<String> {
+        let found = if value.starts_with('%') {
+            match self.types.get(value) {
+                Some(Some(found)) => found.clone(),
+                Some(None) => return None,
+                None => return Some(format!("'{}' is used but never defined", value))
+            }
+        } else if value.starts_with('@') {
+            self.globals.get(value)?.clone()
+        } else if !constant_fits(value, typ) {
+            return Some(format!("'{}' isn't a constant of type '{}'", value, typ));
+        } else {
+            return None;
+        };
+        if found != typ {
+            return Some(format!("'{}' is '{}' but '{}' expects '{}'", value, found, op, typ));
+        }
+        return None;
+    }
+
+    /// Checks the operands of an instruction
+    fn check(&mut self, inst: &str, line: usize) {
+        let body = match inst.split_once(" = ").filter(|(name, _)| name.starts_with('%')) {
+            Some((_, body)) => body,
+            None => inst
+        };
+        let (op, rest) = body.split_once(' ').unwrap_or((body, ""));
+        let parts = split_list(rest);
+        let mut expected: Vec<(String, String)> = Vec::new();
+        match op {
+            "load" if parts.len() == 2 => {
+                let (ptr_typ, ptr) = split_operand(parts[1]);
+                expected.push((ptr.to_string(), ptr_typ.to_string()));
+                expected.push((ptr.to_string(), format!("{}*", parts[0])));
+            },
+            "store" if parts.len() == 2 => {
+                let (typ, value) = split_operand(parts[0]);
+                let (ptr_typ, ptr) = split_operand(parts[1]);
+                expected.push((value.to_string(), typ.to_string()));
+                expected.push((ptr.to_string(), format!("{}*", typ)));
+                expected.push((ptr.to_string(), ptr_typ.to_string()));
+            },
+            "icmp" | "fcmp" => {
+                let (_, rest) = rest.split_once(' ').unwrap_or(("", ""));
+                let parts = split_list(rest);
+                if parts.len() == 2 {
+                    let (typ, left) = split_operand(parts[0]);
+                    expected.push((left.to_string(), typ.to_string()));
+                    expected.push((parts[1].to_string(), typ.to_string()));
+                }
+            },
+            _ if is_binary(op) && parts.len() == 2 => {
+                let (typ, left) = split_operand(parts[0]);
+                expected.push((left.to_string(), typ.to_string()));
+                expected.push((parts[1].to_string(), typ.to_string()));
+            },
+            _ if is_cast(op) => {
+                if let Some((from, _)) = rest.rsplit_once(" to ") {
+                    let (typ, value) = split_operand(from);
+                    expected.push((value.to_string(), typ.to_string()));
+                }
+            },
+            "phi" => {
+                let typ = split_type(rest).0;
+                for incoming in rest.split('[').skip(1) {
+                    let value = incoming.split(',').next().unwrap_or("").trim();
+                    expected.push((value.to_string(), typ.to_string()));
+                }
+            },
+            "select" if parts.len() == 3 => {
+                for part in parts.iter() {
+                    let (typ, value) = split_operand(part);
+                    expected.push((value.to_string(), typ.to_string()));
+                }
+                expected.push((split_operand(parts[0]).1.to_string(), "i1".to_string()));
+                expected.push((split_operand(parts[2]).1.to_string(), split_type(parts[1]).0.to_string()));
+            },
+            "getelementptr" => {
+                let parts = split_list(rest.trim_start_matches("inbounds "));
+                if parts.len() >= 2 {
+                    let (ptr_typ, ptr) = split_operand(parts[1]);
+                    expected.push((ptr.to_string(), ptr_typ.to_string()));
+                    expected.push((ptr.to_string(), format!("{}*", parts[0])));
+                }
+            },
+            "extractvalue" | "insertvalue" if parts.len() >= 2 => {
+                for part in parts[..parts.len() - 1].iter() {
+                    let (typ, value) = split_operand(part);
+                    expected.push((value.to_string(), typ.to_string()));
+                }
+            },
+            "br" if parts.len() == 3 => {
+                let (typ, cond) = split_operand(parts[0]);
+                expected.push((cond.to_string(), typ.to_string()));
+                expected.push((cond.to_string(), "i1".to_string()));
+            },
+            "switch" => {
+                let (typ, value) = split_operand(parts.first().copied().unwrap_or(""));
+                expected.push((value.to_string(), typ.to_string()));
+            },
+            "ret" => {
+                let (typ, value) = split_operand(rest);
+                if typ != self.ret {
+                    self.problem(line, format!("The function returns '{}' but this gives back '{}'", self.ret, typ), inst);
+                } else if !value.is_empty() {
+                    expected.push((value.to_string(), typ.to_string()));
+                }
+            },
+            _ => {}
+        }
+
+        for (value, typ) in expected {
+            if let Some(message) = self.expect(value.as_str(), typ.as_str(), op) {
+                self.problem(line, message, inst);
+                return;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_verify() {
+    let globals = HashMap::from([("@x.0".to_string(), "i32*".to_string())]);
+    let entry = Block {name: "entry".to_string(), code: "\t%0 = load i32, i32* @x.0\n\t%1 = sext i32 %0 to i64\n\t%2 = add i32 %1, 2\n\tbr i1 %2, label %l0, label %l1\n".to_string(), lines: vec![1, 1, 2, 3]};
+    let l0 = Block {name: "l0".to_string(), code: "\tstore i32 2.5, i32* @x.0\n".to_string(), lines: vec![4]};
+    let problems = verify_function("define i32 @main() {", "", &[&entry, &l0], &globals);
+    let messages: Vec<(usize, &str)> = problems.iter().map(|p| (p.line, p.message.as_str())).collect();
+    assert_eq!(messages, vec![
+        (2, "'%1' is 'i64' but 'add' expects 'i32'"),
+        (3, "'%2' is 'i32' but 'br' expects 'i1'"),
+        (4, "'2.5' isn't a constant of type 'i32'"),
+        (4, "The block 'l0' doesn't end with a 'br', 'ret', 'switch' or 'unreachable'"),
+        (3, "The label '%l1' isn't defined in this function")
+    ]);
+
+    let fine = Block {name: "entry".to_string(), code: "\t%0 = call %.Str @gz_f(%P* sret(%P) %.ret, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.empty, i32 0, i32 0))\n\t%1 = extractvalue %.Str %0, 1\n\tret i32 %1\n".to_string(), lines: vec![1, 1, 1]};
+    assert_eq!(verify_function("define i32 @gz_g(%P* sret(%P) %.ret) {", "", &[&fine], &globals), Vec::new());
+}